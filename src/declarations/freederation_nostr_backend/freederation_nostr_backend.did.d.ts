@@ -2,17 +2,118 @@ import type { Principal } from '@dfinity/principal';
 import type { ActorMethod } from '@dfinity/agent';
 import type { IDL } from '@dfinity/candid';
 
-export interface SIGNATURE_INFO {
-  'verifying_key' : string,
-  'signature_str' : string,
+export interface AdmissionPolicy {
+  'min_pow' : number,
+  'max_content_length' : [] | [number],
+}
+export type ApiError = { 'Internal' : string } |
+  { 'Keys' : string } |
+  { 'Parse' : string } |
+  { 'Rejected' : string } |
+  { 'Unauthorized' : null } |
+  { 'InvalidEvent' : string } |
+  { 'Signer' : string };
+export interface EventCount { 'count' : bigint, 'approximate' : boolean }
+export interface EventData {
+  'id' : string,
+  'sig' : string,
+  'content' : string,
+  'kind' : number,
+  'tags' : Array<Array<string>>,
+  'pubkey' : string,
+  'created_at' : bigint,
+}
+export interface EventTemplate {
+  'content' : string,
+  'kind' : number,
+  'tags' : Array<Array<string>>,
+  'created_at' : [] | [bigint],
+}
+export interface Fee {
+  'period' : [] | [bigint],
+  'unit' : string,
+  'amount' : bigint,
+  'kinds' : [] | [Uint16Array | number[]],
+}
+export interface Fees {
+  'subscription' : Array<Fee>,
+  'admission' : Array<Fee>,
+  'publication' : Array<Fee>,
+}
+export interface HttpRequest {
+  'url' : string,
+  'method' : string,
+  'body' : Uint8Array | number[],
+  'headers' : Array<[string, string]>,
+}
+export interface HttpResponse {
+  'body' : Uint8Array | number[],
+  'headers' : Array<[string, string]>,
+  'status_code' : number,
+}
+export interface PurgeStats {
+  'purged_total' : bigint,
+  'runs' : bigint,
+  'last_purged' : bigint,
+  'last_run' : [] | [bigint],
+}
+export interface RelayInfo {
+  'contact' : [] | [string],
+  'fees' : Fees,
+  'name' : string,
+  'description' : string,
+  'pubkey' : [] | [string],
+}
+export type Result = { 'Ok' : string } |
+  { 'Err' : ApiError };
+export type Result_1 = { 'Ok' : EventCount } |
+  { 'Err' : ApiError };
+export type Result_2 = { 'Ok' : Array<string> } |
+  { 'Err' : ApiError };
+export type Result_3 = { 'Ok' : [] | [Principal] } |
+  { 'Err' : ApiError };
+export type Result_4 = { 'Ok' : null } |
+  { 'Err' : ApiError };
+export type Result_5 = { 'Ok' : Array<EventData> } |
+  { 'Err' : ApiError };
+export type Result_6 = { 'Ok' : EventData } |
+  { 'Err' : ApiError };
+export type Result_7 = { 'Ok' : boolean } |
+  { 'Err' : ApiError };
+export interface SignerConfig {
+  'sign_cycles' : bigint,
+  'signers' : [] | [Array<Principal>],
+  'canister_id' : Principal,
+  'key_name' : string,
 }
 export interface _SERVICE {
-  'generate_key' : ActorMethod<[], string>,
-  'greet' : ActorMethod<[string], string>,
-  'rng_seed' : ActorMethod<[], string>,
-  'schnorr_signature' : ActorMethod<[string, string], SIGNATURE_INFO>,
-  'update_rng_seed' : ActorMethod<[string], undefined>,
-  'validate_schnorr' : ActorMethod<[string, string, string], boolean>,
+  'bind_challenge' : ActorMethod<[], Result>,
+  'bind_key' : ActorMethod<[EventData], Result>,
+  'count_events' : ActorMethod<[string], Result_1>,
+  'generate_key' : ActorMethod<[], Result>,
+  'get_nip05' : ActorMethod<[[] | [Principal]], [] | [string]>,
+  'get_npub' : ActorMethod<[[] | [Principal]], Result>,
+  'get_relay_info' : ActorMethod<[], RelayInfo>,
+  'get_relay_policy' : ActorMethod<[], AdmissionPolicy>,
+  'greet' : ActorMethod<[string], Result>,
+  'handle_message' : ActorMethod<[string], Array<string>>,
+  'handle_query_message' : ActorMethod<[string], Array<string>>,
+  'http_request' : ActorMethod<[HttpRequest], HttpResponse>,
+  'list_keys' : ActorMethod<[[] | [Principal]], Result_2>,
+  'lookup_npub' : ActorMethod<[string], Result_3>,
+  'publish_event' : ActorMethod<[EventData], Result_4>,
+  'purge_stats' : ActorMethod<[], PurgeStats>,
+  'query_events' : ActorMethod<[string, [] | [boolean]], Result_5>,
+  'register_nip05' : ActorMethod<
+    [string, [] | [string], Array<string>],
+    Result
+  >,
+  'set_relay_info' : ActorMethod<[RelayInfo], Result_4>,
+  'set_relay_policy' : ActorMethod<[AdmissionPolicy], Result_4>,
+  'sign_event' : ActorMethod<[EventTemplate], Result_6>,
+  'unbind_key' : ActorMethod<[string], Result_7>,
+  'unregister_nip05' : ActorMethod<[], [] | [string]>,
+  'validate_schnorr' : ActorMethod<[string, string, string], Result_7>,
 }
 export declare const idlFactory: IDL.InterfaceFactory;
 export declare const init: (args: { IDL: typeof IDL }) => IDL.Type[];
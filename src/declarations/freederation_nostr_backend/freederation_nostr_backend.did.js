@@ -1,23 +1,141 @@
 export const idlFactory = ({ IDL }) => {
-  const SIGNATURE_INFO = IDL.Record({
-    'verifying_key' : IDL.Text,
-    'signature_str' : IDL.Text,
+  const SignerConfig = IDL.Record({
+    'sign_cycles' : IDL.Nat64,
+    'signers' : IDL.Opt(IDL.Vec(IDL.Principal)),
+    'canister_id' : IDL.Principal,
+    'key_name' : IDL.Text,
   });
+  const ApiError = IDL.Variant({
+    'Internal' : IDL.Text,
+    'Keys' : IDL.Text,
+    'Parse' : IDL.Text,
+    'Rejected' : IDL.Text,
+    'Unauthorized' : IDL.Null,
+    'InvalidEvent' : IDL.Text,
+    'Signer' : IDL.Text,
+  });
+  const Result = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : ApiError });
+  const EventData = IDL.Record({
+    'id' : IDL.Text,
+    'sig' : IDL.Text,
+    'content' : IDL.Text,
+    'kind' : IDL.Nat16,
+    'tags' : IDL.Vec(IDL.Vec(IDL.Text)),
+    'pubkey' : IDL.Text,
+    'created_at' : IDL.Nat64,
+  });
+  const EventCount = IDL.Record({
+    'count' : IDL.Nat64,
+    'approximate' : IDL.Bool,
+  });
+  const Result_1 = IDL.Variant({ 'Ok' : EventCount, 'Err' : ApiError });
+  const Fee = IDL.Record({
+    'period' : IDL.Opt(IDL.Nat64),
+    'unit' : IDL.Text,
+    'amount' : IDL.Nat64,
+    'kinds' : IDL.Opt(IDL.Vec(IDL.Nat16)),
+  });
+  const Fees = IDL.Record({
+    'subscription' : IDL.Vec(Fee),
+    'admission' : IDL.Vec(Fee),
+    'publication' : IDL.Vec(Fee),
+  });
+  const RelayInfo = IDL.Record({
+    'contact' : IDL.Opt(IDL.Text),
+    'fees' : Fees,
+    'name' : IDL.Text,
+    'description' : IDL.Text,
+    'pubkey' : IDL.Opt(IDL.Text),
+  });
+  const AdmissionPolicy = IDL.Record({
+    'min_pow' : IDL.Nat8,
+    'max_content_length' : IDL.Opt(IDL.Nat32),
+  });
+  const HttpRequest = IDL.Record({
+    'url' : IDL.Text,
+    'method' : IDL.Text,
+    'body' : IDL.Vec(IDL.Nat8),
+    'headers' : IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+  });
+  const HttpResponse = IDL.Record({
+    'body' : IDL.Vec(IDL.Nat8),
+    'headers' : IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+    'status_code' : IDL.Nat16,
+  });
+  const Result_2 = IDL.Variant({ 'Ok' : IDL.Vec(IDL.Text), 'Err' : ApiError });
+  const Result_3 = IDL.Variant({
+    'Ok' : IDL.Opt(IDL.Principal),
+    'Err' : ApiError,
+  });
+  const Result_4 = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : ApiError });
+  const PurgeStats = IDL.Record({
+    'purged_total' : IDL.Nat64,
+    'runs' : IDL.Nat64,
+    'last_purged' : IDL.Nat64,
+    'last_run' : IDL.Opt(IDL.Nat64),
+  });
+  const Result_5 = IDL.Variant({ 'Ok' : IDL.Vec(EventData), 'Err' : ApiError });
+  const EventTemplate = IDL.Record({
+    'content' : IDL.Text,
+    'kind' : IDL.Nat16,
+    'tags' : IDL.Vec(IDL.Vec(IDL.Text)),
+    'created_at' : IDL.Opt(IDL.Nat64),
+  });
+  const Result_6 = IDL.Variant({ 'Ok' : EventData, 'Err' : ApiError });
+  const Result_7 = IDL.Variant({ 'Ok' : IDL.Bool, 'Err' : ApiError });
   return IDL.Service({
-    'generate_key' : IDL.Func([], [IDL.Text], ['query']),
-    'greet' : IDL.Func([IDL.Text], [IDL.Text], ['query']),
-    'rng_seed' : IDL.Func([], [IDL.Text], ['query']),
-    'schnorr_signature' : IDL.Func(
-        [IDL.Text, IDL.Text],
-        [SIGNATURE_INFO],
+    'bind_challenge' : IDL.Func([], [Result], []),
+    'bind_key' : IDL.Func([EventData], [Result], []),
+    'count_events' : IDL.Func([IDL.Text], [Result_1], ['query']),
+    'generate_key' : IDL.Func([], [Result], []),
+    'get_nip05' : IDL.Func(
+        [IDL.Opt(IDL.Principal)],
+        [IDL.Opt(IDL.Text)],
         ['query'],
       ),
-    'update_rng_seed' : IDL.Func([IDL.Text], [], []),
+    'get_npub' : IDL.Func([IDL.Opt(IDL.Principal)], [Result], []),
+    'get_relay_info' : IDL.Func([], [RelayInfo], ['query']),
+    'get_relay_policy' : IDL.Func([], [AdmissionPolicy], ['query']),
+    'greet' : IDL.Func([IDL.Text], [Result], ['query']),
+    'handle_message' : IDL.Func([IDL.Text], [IDL.Vec(IDL.Text)], []),
+    'handle_query_message' : IDL.Func(
+        [IDL.Text],
+        [IDL.Vec(IDL.Text)],
+        ['query'],
+      ),
+    'http_request' : IDL.Func([HttpRequest], [HttpResponse], ['query']),
+    'list_keys' : IDL.Func([IDL.Opt(IDL.Principal)], [Result_2], ['query']),
+    'lookup_npub' : IDL.Func([IDL.Text], [Result_3], ['query']),
+    'publish_event' : IDL.Func([EventData], [Result_4], []),
+    'purge_stats' : IDL.Func([], [PurgeStats], ['query']),
+    'query_events' : IDL.Func(
+        [IDL.Text, IDL.Opt(IDL.Bool)],
+        [Result_5],
+        ['query'],
+      ),
+    'register_nip05' : IDL.Func(
+        [IDL.Text, IDL.Opt(IDL.Text), IDL.Vec(IDL.Text)],
+        [Result],
+        [],
+      ),
+    'set_relay_info' : IDL.Func([RelayInfo], [Result_4], []),
+    'set_relay_policy' : IDL.Func([AdmissionPolicy], [Result_4], []),
+    'sign_event' : IDL.Func([EventTemplate], [Result_6], []),
+    'unbind_key' : IDL.Func([IDL.Text], [Result_7], []),
+    'unregister_nip05' : IDL.Func([], [IDL.Opt(IDL.Text)], []),
     'validate_schnorr' : IDL.Func(
         [IDL.Text, IDL.Text, IDL.Text],
-        [IDL.Bool],
+        [Result_7],
         ['query'],
       ),
   });
 };
-export const init = ({ IDL }) => { return []; };
+export const init = ({ IDL }) => {
+  const SignerConfig = IDL.Record({
+    'sign_cycles' : IDL.Nat64,
+    'signers' : IDL.Opt(IDL.Vec(IDL.Principal)),
+    'canister_id' : IDL.Principal,
+    'key_name' : IDL.Text,
+  });
+  return [IDL.Opt(SignerConfig)];
+};
@@ -0,0 +1,194 @@
+use candid::{CandidType, Deserialize};
+
+use crate::identity::IdentityError;
+use crate::nostr::event_error::EventDataError;
+use crate::relay::{PolicyError, RelayError};
+use crate::signing::AsymmetricKeyError;
+use crate::store::{BindingError, NameError, StoreError};
+use crate::util::basecore::ParseError;
+
+/// Error of the canister endpoints, the `Err` of every Candid `Result`
+///
+/// Each variant keeps the message of the underlying error.
+#[derive(thiserror::Error, CandidType, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ApiError {
+
+    #[error("Parse error: {0}")]
+    Parse(String),
+
+    #[error("Keys error: {0}")]
+    Keys(String),
+
+    #[error("Invalid event: {0}")]
+    InvalidEvent(String),
+
+    #[error("Rejected: {0}")]
+    Rejected(String),
+
+    #[error("Signer error: {0}")]
+    Signer(String),
+
+    #[error("Caller is not authorized")]
+    Unauthorized,
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Parse(e.to_string())
+    }
+}
+
+impl From<AsymmetricKeyError> for ApiError {
+    fn from(e: AsymmetricKeyError) -> Self {
+        Self::Keys(e.to_string())
+    }
+}
+
+impl From<EventDataError> for ApiError {
+    fn from(e: EventDataError) -> Self {
+        match e {
+            EventDataError::Parse(e) => e.into(),
+            EventDataError::Keys(e) => e.into(),
+            e => Self::InvalidEvent(e.to_string()),
+        }
+    }
+}
+
+impl From<StoreError> for ApiError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            StoreError::InvalidEvent(e) => e.into(),
+            e => Self::Rejected(e.to_string()),
+        }
+    }
+}
+
+impl From<PolicyError> for ApiError {
+    fn from(e: PolicyError) -> Self {
+        Self::Rejected(e.to_string())
+    }
+}
+
+impl From<RelayError> for ApiError {
+    fn from(e: RelayError) -> Self {
+        match e {
+            RelayError::Policy(e) => e.into(),
+            RelayError::Store(e) => e.into(),
+            e => Self::Rejected(e.to_string()),
+        }
+    }
+}
+
+impl From<NameError> for ApiError {
+    fn from(e: NameError) -> Self {
+        match e {
+            NameError::NameTaken(_) => Self::Rejected(e.to_string()),
+            e => Self::Parse(e.to_string()),
+        }
+    }
+}
+
+impl From<BindingError> for ApiError {
+    fn from(e: BindingError) -> Self {
+        match e {
+            BindingError::Anonymous | BindingError::NotBound => Self::Unauthorized,
+            BindingError::InvalidEvent(e) => e.into(),
+            e => Self::Rejected(e.to_string()),
+        }
+    }
+}
+
+impl From<IdentityError> for ApiError {
+    fn from(e: IdentityError) -> Self {
+        match e {
+            IdentityError::Anonymous => Self::Unauthorized,
+            IdentityError::Keys(e) => e.into(),
+            IdentityError::Event(e) => e.into(),
+            e => Self::Signer(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::{Decode, Encode};
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::nostr::event_data::EventData;
+    use crate::nostr::event_id::EventId;
+    use crate::nostr::event_kind::Kind;
+    use crate::nostr::tag::TagData;
+    use crate::rng::CryptoHashRng;
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, NostrPubKey};
+    use crate::util::jsonutil::JsonUtil;
+
+    fn event() -> EventData {
+        let ecda = AsymmetricKeyImpl();
+        let skey = ecda.parse_secret_key_from_hex("6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e").unwrap();
+        EventBuilder::text_note("candid", [TagData::custom("t", ["nostr"])])
+            .sign(&ecda.new_keypair(skey).unwrap(), &mut CryptoHashRng::from_seed([3u8; 32]))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_candid_roundtrip() {
+        let event = event();
+        let bytes = Encode!(&event).unwrap();
+        let decoded = Decode!(bytes.as_slice(), EventData).unwrap();
+        assert_eq!(decoded, event);
+        assert!(decoded.verify().is_ok());
+
+        let (id, pubkey, kind, tag) = Decode!(
+            Encode!(&event.id.to_hex(), &event.pubkey.to_string(), &1u16, &vec!["t", "nostr"]).unwrap().as_slice(),
+            EventId,
+            NostrPubKey,
+            Kind,
+            TagData
+        )
+        .unwrap();
+        assert_eq!(id, event.id);
+        assert_eq!(pubkey, event.pubkey);
+        assert_eq!(kind, Kind::TextNote);
+        assert_eq!(tag, event.tags[0]);
+
+        let result: Result<EventData, ApiError> = Err(ApiError::Unauthorized);
+        let bytes = Encode!(&result).unwrap();
+        assert_eq!(Decode!(bytes.as_slice(), Result<EventData, ApiError>).unwrap(), result);
+    }
+
+    #[test]
+    fn test_error_mapping() {
+        let mut event = event();
+        event.content = String::from("tampered");
+        assert!(matches!(ApiError::from(event.verify().unwrap_err()), ApiError::InvalidEvent(_)));
+
+        assert!(matches!(ApiError::from(EventData::from_json("{}").unwrap_err()), ApiError::Parse(_)));
+        assert!(matches!(
+            ApiError::from(AsymmetricKeyImpl().parse_public_key("zz").map(|_| ()).unwrap_err()),
+            ApiError::Keys(_)
+        ));
+        assert_eq!(ApiError::from(IdentityError::Anonymous), ApiError::Unauthorized);
+        assert_eq!(
+            ApiError::from(PolicyError::MissingPowCommitment(8)),
+            ApiError::Rejected(String::from("pow: missing nonce tag, difficulty 8 is required"))
+        );
+        assert!(matches!(ApiError::from(NameError::NameTaken(String::from("bob"))), ApiError::Rejected(_)));
+        assert!(matches!(ApiError::from(NameError::TooManyRelays), ApiError::Parse(_)));
+        assert_eq!(ApiError::from(BindingError::Anonymous), ApiError::Unauthorized);
+        assert_eq!(ApiError::from(BindingError::NotBound), ApiError::Unauthorized);
+        assert!(matches!(ApiError::from(BindingError::NoChallenge), ApiError::Rejected(_)));
+        assert!(matches!(ApiError::from(RelayError::from(PolicyError::ContentTooLong(5))), ApiError::Rejected(_)));
+        assert!(matches!(ApiError::from(RelayError::ReadOnly), ApiError::Rejected(_)));
+    }
+}
@@ -0,0 +1,11 @@
+//! Candid interface of the canister
+//!
+//! Endpoints exchange [`EventData`](crate::nostr::event_data::EventData) and its parts as
+//! Candid values and fail with an [`ApiError`] instead of trapping.
+
+pub mod api_error;
+
+pub use self::api_error::ApiError;
+
+/// Result of the canister endpoints
+pub type ApiResult<T> = Result<T, ApiError>;
@@ -7,6 +7,11 @@ use k256::sha2::digest::{Output};
 
 use hex_conservative::{FromHex, HexToBytesError};
 
+use crate::signing::{DataField, NostrPubKey, NostrSecretKey};
+
+pub mod nip04;
+pub mod nip44;
+
 
 pub type Sha256Hash = Sha256;
 pub type Sha256ParseError = HexToBytesError;
@@ -17,4 +22,15 @@ pub fn sha256_from_hex(hexstr:&str) -> Result<Sha256Hash, Sha256ParseError>
 {
     let bytesbuffer: Vec<u8> = FromHex::from_hex(hexstr)?;
     Ok(Sha256Hash::new_with_prefix(bytesbuffer.as_slice()))
-}
\ No newline at end of file
+}
+
+/// ECDH shared secret, the x coordinate of `secret_key * public_key`
+///
+/// The x-only `public_key` is lifted to its even point, the shared secret is not hashed.
+pub fn shared_secret(secret_key: &NostrSecretKey, public_key: &NostrPubKey) -> DataField
+{
+    let shared = k256::ecdh::diffie_hellman(secret_key.0.to_nonzero_scalar(), public_key.0.as_affine());
+    let mut secret: DataField = [0u8; 32];
+    secret.copy_from_slice(&shared.raw_secret_bytes()[..]);
+    secret
+}
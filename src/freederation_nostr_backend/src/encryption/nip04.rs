@@ -0,0 +1,205 @@
+//! NIP04 encrypted direct messages
+//!
+//! AES-256-CBC keyed with the ECDH shared secret, the content is
+//! `base64(ciphertext)?iv=base64(iv)`.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/04.md>
+
+use std::string::FromUtf8Error;
+
+use aes::Aes256;
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use crate::encryption::shared_secret;
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_kind::Kind;
+use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, CryptoRngCore, NostrPubKey, NostrSecretKey};
+
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+/// Size of the AES-CBC initialization vector
+pub const IV_SIZE: usize = 16;
+
+/// Separator between the ciphertext and the IV
+const IV_SEPARATOR: &str = "?iv=";
+
+/// NIP04 error
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Nip04Error {
+
+    #[error("Invalid content format")]
+    InvalidContentFormat,
+
+    #[error("Base64 decode error: {0}")]
+    Base64Decode(#[from] base64::DecodeError),
+
+    #[error("Invalid IV size")]
+    InvalidIv,
+
+    #[error("Wrong key or corrupted ciphertext")]
+    Decryption,
+
+    #[error("Utf8 decoding error: {0}")]
+    FromUTF8(#[from] FromUtf8Error),
+
+    #[error("Not an encrypted direct message")]
+    WrongKind,
+
+    #[error("Missing receiver public key")]
+    MissingReceiver,
+}
+
+/// Encrypt `plaintext` from `secret_key` to `public_key` with a random IV
+pub fn encrypt<RG>(secret_key: &NostrSecretKey, public_key: &NostrPubKey, plaintext: &str, rngcore: &mut RG) -> String
+where RG: CryptoRngCore
+{
+    let mut iv = [0u8; IV_SIZE];
+    rngcore.fill_bytes(&mut iv);
+    encrypt_with_iv(secret_key, public_key, plaintext, &iv)
+}
+
+/// Encrypt `plaintext` from `secret_key` to `public_key` with the given IV
+///
+/// An IV must never be reused with the same pair of keys, prefer [`encrypt`].
+pub fn encrypt_with_iv(secret_key: &NostrSecretKey, public_key: &NostrPubKey, plaintext: &str, iv: &[u8; IV_SIZE]) -> String
+{
+    let key = shared_secret(secret_key, public_key);
+    let ciphertext = Aes256CbcEnc::new(&key.into(), iv.into()).encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes());
+    format!("{}{}{}", BASE64.encode(ciphertext), IV_SEPARATOR, BASE64.encode(iv))
+}
+
+/// Decrypt a NIP04 `content` exchanged between `secret_key` and `public_key`
+pub fn decrypt(secret_key: &NostrSecretKey, public_key: &NostrPubKey, content: &str) -> Result<String, Nip04Error>
+{
+    let (ciphertext, iv) = content.split_once(IV_SEPARATOR).ok_or(Nip04Error::InvalidContentFormat)?;
+    let ciphertext: Vec<u8> = BASE64.decode(ciphertext)?;
+    let iv: [u8; IV_SIZE] = BASE64.decode(iv)?.try_into().map_err(|_| Nip04Error::InvalidIv)?;
+
+    let key = shared_secret(secret_key, public_key);
+    let plaintext = Aes256CbcDec::new(&key.into(), &iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext.as_slice())
+        .map_err(|_| Nip04Error::Decryption)?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// Decrypt the content of a kind 4 event, as its author or as its receiver
+pub fn decrypt_direct_message(secret_key: &NostrSecretKey, event: &EventData) -> Result<String, Nip04Error>
+{
+    if event.kind != Kind::EncryptedDirectMessage {
+        return Err(Nip04Error::WrongKind);
+    }
+
+    let ecda = AsymmetricKeyImpl();
+    let own_key = ecda
+        .new_keypair(secret_key.0.clone())
+        .map(|keypair| NostrPubKey(ecda.pubkey_from_pair(&keypair)))
+        .map_err(|_| Nip04Error::Decryption)?;
+
+    let counterpart: NostrPubKey = if event.pubkey == own_key {
+        event.public_keys().next().ok_or(Nip04Error::MissingReceiver)?
+    } else {
+        event.pubkey.clone()
+    };
+    decrypt(secret_key, &counterpart, event.content.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::rng::CryptoHashRng;
+
+    const SECRET_KEY_1: &str = "6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+    const SECRET_KEY_2: &str = "7b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+    const PUBLIC_KEY_2: &str = "f572561e79d30c334e7ce864a2055e887eafdf5dad71242adc0c43567cc269f8";
+
+    /// Published NIP04 vector, encrypted by key 1 to key 2
+    const VECTOR_CONTENT: &str = "dJc+WbBgaFCD2/kfg1XCWJParplBDxnZIdJGZ6FCTOg=?iv=M6VxRPkMZu7aIdD+10xPuw==";
+    const VECTOR_PLAINTEXT: &str = "Saturn, bringer of old age";
+
+    fn keys(secret_key_hex: &str) -> (NostrSecretKey, NostrPubKey) {
+        let ecda = AsymmetricKeyImpl();
+        let secret_key = NostrSecretKey(ecda.parse_secret_key_from_hex(secret_key_hex).unwrap());
+        let keypair = ecda.new_keypair(secret_key.0.clone()).unwrap();
+        (secret_key, NostrPubKey(ecda.pubkey_from_pair(&keypair)))
+    }
+
+    fn rngcore() -> CryptoHashRng {
+        CryptoHashRng::from_seed([4u8; 32])
+    }
+
+    #[test]
+    fn test_shared_secret_is_symmetric() {
+        let (sk1, pk1) = keys(SECRET_KEY_1);
+        let (sk2, pk2) = keys(SECRET_KEY_2);
+        assert_eq!(pk2.to_string(), PUBLIC_KEY_2);
+        assert_eq!(shared_secret(&sk1, &pk2), shared_secret(&sk2, &pk1));
+    }
+
+    #[test]
+    fn test_published_vector() {
+        let (sk1, pk1) = keys(SECRET_KEY_1);
+        let (sk2, pk2) = keys(SECRET_KEY_2);
+
+        assert_eq!(decrypt(&sk2, &pk1, VECTOR_CONTENT).unwrap(), VECTOR_PLAINTEXT);
+        assert_eq!(decrypt(&sk1, &pk2, VECTOR_CONTENT).unwrap(), VECTOR_PLAINTEXT);
+
+        let (_, iv) = VECTOR_CONTENT.split_once(IV_SEPARATOR).unwrap();
+        let iv: [u8; IV_SIZE] = BASE64.decode(iv).unwrap().try_into().unwrap();
+        assert_eq!(encrypt_with_iv(&sk1, &pk2, VECTOR_PLAINTEXT, &iv), VECTOR_CONTENT);
+    }
+
+    #[test]
+    fn test_roundtrip_and_errors() {
+        let (sk1, pk1) = keys(SECRET_KEY_1);
+        let (sk2, pk2) = keys(SECRET_KEY_2);
+        let mut rng = rngcore();
+
+        let content = encrypt(&sk1, &pk2, "hello bob", &mut rng);
+        assert_ne!(content, encrypt(&sk1, &pk2, "hello bob", &mut rng));
+        assert_eq!(decrypt(&sk2, &pk1, content.as_str()).unwrap(), "hello bob");
+        assert_eq!(decrypt(&sk1, &pk2, encrypt(&sk1, &pk2, "", &mut rng).as_str()).unwrap(), "");
+
+        assert_eq!(decrypt(&sk2, &pk1, "no separator"), Err(Nip04Error::InvalidContentFormat));
+        assert_eq!(decrypt(&sk2, &pk1, "dJc+?iv=AAAA"), Err(Nip04Error::InvalidIv));
+        assert!(matches!(decrypt(&sk2, &pk1, "***?iv=M6VxRPkMZu7aIdD+10xPuw=="), Err(Nip04Error::Base64Decode(_))));
+
+        let (sk3, _) = keys("8b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e");
+        assert_ne!(decrypt(&sk3, &pk1, content.as_str()).ok().as_deref(), Some("hello bob"));
+    }
+
+    #[test]
+    fn test_direct_message_event() {
+        let (sk1, pk1) = keys(SECRET_KEY_1);
+        let (sk2, pk2) = keys(SECRET_KEY_2);
+        let ecda = AsymmetricKeyImpl();
+        let mut rng = rngcore();
+
+        let event = EventBuilder::encrypted_direct_msg(&sk1, &pk2, "see you", None, &mut rng)
+            .sign(&ecda.new_keypair(sk1.0.clone()).unwrap(), &mut rng)
+            .unwrap();
+        assert_eq!(event.kind, Kind::EncryptedDirectMessage);
+        assert_eq!(event.pubkey, pk1);
+        assert_eq!(event.public_keys().collect::<Vec<_>>(), vec![pk2]);
+
+        assert_eq!(decrypt_direct_message(&sk2, &event).unwrap(), "see you");
+        assert_eq!(decrypt_direct_message(&sk1, &event).unwrap(), "see you");
+
+        let reply = EventBuilder::encrypted_direct_msg(&sk2, &pk1, "ok", Some(event.id), &mut rng)
+            .sign(&ecda.new_keypair(sk2.0.clone()).unwrap(), &mut rng)
+            .unwrap();
+        assert_eq!(reply.tags[1].content(), Some(event.id.to_hex().as_str()));
+        assert_eq!(decrypt_direct_message(&sk1, &reply).unwrap(), "ok");
+
+        let note = EventBuilder::text_note("plain", [])
+            .sign(&ecda.new_keypair(sk1.0.clone()).unwrap(), &mut rng)
+            .unwrap();
+        assert_eq!(decrypt_direct_message(&sk1, &note), Err(Nip04Error::WrongKind));
+    }
+}
@@ -0,0 +1,370 @@
+//! NIP44 versioned encryption, version 2
+//!
+//! ChaCha20 with HMAC-SHA256 keyed from a conversation key, the HKDF of the ECDH shared
+//! secret. Plaintexts are length prefixed and padded before encryption, the payload is
+//! `base64(version || nonce || ciphertext || mac)`.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/44.md>
+
+use std::string::FromUtf8Error;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20::ChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+
+use crate::encryption::{shared_secret, Sha256Hash};
+use crate::signing::{CryptoRngCore, DataField, NostrPubKey, NostrSecretKey};
+
+/// Payload version
+pub const VERSION: u8 = 2;
+
+/// Size of the nonce
+pub const NONCE_SIZE: usize = 32;
+
+/// Smallest plaintext size, in bytes
+pub const MIN_PLAINTEXT_SIZE: usize = 1;
+
+/// Largest plaintext size, in bytes
+pub const MAX_PLAINTEXT_SIZE: usize = 65535;
+
+/// HKDF salt of the conversation key
+const CONVERSATION_KEY_SALT: &[u8] = b"nip44-v2";
+
+/// Size of the message keys, ChaCha20 key, ChaCha20 nonce and HMAC key
+const MESSAGE_KEYS_SIZE: usize = 32 + 12 + 32;
+
+/// Size of the HMAC-SHA256 tag
+const MAC_SIZE: usize = 32;
+
+/// Bounds of the base64 payload length
+const MIN_PAYLOAD_SIZE: usize = 132;
+const MAX_PAYLOAD_SIZE: usize = 87472;
+
+/// Bounds of the decoded payload length
+const MIN_DECODED_SIZE: usize = 99;
+const MAX_DECODED_SIZE: usize = 65603;
+
+type HmacSha256 = Hmac<Sha256Hash>;
+
+/// NIP44 error
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Nip44Error {
+
+    #[error("Message is empty")]
+    MessageEmpty,
+
+    #[error("Message is too long")]
+    MessageTooLong,
+
+    #[error("Unsupported encoding")]
+    UnsupportedEncoding,
+
+    #[error("Invalid payload length")]
+    InvalidPayloadLength,
+
+    #[error("Unknown version: {0}")]
+    UnknownVersion(u8),
+
+    #[error("Base64 decode error: {0}")]
+    Base64Decode(#[from] base64::DecodeError),
+
+    #[error("Invalid MAC")]
+    InvalidMac,
+
+    #[error("Invalid padding")]
+    InvalidPadding,
+
+    #[error("Utf8 decoding error: {0}")]
+    FromUTF8(#[from] FromUtf8Error),
+}
+
+/// Long-term key shared by two public keys, identical in both directions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversationKey(DataField);
+
+impl ConversationKey {
+    /// `HKDF-extract(salt = "nip44-v2", ikm = ECDH(secret_key, public_key))`
+    pub fn derive(secret_key: &NostrSecretKey, public_key: &NostrPubKey) -> Self {
+        let shared = shared_secret(secret_key, public_key);
+        let (prk, _) = Hkdf::<Sha256Hash>::extract(Some(CONVERSATION_KEY_SALT), &shared);
+        let mut key: DataField = [0u8; 32];
+        key.copy_from_slice(&prk[..]);
+        Self(key)
+    }
+
+    /// Key bytes
+    #[inline]
+    pub fn as_bytes(&self) -> &DataField {
+        &self.0
+    }
+
+    /// ChaCha20 key, ChaCha20 nonce and HMAC key of a message
+    fn message_keys(&self, nonce: &[u8; NONCE_SIZE]) -> [u8; MESSAGE_KEYS_SIZE] {
+        let hkdf = Hkdf::<Sha256Hash>::from_prk(&self.0).expect("conversation key is a valid PRK");
+        let mut keys = [0u8; MESSAGE_KEYS_SIZE];
+        hkdf.expand(nonce, &mut keys).expect("message keys fit one HKDF output");
+        keys
+    }
+}
+
+impl From<DataField> for ConversationKey {
+    fn from(key: DataField) -> Self {
+        Self(key)
+    }
+}
+
+/// Padded length of a plaintext of `len` bytes
+pub fn calc_padded_len(len: usize) -> usize {
+    if len <= 32 {
+        return 32;
+    }
+    let next_power: usize = 1 << (usize::BITS - (len - 1).leading_zeros());
+    let chunk: usize = if next_power <= 256 { 32 } else { next_power / 8 };
+    chunk * ((len - 1) / chunk + 1)
+}
+
+/// Big endian `u16` length prefix followed by the zero padded plaintext
+fn pad(plaintext: &[u8]) -> Result<Vec<u8>, Nip44Error> {
+    let len: usize = plaintext.len();
+    if len < MIN_PLAINTEXT_SIZE {
+        return Err(Nip44Error::MessageEmpty);
+    }
+    if len > MAX_PLAINTEXT_SIZE {
+        return Err(Nip44Error::MessageTooLong);
+    }
+
+    let mut padded: Vec<u8> = Vec::with_capacity(2 + calc_padded_len(len));
+    padded.extend_from_slice(&(len as u16).to_be_bytes());
+    padded.extend_from_slice(plaintext);
+    padded.resize(2 + calc_padded_len(len), 0);
+    Ok(padded)
+}
+
+fn unpad(padded: &[u8]) -> Result<Vec<u8>, Nip44Error> {
+    if padded.len() < 2 {
+        return Err(Nip44Error::InvalidPadding);
+    }
+    let len: usize = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+    if len < MIN_PLAINTEXT_SIZE || padded.len() != 2 + calc_padded_len(len) {
+        return Err(Nip44Error::InvalidPadding);
+    }
+    Ok(padded[2..2 + len].to_vec())
+}
+
+fn hmac_aad(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key size");
+    mac.update(nonce);
+    mac.update(ciphertext);
+    mac
+}
+
+/// Encrypt `plaintext` with a random nonce
+pub fn encrypt<RG>(conversation_key: &ConversationKey, plaintext: &str, rngcore: &mut RG) -> Result<String, Nip44Error>
+where RG: CryptoRngCore
+{
+    let mut nonce = [0u8; NONCE_SIZE];
+    rngcore.fill_bytes(&mut nonce);
+    encrypt_with_nonce(conversation_key, plaintext, &nonce)
+}
+
+/// Encrypt `plaintext` with the given nonce
+///
+/// A nonce must never be reused with the same conversation key, prefer [`encrypt`].
+pub fn encrypt_with_nonce(conversation_key: &ConversationKey, plaintext: &str, nonce: &[u8; NONCE_SIZE]) -> Result<String, Nip44Error>
+{
+    let keys = conversation_key.message_keys(nonce);
+    let mut ciphertext: Vec<u8> = pad(plaintext.as_bytes())?;
+    ChaCha20::new(keys[0..32].into(), keys[32..44].into()).apply_keystream(&mut ciphertext);
+    let mac = hmac_aad(&keys[44..], nonce, &ciphertext).finalize().into_bytes();
+
+    let mut payload: Vec<u8> = Vec::with_capacity(1 + NONCE_SIZE + ciphertext.len() + MAC_SIZE);
+    payload.push(VERSION);
+    payload.extend_from_slice(nonce);
+    payload.extend_from_slice(&ciphertext);
+    payload.extend_from_slice(&mac);
+    Ok(BASE64.encode(payload))
+}
+
+/// Decrypt a base64 `payload`
+pub fn decrypt(conversation_key: &ConversationKey, payload: &str) -> Result<String, Nip44Error>
+{
+    if payload.starts_with('#') {
+        return Err(Nip44Error::UnsupportedEncoding);
+    }
+    if !(MIN_PAYLOAD_SIZE..=MAX_PAYLOAD_SIZE).contains(&payload.len()) {
+        return Err(Nip44Error::InvalidPayloadLength);
+    }
+
+    let data: Vec<u8> = BASE64.decode(payload)?;
+    if !(MIN_DECODED_SIZE..=MAX_DECODED_SIZE).contains(&data.len()) {
+        return Err(Nip44Error::InvalidPayloadLength);
+    }
+    if data[0] != VERSION {
+        return Err(Nip44Error::UnknownVersion(data[0]));
+    }
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&data[1..1 + NONCE_SIZE]);
+    let (ciphertext, mac) = data[1 + NONCE_SIZE..].split_at(data.len() - 1 - NONCE_SIZE - MAC_SIZE);
+
+    let keys = conversation_key.message_keys(&nonce);
+    hmac_aad(&keys[44..], &nonce, ciphertext)
+        .verify_slice(mac)
+        .map_err(|_| Nip44Error::InvalidMac)?;
+
+    let mut padded: Vec<u8> = ciphertext.to_vec();
+    ChaCha20::new(keys[0..32].into(), keys[32..44].into()).apply_keystream(&mut padded);
+    Ok(String::from_utf8(unpad(&padded)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_conservative::{DisplayHex, FromHex};
+    use rand_core::SeedableRng;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::encryption::{Sha2Digest, Sha256Hash};
+    use crate::rng::CryptoHashRng;
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps};
+
+    /// Subset of the official NIP44 test vectors, in the layout of the upstream file
+    ///
+    /// <https://github.com/paulmillr/nip44/blob/main/nip44.vectors.json>
+    const VECTORS: &str = include_str!("nip44.vectors.json");
+
+    fn vectors(section: &str, name: &str) -> Vec<Value> {
+        let all: Value = serde_json::from_str(VECTORS).unwrap();
+        all["v2"][section][name].as_array().unwrap().clone()
+    }
+
+    fn field<'a>(vector: &'a Value, name: &str) -> &'a str {
+        vector[name].as_str().unwrap()
+    }
+
+    fn bytes32(hex: &str) -> [u8; 32] {
+        <[u8; 32] as FromHex>::from_hex(hex).unwrap()
+    }
+
+    fn secret_key(hex: &str) -> Result<NostrSecretKey, crate::signing::AsymmetricKeyError> {
+        AsymmetricKeyImpl().parse_secret_key_from_hex(hex).map(NostrSecretKey)
+    }
+
+    fn public_key_of(secret_key: &NostrSecretKey) -> NostrPubKey {
+        let ecda = AsymmetricKeyImpl();
+        NostrPubKey(ecda.pubkey_from_pair(&ecda.new_keypair(secret_key.0.clone()).unwrap()))
+    }
+
+    #[test]
+    fn test_valid_get_conversation_key() {
+        for vector in vectors("valid", "get_conversation_key") {
+            let sec1 = secret_key(field(&vector, "sec1")).unwrap();
+            let pub2 = NostrPubKey::try_from(&bytes32(field(&vector, "pub2"))[..]).unwrap();
+            let key = ConversationKey::derive(&sec1, &pub2);
+            assert_eq!(key.as_bytes().to_lower_hex_string(), field(&vector, "conversation_key"));
+        }
+    }
+
+    #[test]
+    fn test_valid_calc_padded_len() {
+        for vector in vectors("valid", "calc_padded_len") {
+            let len = vector[0].as_u64().unwrap() as usize;
+            assert_eq!(calc_padded_len(len), vector[1].as_u64().unwrap() as usize, "len {}", len);
+        }
+    }
+
+    #[test]
+    fn test_valid_encrypt_decrypt() {
+        for vector in vectors("valid", "encrypt_decrypt") {
+            let sec1 = secret_key(field(&vector, "sec1")).unwrap();
+            let sec2 = secret_key(field(&vector, "sec2")).unwrap();
+            let key = ConversationKey::derive(&sec1, &public_key_of(&sec2));
+            assert_eq!(key.as_bytes().to_lower_hex_string(), field(&vector, "conversation_key"));
+            assert_eq!(key, ConversationKey::derive(&sec2, &public_key_of(&sec1)));
+
+            let nonce = bytes32(field(&vector, "nonce"));
+            let plaintext = field(&vector, "plaintext");
+            let payload = field(&vector, "ciphertext");
+            assert_eq!(encrypt_with_nonce(&key, plaintext, &nonce).unwrap(), payload);
+            assert_eq!(decrypt(&key, payload).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_valid_get_message_keys() {
+        let all: Value = serde_json::from_str(VECTORS).unwrap();
+        let section = &all["v2"]["valid"]["get_message_keys"];
+        let key = ConversationKey::from(bytes32(field(section, "conversation_key")));
+        for vector in section["keys"].as_array().unwrap() {
+            let keys = key.message_keys(&bytes32(field(vector, "nonce")));
+            assert_eq!(keys[..32].to_lower_hex_string(), field(vector, "chacha_key"));
+            assert_eq!(keys[32..44].to_lower_hex_string(), field(vector, "chacha_nonce"));
+            assert_eq!(keys[44..].to_lower_hex_string(), field(vector, "hmac_key"));
+        }
+    }
+
+    /// The plaintext is `pattern` repeated, the vector holds the checksums of the plaintext and payload
+    #[test]
+    fn test_valid_encrypt_decrypt_long_msg() {
+        for vector in vectors("valid", "encrypt_decrypt_long_msg") {
+            let key = ConversationKey::from(bytes32(field(&vector, "conversation_key")));
+            let nonce = bytes32(field(&vector, "nonce"));
+            let plaintext = field(&vector, "pattern").repeat(vector["repeat"].as_u64().unwrap() as usize);
+            assert_eq!(Sha256Hash::digest(plaintext.as_bytes())[..].to_lower_hex_string(), field(&vector, "plaintext_sha256"));
+
+            let payload = encrypt_with_nonce(&key, plaintext.as_str(), &nonce).unwrap();
+            assert_eq!(Sha256Hash::digest(payload.as_bytes())[..].to_lower_hex_string(), field(&vector, "payload_sha256"));
+            assert_eq!(decrypt(&key, payload.as_str()).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_invalid_msg_lengths() {
+        let key = ConversationKey::from([1u8; 32]);
+        let nonce = [2u8; NONCE_SIZE];
+        for vector in vectors("invalid", "encrypt_msg_lengths") {
+            let plaintext = "a".repeat(vector.as_u64().unwrap() as usize);
+            assert!(encrypt_with_nonce(&key, plaintext.as_str(), &nonce).is_err());
+        }
+        for vector in vectors("invalid", "decrypt_msg_lengths") {
+            let payload = "a".repeat(vector.as_u64().unwrap() as usize);
+            assert!(decrypt(&key, payload.as_str()).is_err());
+        }
+        assert_eq!(decrypt(&key, "#unsupported"), Err(Nip44Error::UnsupportedEncoding));
+    }
+
+    #[test]
+    fn test_invalid_get_conversation_key() {
+        for vector in vectors("invalid", "get_conversation_key") {
+            let sec1 = secret_key(field(&vector, "sec1"));
+            let pub2 = NostrPubKey::try_from(&bytes32(field(&vector, "pub2"))[..]);
+            assert!(sec1.is_err() || pub2.is_err(), "{}", field(&vector, "note"));
+        }
+    }
+
+    #[test]
+    fn test_invalid_decrypt() {
+        for vector in vectors("invalid", "decrypt") {
+            let key = ConversationKey::from(bytes32(field(&vector, "conversation_key")));
+            assert!(decrypt(&key, field(&vector, "ciphertext")).is_err(), "{}", field(&vector, "note"));
+        }
+    }
+
+    #[test]
+    fn test_random_nonce_roundtrip() {
+        let sec1 = secret_key("6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e").unwrap();
+        let sec2 = secret_key("7b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e").unwrap();
+        let key = ConversationKey::derive(&sec1, &public_key_of(&sec2));
+        let mut rng = CryptoHashRng::from_seed([5u8; 32]);
+
+        let first = encrypt(&key, "hello", &mut rng).unwrap();
+        let second = encrypt(&key, "hello", &mut rng).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(decrypt(&ConversationKey::derive(&sec2, &public_key_of(&sec1)), first.as_str()).unwrap(), "hello");
+        assert_eq!(decrypt(&key, second.as_str()).unwrap(), "hello");
+        assert_eq!(encrypt(&key, "", &mut rng), Err(Nip44Error::MessageEmpty));
+    }
+}
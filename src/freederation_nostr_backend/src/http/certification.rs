@@ -0,0 +1,537 @@
+//! Certified HTTP responses
+//!
+//! Responses are certified in a hash tree whose root hash is the certified data of the canister.
+//! Queries attach the subnet certificate and a witness in the `IC-Certificate` header, so the
+//! HTTP gateway can verify them.
+//!
+//! - Version 1, `http_assets`: the SHA256 of the body by path, for responses ignoring the query.
+//! - Version 2, `http_expr`: the hashes of the request and of the response by path and
+//!   [`Expression`], for responses selected by the query, e.g. a NIP05 `name`.
+//!
+//! The tree is lost on upgrade and rebuilt from the canister state in batches, see `post_upgrade`.
+//!
+//! <https://internetcomputer.org/docs/current/references/http-gateway-protocol-spec#response-verification>
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ic_certification::{AsHashTree, Hash, HashTree, NestedTree};
+use serde::Serialize;
+
+use crate::encryption::{Sha256Hash, Sha2Digest};
+use crate::http::{event_response, HeaderField, HttpRequest, HttpResponse};
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_id::EventId;
+
+/// Label of the response hashes by path, certification version 1
+pub const HTTP_ASSETS_LABEL: &[u8] = b"http_assets";
+
+/// Label of the expression paths, certification version 2
+pub const HTTP_EXPR_LABEL: &[u8] = b"http_expr";
+
+/// Name of the certificate header
+pub const CERTIFICATE_HEADER: &str = "IC-Certificate";
+
+/// Name of the header holding the [`Expression`] of a version 2 response
+pub const CERTIFICATE_EXPRESSION_HEADER: &str = "IC-CertificateExpression";
+
+/// Last segment of an exact expression path
+const EXACT_PATH_SEGMENT: &str = "<$>";
+
+/// Path of the event endpoint
+pub fn event_path(id: &EventId) -> String {
+    format!("/e/{}", id.to_hex())
+}
+
+/// Parts of a request and of its response certified together, certification version 2
+///
+/// No request header is certified, the method, the query parameters of `query_parameters`
+/// and the body are. The response status, body and headers of `response_headers` are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expression {
+    /// Certified query parameters, case insensitive
+    pub query_parameters: &'static [&'static str],
+    /// Certified response headers, lowercase
+    pub response_headers: &'static [&'static str],
+    /// `default_certification` CEL expression of the above, the value of the expression header
+    pub cel: &'static str,
+}
+
+impl Expression {
+    /// Hash of the CEL expression, the segment after the expression path
+    fn hash(&self) -> Hash {
+        sha256(self.cel.as_bytes())
+    }
+
+    /// Hash of the method, certified query parameters and body of `request`
+    pub fn request_hash(&self, request: &HttpRequest) -> Hash {
+        let query: String = request
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| {
+                let name: &str = pair.split('=').next().unwrap_or_default();
+                self.query_parameters.iter().any(|parameter| parameter.eq_ignore_ascii_case(name))
+            })
+            .collect::<Vec<&str>>()
+            .join("&");
+        let fields = [
+            (String::from(":ic-cert-method"), Value::Text(request.method.as_str())),
+            (String::from(":ic-cert-query"), Value::Bytes(&sha256(query.as_bytes()))),
+        ];
+        sha256(&[representation_independent_hash(&fields), sha256(&request.body)].concat())
+    }
+
+    /// Hash of the status, certified headers, expression header and body of `response`
+    pub fn response_hash(&self, response: &HttpResponse) -> Hash {
+        let mut fields: Vec<(String, Value)> = response
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), Value::Text(value.as_str())))
+            .filter(|(name, _)| {
+                name.eq_ignore_ascii_case(CERTIFICATE_EXPRESSION_HEADER) || self.response_headers.contains(&name.as_str())
+            })
+            .collect();
+        fields.push((String::from(":ic-cert-status"), Value::Number(u64::from(response.status_code))));
+        sha256(&[representation_independent_hash(&fields), sha256(&response.body)].concat())
+    }
+}
+
+/// Value of a field hashed by [`representation_independent_hash`]
+#[derive(Debug, Clone, Copy)]
+enum Value<'a> {
+    Text(&'a str),
+    Bytes(&'a [u8]),
+    Number(u64),
+}
+
+impl Value<'_> {
+    fn hash(&self) -> Hash {
+        match self {
+            Self::Text(text) => sha256(text.as_bytes()),
+            Self::Bytes(bytes) => sha256(bytes),
+            Self::Number(number) => sha256(&leb128(*number)),
+        }
+    }
+}
+
+/// Hash of a map, independent of the order of its fields
+///
+/// <https://internetcomputer.org/docs/current/references/ic-interface-spec#hash-of-map>
+fn representation_independent_hash(fields: &[(String, Value)]) -> Hash {
+    let mut hashes: Vec<Vec<u8>> = fields
+        .iter()
+        .map(|(name, value)| [sha256(name.as_bytes()), value.hash()].concat())
+        .collect();
+    hashes.sort();
+    sha256(&hashes.concat())
+}
+
+/// Unsigned LEB128 encoding of `number`
+fn leb128(mut number: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    loop {
+        let byte: u8 = (number & 0x7f) as u8;
+        number >>= 7;
+        if number == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn sha256(bytes: &[u8]) -> Hash {
+    Sha256Hash::digest(bytes).into()
+}
+
+/// Self-described CBOR of a header field
+fn cbor<T: Serialize>(value: &T) -> Option<Vec<u8>> {
+    let mut serializer = serde_cbor::Serializer::new(Vec::new());
+    serializer.self_describe().ok()?;
+    value.serialize(&mut serializer).ok()?;
+    Some(serializer.into_inner())
+}
+
+/// Tree path of the body hash served at `path`
+fn asset_path(path: &str) -> [Vec<u8>; 2] {
+    [HTTP_ASSETS_LABEL.to_vec(), path.as_bytes().to_vec()]
+}
+
+/// Exact expression path of `path`, e.g. `["http_expr", ".well-known", "nostr.json", "<$>"]`
+fn expr_path(path: &str) -> Vec<String> {
+    let mut segments: Vec<String> = vec![String::from_utf8_lossy(HTTP_EXPR_LABEL).into_owned()];
+    segments.extend(path.trim_start_matches('/').split('/').map(String::from));
+    segments.push(String::from(EXACT_PATH_SEGMENT));
+    segments
+}
+
+/// Tree path of the responses to `request`, up to the request hash
+fn request_path(expression: &Expression, request: &HttpRequest) -> Vec<Vec<u8>> {
+    let mut path: Vec<Vec<u8>> = expr_path(request.path()).into_iter().map(String::into_bytes).collect();
+    path.push(expression.hash().to_vec());
+    path.push(expression.request_hash(request).to_vec());
+    path
+}
+
+/// Destination of the root hash
+pub trait CertifiedData {
+    fn set_certified_data(&mut self, root_hash: &Hash);
+}
+
+/// Certified data of the canister
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IcCertifiedData;
+
+impl CertifiedData for IcCertifiedData {
+    #[inline]
+    fn set_certified_data(&mut self, root_hash: &Hash) {
+        ic_cdk::api::set_certified_data(root_hash);
+    }
+}
+
+/// Hashes of the certified responses, the certified data follows every change
+pub struct CertifiedResponses<C: CertifiedData> {
+    tree: NestedTree<Vec<u8>, Vec<u8>>,
+    certified_data: C,
+}
+
+impl<C: CertifiedData> CertifiedResponses<C> {
+    /// Empty tree, certified at once
+    pub fn new(certified_data: C) -> Self {
+        let mut responses = Self {
+            tree: NestedTree::default(),
+            certified_data,
+        };
+        responses.commit();
+        responses
+    }
+
+    /// Certify `body` as the response of `path`, replacing the previous one
+    pub fn certify(&mut self, path: &str, body: &[u8]) {
+        self.tree.insert(&asset_path(path), sha256(body).to_vec());
+        self.commit();
+    }
+
+    /// Stop certifying `path`
+    pub fn remove(&mut self, path: &str) {
+        self.tree.delete(&asset_path(path));
+        self.commit();
+    }
+
+    /// Check if `body` is the certified response of `path`
+    pub fn is_certified(&self, path: &str, body: &[u8]) -> bool {
+        self.tree.get(&asset_path(path)).is_some_and(|hash| hash[..] == sha256(body))
+    }
+
+    /// Certify `response` as the response to `request`, replacing the previous one
+    ///
+    /// `response` carries the expression header of `expression`.
+    pub fn certify_request(&mut self, expression: &Expression, request: &HttpRequest, response: &HttpResponse) {
+        let mut path: Vec<Vec<u8>> = request_path(expression, request);
+        self.tree.delete(&path);
+        path.push(expression.response_hash(response).to_vec());
+        self.tree.insert(&path, Vec::new());
+        self.commit();
+    }
+
+    /// Stop certifying the response to `request`
+    pub fn remove_request(&mut self, expression: &Expression, request: &HttpRequest) {
+        self.tree.delete(&request_path(expression, request));
+        self.commit();
+    }
+
+    /// Check if `response` is the certified response to `request`
+    pub fn is_request_certified(&self, expression: &Expression, request: &HttpRequest, response: &HttpResponse) -> bool {
+        let mut path: Vec<Vec<u8>> = request_path(expression, request);
+        path.push(expression.response_hash(response).to_vec());
+        self.tree.contains_leaf(&path)
+    }
+
+    /// Certify the responses of the `stored` events and stop certifying the `removed` ones
+    pub fn certify_events<'a, I>(&mut self, removed: &[EventId], stored: I)
+    where
+        I: IntoIterator<Item = &'a EventData>,
+    {
+        for id in removed {
+            self.tree.delete(&asset_path(event_path(id).as_str()));
+        }
+        for event in stored {
+            let body: Vec<u8> = event_response(event).body;
+            self.tree.insert(&asset_path(event_path(&event.id).as_str()), sha256(&body).to_vec());
+        }
+        self.commit();
+    }
+
+    /// Root hash of the tree, the certified data
+    pub fn root_hash(&self) -> Hash {
+        self.tree.root_hash()
+    }
+
+    /// Tree revealing only the hash of `path`
+    pub fn witness(&self, path: &str) -> HashTree {
+        self.tree.witness(&asset_path(path))
+    }
+
+    /// `IC-Certificate` header of `response` to `request`, `None` when it is not certified
+    ///
+    /// The response is checked against `expression` when given, version 2, otherwise by path
+    /// and body, version 1. `certificate` is the data certificate of the query.
+    pub fn certificate_header(
+        &self,
+        request: &HttpRequest,
+        response: &HttpResponse,
+        expression: Option<&Expression>,
+        certificate: &[u8],
+    ) -> Option<HeaderField> {
+        let value: String = match expression {
+            Some(expression) => {
+                if !self.is_request_certified(expression, request, response) {
+                    return None;
+                }
+                let mut path: Vec<Vec<u8>> = request_path(expression, request);
+                path.push(expression.response_hash(response).to_vec());
+                format!(
+                    "certificate=:{}:, tree=:{}:, expr_path=:{}:, version=2",
+                    BASE64.encode(certificate),
+                    BASE64.encode(cbor(&self.tree.witness(&path))?),
+                    BASE64.encode(cbor(&expr_path(request.path()))?)
+                )
+            }
+            None => {
+                if !self.is_certified(request.path(), response.body.as_slice()) {
+                    return None;
+                }
+                format!(
+                    "certificate=:{}:, tree=:{}:",
+                    BASE64.encode(certificate),
+                    BASE64.encode(cbor(&self.witness(request.path()))?)
+                )
+            }
+        };
+        Some((String::from(CERTIFICATE_HEADER), value))
+    }
+
+    fn commit(&mut self) {
+        let root_hash: Hash = self.root_hash();
+        self.certified_data.set_certified_data(&root_hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_certification::LookupResult;
+    use ic_stable_structures::memory_manager::MemoryManager;
+    use ic_stable_structures::DefaultMemoryImpl;
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::nostr::tag::coordinate::EventIdOrCoordinate;
+    use crate::nostr::tag::TagData;
+    use crate::rng::CryptoHashRng;
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps};
+    use crate::store::EventStore;
+    use crate::util::time::{MockTimeSupplier, Timestamp};
+
+    /// Last certified data
+    #[derive(Default)]
+    struct MockCertifiedData(Option<Hash>);
+
+    impl CertifiedData for MockCertifiedData {
+        fn set_certified_data(&mut self, root_hash: &Hash) {
+            self.0 = Some(*root_hash);
+        }
+    }
+
+    fn assert_certified(responses: &CertifiedResponses<MockCertifiedData>) {
+        assert_eq!(responses.certified_data.0, Some(responses.root_hash()));
+        assert_eq!(responses.witness("/").digest(), responses.root_hash());
+    }
+
+    #[test]
+    fn test_root_follows_changes() {
+        let mut responses = CertifiedResponses::new(MockCertifiedData::default());
+        assert_certified(&responses);
+        let empty = responses.root_hash();
+
+        responses.certify("/", b"relay");
+        assert_certified(&responses);
+        assert_ne!(responses.root_hash(), empty);
+        let relay = responses.root_hash();
+
+        responses.certify("/e/00", b"event");
+        assert_certified(&responses);
+        assert_eq!(responses.witness("/e/00").digest(), responses.root_hash());
+
+        responses.certify("/", b"relay v2");
+        assert_certified(&responses);
+        assert!(responses.is_certified("/", b"relay v2"));
+        assert!(!responses.is_certified("/", b"relay"));
+
+        responses.remove("/e/00");
+        responses.certify("/", b"relay");
+        assert_certified(&responses);
+        assert_eq!(responses.root_hash(), relay);
+
+        responses.remove("/");
+        assert_certified(&responses);
+        assert_eq!(responses.root_hash(), empty);
+    }
+
+    #[test]
+    fn test_witness() {
+        let mut responses = CertifiedResponses::new(MockCertifiedData::default());
+        responses.certify("/", b"relay");
+        responses.certify("/.well-known/nostr.json", b"{\"names\":{}}");
+
+        let witness = responses.witness("/");
+        let hash: Hash = Sha256Hash::digest(b"relay").into();
+        assert_eq!(witness.lookup_path([HTTP_ASSETS_LABEL, "/".as_bytes()]), LookupResult::Found(&hash[..]));
+        assert!(!matches!(witness.lookup_path([HTTP_ASSETS_LABEL, "/.well-known/nostr.json".as_bytes()]), LookupResult::Found(_)));
+    }
+
+    #[test]
+    fn test_certificate_header() {
+        let mut responses = CertifiedResponses::new(MockCertifiedData::default());
+        responses.certify("/", b"relay");
+        let request = HttpRequest::new("GET", "/?x=1", vec![]);
+        let response = |body: &str| HttpResponse::new(200, "text/plain", body);
+        assert_eq!(responses.certificate_header(&request, &response("other"), None, b"cert"), None);
+        assert_eq!(responses.certificate_header(&HttpRequest::new("GET", "/missing", vec![]), &response("relay"), None, b"cert"), None);
+
+        let (name, value) = responses.certificate_header(&request, &response("relay"), None, b"cert").unwrap();
+        assert_eq!(name, CERTIFICATE_HEADER);
+        let tree = value.strip_prefix("certificate=:Y2VydA==:, tree=:").and_then(|tree| tree.strip_suffix(':')).unwrap();
+        let cbor = BASE64.decode(tree).unwrap();
+        assert_eq!(&cbor[..3], &[0xd9, 0xd9, 0xf7]);
+        let decoded: HashTree = serde_cbor::from_slice(cbor.as_slice()).unwrap();
+        assert_eq!(decoded.digest(), responses.root_hash());
+    }
+
+    #[test]
+    fn test_representation_independent_hash() {
+        // Request id example of the interface specification
+        let fields = [
+            (String::from("request_type"), Value::Text("call")),
+            (String::from("sender"), Value::Bytes(&[0x04])),
+            (String::from("ingress_expiry"), Value::Number(1_685_570_400_000_000_000)),
+            (String::from("canister_id"), Value::Bytes(&[0, 0, 0, 0, 0, 0, 0x04, 0xd2])),
+            (String::from("method_name"), Value::Text("hello")),
+            (String::from("arg"), Value::Bytes(b"DIDL\x00\xfd*")),
+        ];
+        let expected = "1d1091364d6bb8a6c16b203ee75467d59ead468f523eb058880ae8ec80e2b101";
+        assert_eq!(hex_conservative::DisplayHex::to_lower_hex_string(&representation_independent_hash(&fields)[..]), expected);
+        assert_eq!(leb128(624_485), [0xe5, 0x8e, 0x26]);
+        assert_eq!(leb128(0), [0]);
+    }
+
+    const EXPRESSION: Expression = Expression {
+        query_parameters: &["name"],
+        response_headers: &["content-type"],
+        cel: "expression",
+    };
+
+    fn expression_response(body: &str) -> HttpResponse {
+        let mut response = HttpResponse::new(200, "application/json", body);
+        response.headers.push((String::from(CERTIFICATE_EXPRESSION_HEADER), String::from(EXPRESSION.cel)));
+        response
+    }
+
+    #[test]
+    fn test_expression() {
+        let request = |url: &str| HttpRequest::new("GET", url, vec![]);
+        let bob = request("/.well-known/nostr.json?name=bob");
+        let hash = EXPRESSION.request_hash(&bob);
+        assert_eq!(EXPRESSION.request_hash(&request("/.well-known/nostr.json?other=1&NAME=bob#x")), EXPRESSION.request_hash(&request("/x?NAME=bob")));
+        assert_eq!(EXPRESSION.request_hash(&request("/.well-known/nostr.json?other=1&name=bob")), hash);
+        assert_ne!(EXPRESSION.request_hash(&request("/.well-known/nostr.json?name=carol")), hash);
+        assert_ne!(EXPRESSION.request_hash(&request("/.well-known/nostr.json")), hash);
+        assert_ne!(EXPRESSION.request_hash(&HttpRequest::new("HEAD", "/.well-known/nostr.json?name=bob", vec![])), hash);
+
+        let response = expression_response("{}");
+        let mut uncertified = response.clone();
+        uncertified.headers.push((String::from("X-Other"), String::from("1")));
+        assert_eq!(EXPRESSION.response_hash(&uncertified), EXPRESSION.response_hash(&response));
+        let mut retyped = response.clone();
+        retyped.headers[3].1 = String::from("text/plain");
+        assert_ne!(EXPRESSION.response_hash(&retyped), EXPRESSION.response_hash(&response));
+        assert_ne!(EXPRESSION.response_hash(&HttpResponse { status_code: 404, ..response.clone() }), EXPRESSION.response_hash(&response));
+        assert_ne!(EXPRESSION.response_hash(&expression_response("{ }")), EXPRESSION.response_hash(&response));
+    }
+
+    #[test]
+    fn test_certify_request() {
+        let mut responses = CertifiedResponses::new(MockCertifiedData::default());
+        let empty = responses.root_hash();
+        let bob = HttpRequest::new("GET", "/.well-known/nostr.json?name=bob", vec![]);
+        let carol = HttpRequest::new("GET", "/.well-known/nostr.json?name=carol", vec![]);
+        let (old, new) = (expression_response(r#"{"names":{"bob":"00"}}"#), expression_response(r#"{"names":{"bob":"01"}}"#));
+
+        responses.certify_request(&EXPRESSION, &bob, &old);
+        responses.certify_request(&EXPRESSION, &bob, &new);
+        assert_certified(&responses);
+        assert!(responses.is_request_certified(&EXPRESSION, &bob, &new));
+        assert!(!responses.is_request_certified(&EXPRESSION, &bob, &old));
+        assert!(!responses.is_request_certified(&EXPRESSION, &carol, &new));
+        assert_eq!(responses.certificate_header(&carol, &new, Some(&EXPRESSION), b"cert"), None);
+        assert_eq!(responses.certificate_header(&bob, &new, None, b"cert"), None);
+
+        let (_, value) = responses.certificate_header(&bob, &new, Some(&EXPRESSION), b"cert").unwrap();
+        let fields: Vec<&str> = value.split(", ").collect();
+        assert_eq!(fields[0], "certificate=:Y2VydA==:");
+        assert_eq!(fields[3], "version=2");
+        let decode = |field: &str, name: &str| BASE64.decode(field.strip_prefix(name).and_then(|value| value.strip_suffix(':')).unwrap()).unwrap();
+        let tree: HashTree = serde_cbor::from_slice(&decode(fields[1], "tree=:")).unwrap();
+        assert_eq!(tree.digest(), responses.root_hash());
+        let expr_path: Vec<String> = serde_cbor::from_slice(&decode(fields[2], "expr_path=:")).unwrap();
+        assert_eq!(expr_path, ["http_expr", ".well-known", "nostr.json", "<$>"]);
+        let mut leaf: Vec<Vec<u8>> = expr_path.into_iter().map(String::into_bytes).collect();
+        leaf.extend([EXPRESSION.hash().to_vec(), EXPRESSION.request_hash(&bob).to_vec(), EXPRESSION.response_hash(&new).to_vec()]);
+        assert_eq!(tree.lookup_path(&leaf), LookupResult::Found(&[]));
+
+        responses.remove_request(&EXPRESSION, &bob);
+        assert_certified(&responses);
+        assert_eq!(responses.root_hash(), empty);
+    }
+
+    #[test]
+    fn test_certify_events() {
+        let clock = MockTimeSupplier::new(Timestamp::from(1_000));
+        let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+        let mut store = EventStore::init(|id| memory_manager.get(id), clock.clone());
+        let mut responses = CertifiedResponses::new(MockCertifiedData::default());
+        let empty = responses.root_hash();
+        let ecda = AsymmetricKeyImpl();
+        let signer = ecda.generate_keypair(&mut CryptoHashRng::from_seed([1u8; 32])).unwrap();
+        let publish = |store: &mut EventStore<_, _>, responses: &mut CertifiedResponses<_>, builder: EventBuilder| {
+            let event = builder.custom_created_at(Timestamp::from(900)).sign(&signer, &mut CryptoHashRng::from_seed([2u8; 32])).unwrap();
+            store.publish(event.clone()).unwrap();
+            responses.certify_events(&store.take_removed(), Some(&event));
+            assert_certified(responses);
+            event
+        };
+
+        let note = publish(&mut store, &mut responses, EventBuilder::text_note("note", []));
+        let expiring = publish(&mut store, &mut responses, EventBuilder::text_note("expiring", [TagData::custom("expiration", ["2000"])]));
+        let published = responses.root_hash();
+        assert_ne!(published, empty);
+        assert!(responses.is_certified(&event_path(&note.id), &event_response(&note).body));
+
+        let deletion = publish(&mut store, &mut responses, EventBuilder::delete([EventIdOrCoordinate::Id(note.id)], None::<String>));
+        assert!(!responses.is_certified(&event_path(&note.id), &event_response(&note).body));
+        let deleted = responses.root_hash();
+        assert_ne!(deleted, published);
+
+        clock.set(Timestamp::from(2_500));
+        assert_eq!(store.purge_expired(10), 1);
+        responses.certify_events(&store.take_removed(), None);
+        assert_certified(&responses);
+        assert!(!responses.is_certified(&event_path(&expiring.id), &event_response(&expiring).body));
+        assert_ne!(responses.root_hash(), deleted);
+
+        let mut rebuilt = CertifiedResponses::new(MockCertifiedData::default());
+        rebuilt.certify_events(&[], store.iter_after(None).collect::<Vec<_>>().iter());
+        assert_eq!(rebuilt.root_hash(), responses.root_hash());
+        assert!(rebuilt.is_certified(&event_path(&deletion.id), &event_response(&deletion).body));
+    }
+}
@@ -0,0 +1,339 @@
+//! HTTP interface of the canister
+//!
+//! Requests reach `http_request` through the HTTP gateway of the IC.
+//!
+//! <https://internetcomputer.org/docs/current/references/http-gateway-protocol-spec>
+
+use candid::{CandidType, Deserialize};
+use url::form_urlencoded;
+
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_id::EventId;
+use crate::util::jsonutil::JsonUtil;
+
+pub mod certification;
+pub mod nip05;
+pub mod relay_info;
+
+pub use self::certification::{CertifiedResponses, Expression, IcCertifiedData};
+pub use self::nip05::{Nip05Document, NIP05_EXPRESSION};
+pub use self::relay_info::{RelayInfo, RelayInformationDocument};
+
+/// Header name and value
+pub type HeaderField = (String, String);
+
+/// Request forwarded by the HTTP gateway
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Path and query
+    pub url: String,
+    pub headers: Vec<HeaderField>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// New request without body
+    pub fn new<S, U>(method: S, url: U, headers: Vec<HeaderField>) -> Self
+    where
+        S: Into<String>,
+        U: Into<String>,
+    {
+        Self {
+            method: method.into(),
+            url: url.into(),
+            headers,
+            body: Vec::new(),
+        }
+    }
+
+    /// Value of the first header named `name`, case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Path of the url, without the query
+    pub fn path(&self) -> &str {
+        self.url.split(['?', '#']).next().unwrap_or_default()
+    }
+
+    /// Raw query of the url, without the `?`
+    pub fn query(&self) -> Option<&str> {
+        Some(self.url.split('#').next()?.split_once('?')?.1)
+    }
+
+    /// Decoded value of the first query parameter named `name`
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        form_urlencoded::parse(self.query()?.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Check if the `Accept` header lists `mime_type`
+    pub fn accepts(&self, mime_type: &str) -> bool {
+        self.header("accept").is_some_and(|accept| {
+            accept
+                .split(',')
+                .filter_map(|item| item.split(';').next())
+                .any(|item| item.trim().eq_ignore_ascii_case(mime_type))
+        })
+    }
+}
+
+/// Response returned to the HTTP gateway
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<HeaderField>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Response with `content_type` and the CORS headers
+    pub fn new<B>(status_code: u16, content_type: &str, body: B) -> Self
+    where
+        B: Into<Vec<u8>>,
+    {
+        let mut headers: Vec<HeaderField> = cors_headers();
+        headers.push((String::from("Content-Type"), String::from(content_type)));
+        Self {
+            status_code,
+            headers,
+            body: body.into(),
+        }
+    }
+
+    /// `204` answer of a CORS preflight
+    pub fn no_content() -> Self {
+        Self {
+            status_code: 204,
+            headers: cors_headers(),
+            body: Vec::new(),
+        }
+    }
+
+    /// `404` plain text
+    pub fn not_found() -> Self {
+        Self::new(404, "text/plain", "Not found")
+    }
+
+    /// `405` plain text
+    pub fn method_not_allowed() -> Self {
+        Self::new(405, "text/plain", "Method not allowed")
+    }
+
+    /// Value of the first header named `name`, case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// `200` JSON response of a stored event
+pub fn event_response(event: &EventData) -> HttpResponse {
+    HttpResponse::new(200, "application/json", event.as_json())
+}
+
+/// Headers letting web clients read the responses, NIP11 requires them
+pub fn cors_headers() -> Vec<HeaderField> {
+    vec![
+        (String::from("Access-Control-Allow-Origin"), String::from("*")),
+        (String::from("Access-Control-Allow-Headers"), String::from("*")),
+        (String::from("Access-Control-Allow-Methods"), String::from("GET, OPTIONS")),
+    ]
+}
+
+/// Canister state read by [`handle_request`], only the documents of the route are built
+pub trait HttpContext {
+    /// NIP11 document
+    fn relay_info(&self) -> RelayInformationDocument;
+
+    /// NIP05 document listing `name`, empty when it is not registered
+    fn nip05(&self, name: &str) -> Nip05Document;
+
+    /// Stored event
+    fn event(&self, id: &EventId) -> Option<EventData>;
+
+    /// `IC-Certificate` header of `response` to `request`, see [`CertifiedResponses::certificate_header`]
+    fn certificate_header(&self, request: &HttpRequest, response: &HttpResponse, expression: Option<&Expression>) -> Option<HeaderField>;
+}
+
+/// Route a request
+///
+/// - `/` with `Accept: application/nostr+json`: NIP11 document
+/// - `/.well-known/nostr.json?name=<name>`: NIP05 document listing the name, empty without it
+/// - `/e/<hex id>`: stored event
+///
+/// Successful responses carry the certificate of their path, the NIP05 documents of
+/// registered names the certificate of their query, see [`NIP05_EXPRESSION`].
+pub fn handle_request<C>(request: &HttpRequest, context: &C) -> HttpResponse
+where
+    C: HttpContext,
+{
+    let path: &str = request.path();
+    let (mut response, expression): (HttpResponse, Option<&Expression>) = match request.method.to_ascii_uppercase().as_str() {
+        "OPTIONS" => return HttpResponse::no_content(),
+        "GET" | "HEAD" => match path {
+            relay_info::RELAY_INFO_PATH if request.accepts(relay_info::NOSTR_JSON_MIME) => (context.relay_info().to_response(), None),
+            nip05::NOSTR_JSON_PATH => {
+                let document: Nip05Document = match request.query_param("name") {
+                    Some(name) => context.nip05(name.as_str()),
+                    None => Nip05Document::default(),
+                };
+                (document.to_response(), Some(&NIP05_EXPRESSION))
+            }
+            _ => match path.strip_prefix("/e/").and_then(|id| EventId::from_hex(id).ok()).and_then(|id| context.event(&id)) {
+                Some(event) => (event_response(&event), None),
+                None => return HttpResponse::not_found(),
+            },
+        },
+        _ => return HttpResponse::method_not_allowed(),
+    };
+    if let Some(header) = context.certificate_header(request, &response, expression) {
+        response.headers.push(header);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use ic_certification::Hash;
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::http::certification::{event_path, CertifiedData, CERTIFICATE_EXPRESSION_HEADER, CERTIFICATE_HEADER};
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::rng::CryptoHashRng;
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, NostrPubKey};
+
+    const BOB_KEY: &str = "b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9";
+
+    struct NoCertifiedData;
+
+    impl CertifiedData for NoCertifiedData {
+        fn set_certified_data(&mut self, _root_hash: &Hash) {}
+    }
+
+    /// Default relay information, a single NIP05 name and a single event
+    struct TestContext {
+        event: EventData,
+        responses: CertifiedResponses<NoCertifiedData>,
+    }
+
+    impl TestContext {
+        fn new() -> Self {
+            let ecda = AsymmetricKeyImpl();
+            let skey = ecda.parse_secret_key_from_hex("6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e").unwrap();
+            let event = EventBuilder::text_note("served", [])
+                .sign(&ecda.new_keypair(skey).unwrap(), &mut CryptoHashRng::from_seed([3u8; 32]))
+                .unwrap();
+            let mut context = Self { event, responses: CertifiedResponses::new(NoCertifiedData) };
+            context.responses.certify(relay_info::RELAY_INFO_PATH, context.relay_info().to_response().body.as_slice());
+            context.responses.certify_request(&NIP05_EXPRESSION, &nip05::name_request("bob"), &context.nip05("bob").to_response());
+            context
+        }
+    }
+
+    impl HttpContext for TestContext {
+        fn relay_info(&self) -> RelayInformationDocument {
+            RelayInformationDocument::new(&RelayInfo::default(), &Default::default())
+        }
+
+        fn nip05(&self, name: &str) -> Nip05Document {
+            let mut document = Nip05Document::default();
+            if name == "bob" {
+                document.insert(String::from("bob"), NostrPubKey::from_str(BOB_KEY).unwrap(), vec![String::from("wss://relay.example.com")]);
+            }
+            document
+        }
+
+        fn event(&self, id: &EventId) -> Option<EventData> {
+            Some(self.event.clone()).filter(|event| event.id == *id)
+        }
+
+        fn certificate_header(&self, request: &HttpRequest, response: &HttpResponse, expression: Option<&Expression>) -> Option<HeaderField> {
+            self.responses.certificate_header(request, response, expression, b"certificate")
+        }
+    }
+
+    fn get(url: &str, accept: Option<&str>) -> HttpRequest {
+        let headers = accept.into_iter().map(|value| (String::from("Accept"), String::from(value))).collect();
+        HttpRequest::new("GET", url, headers)
+    }
+
+    #[test]
+    fn test_request_helpers() {
+        let request = get("/path/to?name=bob#frag", Some("text/html, application/nostr+json;q=0.9"));
+        assert_eq!(request.path(), "/path/to");
+        assert_eq!(request.query(), Some("name=bob"));
+        assert_eq!(request.query_param("name").as_deref(), Some("bob"));
+        assert_eq!(get("/?name=a%20b&name=c", None).query_param("name").as_deref(), Some("a b"));
+        assert_eq!(get("/?other=1", None).query_param("name"), None);
+        assert_eq!(get("/", None).query(), None);
+        assert_eq!(request.header("ACCEPT"), Some("text/html, application/nostr+json;q=0.9"));
+        assert!(request.accepts("application/nostr+json"));
+        assert!(!request.accepts("application/json"));
+        assert!(!get("/", None).accepts("application/nostr+json"));
+    }
+
+    #[test]
+    fn test_routing() {
+        let context = TestContext::new();
+        let response = handle_request(&get("/", Some("application/nostr+json")), &context);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.header("content-type"), Some("application/nostr+json"));
+        assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+        assert!(response.header(CERTIFICATE_HEADER).is_some());
+
+        assert_eq!(handle_request(&get("/", Some("text/html")), &context).status_code, 404);
+        assert_eq!(handle_request(&get("/other", Some("application/nostr+json")), &context).status_code, 404);
+        assert_eq!(handle_request(&HttpRequest::new("OPTIONS", "/", vec![]), &context).status_code, 204);
+        assert_eq!(handle_request(&HttpRequest::new("POST", "/", vec![]), &context).status_code, 405);
+    }
+
+    #[test]
+    fn test_nip05_route() {
+        let context = TestContext::new();
+        for url in ["/.well-known/nostr.json?name=bob", "/.well-known/nostr.json?other=1&name=bob"] {
+            let response = handle_request(&get(url, None), &context);
+            assert_eq!(response.status_code, 200);
+            assert_eq!(response.header("content-type"), Some("application/json"));
+            assert_eq!(response.header("access-control-allow-origin"), Some("*"));
+            assert_eq!(response.header(CERTIFICATE_EXPRESSION_HEADER), Some(NIP05_EXPRESSION.cel));
+            assert!(response.header(CERTIFICATE_HEADER).is_some_and(|value| value.ends_with("version=2")));
+            let json: serde_json::Value = serde_json::from_slice(response.body.as_slice()).unwrap();
+            assert_eq!(json["names"]["bob"], BOB_KEY);
+            assert_eq!(json["relays"][BOB_KEY][0], "wss://relay.example.com");
+        }
+        for url in ["/.well-known/nostr.json?name=carol", "/.well-known/nostr.json"] {
+            let response = handle_request(&get(url, None), &context);
+            assert_eq!(response.status_code, 200);
+            assert_eq!(response.body, br#"{"names":{}}"#);
+            assert!(response.header(CERTIFICATE_HEADER).is_none());
+        }
+    }
+
+    #[test]
+    fn test_event_route() {
+        let mut context = TestContext::new();
+        let path = event_path(&context.event.id);
+
+        let response = handle_request(&get(path.as_str(), None), &context);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(EventData::from_json(response.body.as_slice()).unwrap(), context.event);
+        assert!(response.header(CERTIFICATE_HEADER).is_none());
+
+        context.responses.certify(path.as_str(), event_response(&context.event).body.as_slice());
+        assert!(handle_request(&get(path.as_str(), None), &context).header(CERTIFICATE_HEADER).is_some());
+
+        assert_eq!(handle_request(&get(&event_path(&EventId::all_zeros()), None), &context).status_code, 404);
+        assert_eq!(handle_request(&get("/e/zz", None), &context).status_code, 404);
+    }
+}
@@ -0,0 +1,249 @@
+//! NIP05 identifiers
+//!
+//! Registered names are served at `/.well-known/nostr.json?name=<name>`, one document per name,
+//! certified along with the `name` parameter by [`NIP05_EXPRESSION`].
+//! [`verify`] checks the `nip05` claim of a metadata event against a fetched document.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/05.md>
+
+use core::fmt;
+use core::str::FromStr;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::http::certification::{Expression, CERTIFICATE_EXPRESSION_HEADER};
+use crate::http::{HttpRequest, HttpResponse};
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_kind::Kind;
+use crate::nostr::metadata::{Metadata, MetadataError};
+use crate::signing::NostrPubKey;
+use crate::store::name_registry::{normalize_name, NameError};
+use crate::util::jsonutil::JsonUtil;
+
+/// Path of the document
+pub const NOSTR_JSON_PATH: &str = "/.well-known/nostr.json";
+
+/// Certification of the documents, the `name` parameter and the content type are certified
+pub const NIP05_EXPRESSION: Expression = Expression {
+    query_parameters: &["name"],
+    response_headers: &["content-type"],
+    cel: concat!(
+        "default_certification(ValidationArgs{certification:Certification{",
+        "request_certification:RequestCertification{certified_request_headers:[],certified_query_parameters:[\"name\"]},",
+        "response_certification:ResponseCertification{certified_response_headers:ResponseHeaderList{headers:[\"content-type\"]}}",
+        "}})"
+    ),
+};
+
+/// Request of the document listing `name`, as certified
+pub fn name_request(name: &str) -> HttpRequest {
+    HttpRequest::new("GET", format!("{NOSTR_JSON_PATH}?name={name}"), vec![])
+}
+
+/// NIP05 error
+#[derive(thiserror::Error, Debug)]
+pub enum Nip05Error {
+
+    #[error("Invalid identifier: {0}")]
+    InvalidIdentifier(String),
+
+    #[error(transparent)]
+    Name(#[from] NameError),
+
+    #[error("Expected a metadata event, found kind {0}")]
+    NotMetadata(Kind),
+
+    #[error("Metadata: {0}")]
+    Metadata(#[from] MetadataError),
+
+    #[error("Metadata has no nip05 field")]
+    MissingNip05,
+
+    #[error("Json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Name not listed in the document: {0}")]
+    UnknownName(String),
+
+    #[error("Name is mapped to another public key")]
+    PublicKeyMismatch,
+}
+
+/// `name@domain` identifier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nip05Identifier {
+    /// Lowercase local part, `_` for the root of the domain
+    pub name: String,
+    pub domain: String,
+}
+
+impl Nip05Identifier {
+    /// Url of the document listing the name
+    pub fn url(&self) -> String {
+        format!("https://{}{}?name={}", self.domain, NOSTR_JSON_PATH, self.name)
+    }
+}
+
+impl FromStr for Nip05Identifier {
+    type Err = Nip05Error;
+
+    /// A bare domain is the root identifier `_@domain`
+    fn from_str(identifier: &str) -> Result<Self, Self::Err> {
+        let (name, domain) = identifier.trim().rsplit_once('@').unwrap_or(("_", identifier.trim()));
+        if domain.is_empty() || domain.contains(['/', '?', '#', ' ']) {
+            return Err(Nip05Error::InvalidIdentifier(String::from(identifier)));
+        }
+        Ok(Self {
+            name: normalize_name(name)?,
+            domain: domain.to_ascii_lowercase(),
+        })
+    }
+}
+
+impl fmt::Display for Nip05Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.domain)
+    }
+}
+
+/// Content of `nostr.json`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Nip05Document {
+    pub names: BTreeMap<String, NostrPubKey>,
+    /// Preferred relays by public key
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub relays: BTreeMap<NostrPubKey, Vec<String>>,
+}
+
+impl Nip05Document {
+    /// List `name`, relays are omitted when empty
+    pub fn insert(&mut self, name: String, public_key: NostrPubKey, relays: Vec<String>) {
+        if !relays.is_empty() {
+            self.relays.insert(public_key.clone(), relays);
+        }
+        self.names.insert(name, public_key);
+    }
+
+    /// Check that `name` maps to `public_key`, names are compared lowercase
+    pub fn verify(&self, name: &str, public_key: &NostrPubKey) -> Result<(), Nip05Error> {
+        let listed = self
+            .names
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+            .ok_or_else(|| Nip05Error::UnknownName(String::from(name)))?;
+        match listed == public_key {
+            true => Ok(()),
+            false => Err(Nip05Error::PublicKeyMismatch),
+        }
+    }
+
+    /// Relays listed for `public_key`
+    pub fn relays_of(&self, public_key: &NostrPubKey) -> &[String] {
+        self.relays.get(public_key).map_or(&[], Vec::as_slice)
+    }
+
+    /// `200` JSON response with the expression header of [`NIP05_EXPRESSION`]
+    pub fn to_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::new(200, "application/json", self.as_json());
+        response.headers.push((String::from(CERTIFICATE_EXPRESSION_HEADER), String::from(NIP05_EXPRESSION.cel)));
+        response
+    }
+}
+
+impl JsonUtil for Nip05Document {
+    type Err = Nip05Error;
+}
+
+/// Check the `nip05` claim of the metadata `event` against `document_json`
+///
+/// Offline: the caller fetches [`Nip05Identifier::url`] and passes the body.
+/// Returns the claimed identifier.
+pub fn verify<T>(event: &EventData, document_json: T) -> Result<Nip05Identifier, Nip05Error>
+where
+    T: AsRef<[u8]>,
+{
+    if event.kind != Kind::Metadata {
+        return Err(Nip05Error::NotMetadata(event.kind));
+    }
+    let metadata: Metadata = Metadata::from_json(event.content.as_str())?;
+    let identifier: Nip05Identifier = metadata.nip05.ok_or(Nip05Error::MissingNip05)?.parse()?;
+    Nip05Document::from_json(document_json)?.verify(identifier.name.as_str(), &event.pubkey)?;
+    Ok(identifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::rng::CryptoHashRng;
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, NostrSigningKey};
+
+    const BOB_KEY: &str = "b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9";
+    const DOCUMENT: &str = r#"{
+        "names": {"bob": "b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9"},
+        "relays": {"b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9": ["wss://relay.example.com"]}
+    }"#;
+
+    fn signer(skey: &str) -> NostrSigningKey {
+        let ecda = AsymmetricKeyImpl();
+        ecda.new_keypair(ecda.parse_secret_key_from_hex(skey).unwrap()).unwrap()
+    }
+
+    fn metadata_event(signer: &NostrSigningKey, metadata: &Metadata) -> EventData {
+        EventBuilder::metadata(metadata).sign(signer, &mut CryptoHashRng::from_seed([3u8; 32])).unwrap()
+    }
+
+    #[test]
+    fn test_identifier() {
+        let identifier: Nip05Identifier = "Bob@Example.com".parse().unwrap();
+        assert_eq!(identifier.to_string(), "bob@example.com");
+        assert_eq!(identifier.url(), "https://example.com/.well-known/nostr.json?name=bob");
+        assert_eq!(Nip05Identifier::from_str("example.com").unwrap().name, "_");
+        assert!(Nip05Identifier::from_str("b b@example.com").is_err());
+        assert!(Nip05Identifier::from_str("bob@").is_err());
+    }
+
+    #[test]
+    fn test_document() {
+        let document = Nip05Document::from_json(DOCUMENT).unwrap();
+        let bob = NostrPubKey::from_str(BOB_KEY).unwrap();
+        assert!(document.verify("BOB", &bob).is_ok());
+        assert_eq!(document.relays_of(&bob), ["wss://relay.example.com"]);
+        assert_eq!(Nip05Document::from_json(document.as_json()).unwrap(), document);
+
+        let mut inserted = Nip05Document::default();
+        inserted.insert(String::from("bob"), bob.clone(), vec![String::from("wss://relay.example.com")]);
+        assert_eq!(inserted, document);
+        let mut without_relays = Nip05Document::default();
+        without_relays.insert(String::from("bob"), bob, vec![]);
+        assert_eq!(without_relays.as_json(), format!(r#"{{"names":{{"bob":"{BOB_KEY}"}}}}"#));
+    }
+
+    #[test]
+    fn test_verify() {
+        let skey = "6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+        let keys = signer(skey);
+        let public_key = NostrPubKey(AsymmetricKeyImpl().pubkey_from_pair(&keys));
+        let mut document = Nip05Document::default();
+        document.insert(String::from("alice"), public_key, vec![]);
+        let document = document.as_json();
+
+        let event = metadata_event(&keys, &Metadata::new().name("alice").nip05("alice@example.com"));
+        assert_eq!(verify(&event, document.as_str()).unwrap().to_string(), "alice@example.com");
+
+        let impostor = metadata_event(&signer("7f3b02c9d1cd71d0d3b16b3c4c2d6b87b8c6e58ba4f6b0a8c4e0d8b0d8b9f6a1"), &Metadata::new().nip05("alice@example.com"));
+        assert!(matches!(verify(&impostor, document.as_str()), Err(Nip05Error::PublicKeyMismatch)));
+
+        let unknown = metadata_event(&keys, &Metadata::new().nip05("carol@example.com"));
+        assert!(matches!(verify(&unknown, document.as_str()), Err(Nip05Error::UnknownName(_))));
+        assert!(matches!(verify(&metadata_event(&keys, &Metadata::new()), document.as_str()), Err(Nip05Error::MissingNip05)));
+
+        let note = EventBuilder::text_note("alice@example.com", []).sign(&keys, &mut CryptoHashRng::from_seed([3u8; 32])).unwrap();
+        assert!(matches!(verify(&note, document.as_str()), Err(Nip05Error::NotMetadata(Kind::TextNote))));
+        assert!(matches!(verify(&event, "{}"), Err(Nip05Error::Json(_))));
+    }
+}
@@ -0,0 +1,225 @@
+//! NIP11 relay information document
+//!
+//! Served at the relay url to requests with `Accept: application/nostr+json`.
+//! The limitations come from the [`AdmissionPolicy`], the rest is set by the controllers.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/11.md>
+
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::http::HttpResponse;
+use crate::relay::AdmissionPolicy;
+use crate::signing::NostrPubKey;
+use crate::util::jsonutil::JsonUtil;
+use crate::util::basecore::ParseError;
+
+/// Path of the document
+pub const RELAY_INFO_PATH: &str = "/";
+
+/// Media type of the document
+pub const NOSTR_JSON_MIME: &str = "application/nostr+json";
+
+/// NIPs implemented by the relay
+pub const SUPPORTED_NIPS: &[u16] = &[1, 5, 9, 11, 13, 26, 40, 45, 50];
+
+/// Fee of a [`Fees`] category
+#[derive(CandidType, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Fee {
+    pub amount: u64,
+    /// e.g. `msats`
+    pub unit: String,
+    /// Validity of a subscription in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<u64>,
+    /// Kinds charged by a publication fee
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kinds: Option<Vec<u16>>,
+}
+
+/// Fees of the relay
+#[derive(CandidType, Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fees {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub admission: Vec<Fee>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subscription: Vec<Fee>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub publication: Vec<Fee>,
+}
+
+impl Fees {
+    /// Check if the relay charges nothing
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.admission.is_empty() && self.subscription.is_empty() && self.publication.is_empty()
+    }
+}
+
+/// Relay information set by the controllers
+#[derive(CandidType, Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelayInfo {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Public key of the operator
+    #[serde(default)]
+    pub pubkey: Option<NostrPubKey>,
+    /// Alternative contact of the operator, e.g. a `mailto:` URI
+    #[serde(default)]
+    pub contact: Option<String>,
+    #[serde(default)]
+    pub fees: Fees,
+}
+
+/// Limitations of the relay
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limitation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_content_length: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_pow_difficulty: Option<u8>,
+    /// Always `false`, NIP42 authentication is not supported
+    #[serde(default)]
+    pub auth_required: bool,
+    #[serde(default)]
+    pub payment_required: bool,
+}
+
+/// NIP11 document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RelayInformationDocument {
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<NostrPubKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+    pub supported_nips: Vec<u16>,
+    pub version: String,
+    pub limitation: Limitation,
+    #[serde(default, skip_serializing_if = "Fees::is_empty")]
+    pub fees: Fees,
+}
+
+impl RelayInformationDocument {
+    /// Document of the relay configured with `info` and `policy`
+    pub fn new(info: &RelayInfo, policy: &AdmissionPolicy) -> Self {
+        Self {
+            name: info.name.clone(),
+            description: info.description.clone(),
+            pubkey: info.pubkey.clone(),
+            contact: info.contact.clone(),
+            supported_nips: SUPPORTED_NIPS.to_vec(),
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            limitation: Limitation {
+                max_content_length: policy.max_content_length,
+                min_pow_difficulty: Some(policy.min_pow).filter(|difficulty| *difficulty > 0),
+                auth_required: false,
+                payment_required: !info.fees.is_empty(),
+            },
+            fees: info.fees.clone(),
+        }
+    }
+
+    /// `200` response with the JSON document
+    pub fn to_response(&self) -> HttpResponse {
+        HttpResponse::new(200, NOSTR_JSON_MIME, self.as_json())
+    }
+}
+
+impl JsonUtil for RelayInformationDocument {
+    type Err = ParseError;
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use super::*;
+    use crate::http::{handle_request, Expression, HeaderField, HttpContext, HttpRequest, Nip05Document};
+    use crate::nostr::event_data::EventData;
+    use crate::nostr::event_id::EventId;
+
+    const OPERATOR: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    fn info() -> RelayInfo {
+        RelayInfo {
+            name: String::from("freederation"),
+            description: String::from("Nostr relay on the Internet Computer"),
+            pubkey: Some(NostrPubKey::from_str(OPERATOR).unwrap()),
+            contact: Some(String::from("mailto:relay@example.com")),
+            fees: Fees {
+                publication: vec![Fee { amount: 100, unit: String::from("msats"), period: None, kinds: Some(vec![4]) }],
+                ..Default::default()
+            },
+        }
+    }
+
+    fn policy() -> AdmissionPolicy {
+        AdmissionPolicy { min_pow: 16, max_content_length: Some(8196) }
+    }
+
+    struct TestContext;
+
+    impl HttpContext for TestContext {
+        fn relay_info(&self) -> RelayInformationDocument {
+            RelayInformationDocument::new(&info(), &policy())
+        }
+
+        fn nip05(&self, _name: &str) -> Nip05Document {
+            Nip05Document::default()
+        }
+
+        fn event(&self, _id: &EventId) -> Option<EventData> {
+            None
+        }
+
+        fn certificate_header(&self, _request: &HttpRequest, _response: &HttpResponse, _expression: Option<&Expression>) -> Option<HeaderField> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_document_json() {
+        let request = HttpRequest::new("GET", "/", vec![(String::from("accept"), String::from(NOSTR_JSON_MIME))]);
+        let response = handle_request(&request, &TestContext);
+        assert_eq!(response.status_code, 200);
+
+        let json: serde_json::Value = serde_json::from_slice(response.body.as_slice()).unwrap();
+        assert_eq!(json["name"], "freederation");
+        assert_eq!(json["pubkey"], OPERATOR);
+        assert_eq!(json["contact"], "mailto:relay@example.com");
+        assert_eq!(json["supported_nips"], serde_json::json!(SUPPORTED_NIPS));
+        assert_eq!(json["limitation"]["max_content_length"], 8196);
+        assert_eq!(json["limitation"]["min_pow_difficulty"], 16);
+        assert_eq!(json["limitation"]["auth_required"], false);
+        assert!(!json["supported_nips"].as_array().unwrap().contains(&serde_json::json!(42)));
+        assert_eq!(json["limitation"]["payment_required"], true);
+        assert_eq!(json["fees"]["publication"][0], serde_json::json!({"amount": 100, "unit": "msats", "kinds": [4]}));
+        assert!(json["fees"].get("admission").is_none());
+
+        let parsed = RelayInformationDocument::from_json(response.body).unwrap();
+        assert_eq!(parsed, RelayInformationDocument::new(&info(), &policy()));
+    }
+
+    #[test]
+    fn test_auth_required() {
+        let request = HttpRequest::new("GET", RELAY_INFO_PATH, vec![(String::from("Accept"), String::from(NOSTR_JSON_MIME))]);
+        let response = handle_request(&request, &TestContext);
+        let json: serde_json::Value = serde_json::from_slice(response.body.as_slice()).unwrap();
+        assert_eq!(json["limitation"].get("auth_required"), Some(&serde_json::Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_default_document() {
+        let json: serde_json::Value =
+            serde_json::from_str(RelayInformationDocument::new(&RelayInfo::default(), &AdmissionPolicy::default()).as_json().as_str())
+                .unwrap();
+        assert!(json.get("pubkey").is_none());
+        assert!(json.get("fees").is_none());
+        assert!(json["limitation"].get("min_pow_difficulty").is_none());
+        assert!(json["limitation"].get("max_content_length").is_none());
+    }
+}
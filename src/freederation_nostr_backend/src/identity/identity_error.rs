@@ -0,0 +1,27 @@
+use ic_cdk::api::call::RejectionCode;
+
+use crate::nostr::event_error::EventDataError;
+use crate::signing::AsymmetricKeyError;
+
+/// [`SchnorrSigner`](crate::identity::SchnorrSigner) error
+#[derive(thiserror::Error, Debug)]
+pub enum IdentityError {
+
+    #[error("Signer call rejected ({0:?}): {1}")]
+    CallRejected(RejectionCode, String),
+
+    #[error("Anonymous principal has no identity")]
+    Anonymous,
+
+    #[error("Invalid key: {0}")]
+    Keys(#[from] AsymmetricKeyError),
+
+    #[error("Invalid event: {0}")]
+    Event(#[from] EventDataError),
+}
+
+impl From<(RejectionCode, String)> for IdentityError {
+    fn from((code, message): (RejectionCode, String)) -> Self {
+        Self::CallRejected(code, message)
+    }
+}
@@ -0,0 +1,191 @@
+//! Canister-held Nostr identities
+//!
+//! Every principal owns a BIP340 key derived from the threshold key of the subnet with the
+//! principal as derivation path. The canister signs on behalf of its caller, the secret key
+//! is never reconstructed nor sent over the wire.
+
+use std::future::Future;
+
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::event_builder::EventBuilder;
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_kind::Kind;
+use crate::nostr::tag::TagData;
+use crate::signing::{NostrPubKey, NostrSignature};
+use crate::util::basecore::ParseError;
+use crate::util::jsonutil::JsonUtil;
+use crate::util::time::{TimeSupplier, Timestamp};
+
+pub mod identity_error;
+pub mod threshold;
+
+pub use self::identity_error::IdentityError;
+pub use self::threshold::{SignerConfig, ThresholdSchnorrSigner};
+
+/// Derivation path of a threshold key
+pub type DerivationPath = Vec<Vec<u8>>;
+
+/// Derivation path of the key owned by `principal`
+pub fn principal_derivation_path(principal: &Principal) -> Result<DerivationPath, IdentityError> {
+    if *principal == Principal::anonymous() {
+        return Err(IdentityError::Anonymous);
+    }
+    Ok(vec![principal.as_slice().to_vec()])
+}
+
+/// BIP340 signer of derived keys
+pub trait SchnorrSigner {
+    /// Public key derived with `derivation_path`
+    fn public_key(&self, derivation_path: &DerivationPath) -> impl Future<Output = Result<NostrPubKey, IdentityError>>;
+
+    /// Sign `message` with the key derived with `derivation_path`
+    fn sign(&self, message: &[u8], derivation_path: &DerivationPath) -> impl Future<Output = Result<NostrSignature, IdentityError>>;
+}
+
+/// Event to be signed by the canister, the author is the key of the caller
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, CandidType)]
+pub struct EventTemplate {
+    /// Kind
+    pub kind: Kind,
+    /// Vector of [`TagData`]
+    #[serde(default)]
+    pub tags: Vec<TagData>,
+    /// Content
+    #[serde(default)]
+    pub content: String,
+    /// Timestamp (seconds), the canister time when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<Timestamp>,
+}
+
+impl JsonUtil for EventTemplate {
+    type Err = ParseError;
+}
+
+impl From<EventTemplate> for EventBuilder {
+    fn from(template: EventTemplate) -> Self {
+        let builder = EventBuilder::new(template.kind, template.content, template.tags);
+        match template.created_at {
+            Some(created_at) => builder.custom_created_at(created_at),
+            None => builder,
+        }
+    }
+}
+
+/// Build and sign an event with the key derived with `derivation_path`
+pub async fn sign_event<S, T>(
+    signer: &S,
+    derivation_path: &DerivationPath,
+    builder: EventBuilder,
+    supplier: &T,
+) -> Result<EventData, IdentityError>
+where
+    S: SchnorrSigner,
+    T: TimeSupplier,
+{
+    let public_key = signer.public_key(derivation_path).await?;
+    let unsigned = builder.into_unsigned_event_with_supplier(public_key, supplier);
+    let sig = signer.sign(unsigned.id.as_bytes(), derivation_path).await?;
+    Ok(unsigned.add_signature(sig)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    use rand_core::SeedableRng;
+
+    use crate::encryption::{Sha256Hash, Sha2Digest};
+    use crate::rng::CryptoHashRng;
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, NostrSigningKey};
+    use crate::util::nostrbech32_params::ToBech32;
+    use crate::util::time::MockTimeSupplier;
+
+    /// Stand-in for the threshold key, derives keys from a local master secret
+    struct SoftwareSigner {
+        master: [u8; 32],
+    }
+
+    impl SoftwareSigner {
+        fn derive(&self, derivation_path: &DerivationPath) -> NostrSigningKey {
+            let mut hasher = Sha256Hash::new();
+            hasher.update(self.master);
+            for segment in derivation_path {
+                hasher.update((segment.len() as u64).to_be_bytes());
+                hasher.update(segment);
+            }
+            let ecda = AsymmetricKeyImpl();
+            let secret = ecda.new_secret_key_from_bytes(hasher.finalize().as_ref()).unwrap();
+            ecda.new_keypair(secret).unwrap()
+        }
+    }
+
+    impl SchnorrSigner for SoftwareSigner {
+        async fn public_key(&self, derivation_path: &DerivationPath) -> Result<NostrPubKey, IdentityError> {
+            let ecda = AsymmetricKeyImpl();
+            Ok(NostrPubKey(ecda.pubkey_from_pair(&self.derive(derivation_path))))
+        }
+
+        async fn sign(&self, message: &[u8], derivation_path: &DerivationPath) -> Result<NostrSignature, IdentityError> {
+            let ecda = AsymmetricKeyImpl();
+            let mut rngcore = CryptoHashRng::from_seed(Sha256Hash::digest(message).into());
+            let sig = ecda.generate_signature_from_bytes(message, &self.derive(derivation_path), &mut rngcore)?;
+            Ok(NostrSignature(sig))
+        }
+    }
+
+    /// Run a future that never pends, as the [`SoftwareSigner`] ones
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut context = Context::from_waker(Waker::noop());
+        match pin!(future).as_mut().poll(&mut context) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future is pending"),
+        }
+    }
+
+    #[test]
+    fn test_sign_event_per_principal() {
+        let signer = SoftwareSigner { master: [9u8; 32] };
+        let supplier = MockTimeSupplier::new(Timestamp::from(1_700_000_000));
+        let alice = principal_derivation_path(&Principal::from_slice(&[1, 2, 3])).unwrap();
+        let bob = principal_derivation_path(&Principal::from_slice(&[4, 5, 6])).unwrap();
+
+        let alice_key = block_on(signer.public_key(&alice)).unwrap();
+        let bob_key = block_on(signer.public_key(&bob)).unwrap();
+        assert_ne!(alice_key, bob_key);
+        assert!(alice_key.to_bech32().unwrap().starts_with("npub1"));
+
+        let event = block_on(sign_event(&signer, &alice, EventBuilder::text_note("hello", []), &supplier)).unwrap();
+        assert!(event.verify().is_ok());
+        assert_eq!(event.pubkey, alice_key);
+        assert_eq!(event.created_at, Timestamp::from(1_700_000_000));
+
+        assert!(matches!(
+            principal_derivation_path(&Principal::anonymous()),
+            Err(IdentityError::Anonymous)
+        ));
+    }
+
+    #[test]
+    fn test_event_template() {
+        let signer = SoftwareSigner { master: [9u8; 32] };
+        let supplier = MockTimeSupplier::new(Timestamp::from(1_700_000_000));
+        let path = principal_derivation_path(&Principal::from_slice(&[7])).unwrap();
+
+        let template = EventTemplate::from_json(r#"{"kind":1,"tags":[["t","nostr"]],"content":"tagged","created_at":1600000000}"#).unwrap();
+        let event = block_on(sign_event(&signer, &path, template.into(), &supplier)).unwrap();
+        assert_eq!(event.kind, Kind::TextNote);
+        assert_eq!(event.created_at, Timestamp::from(1_600_000_000));
+        assert_eq!(event.tags.len(), 1);
+
+        let template = EventTemplate::from_json(r#"{"kind":0}"#).unwrap();
+        let event = block_on(sign_event(&signer, &path, template.into(), &supplier)).unwrap();
+        assert_eq!(event.created_at, Timestamp::from(1_700_000_000));
+        assert!(EventTemplate::from_json(r#"{"content":"no kind"}"#).is_err());
+    }
+}
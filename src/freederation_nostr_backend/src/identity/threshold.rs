@@ -0,0 +1,216 @@
+//! Threshold Schnorr signer
+//!
+//! Calls `schnorr_public_key` and `sign_with_schnorr` of the management canister, or of any
+//! canister exposing the same interface, e.g. a local stand-in during development.
+//!
+//! <https://internetcomputer.org/docs/current/references/ic-interface-spec#ic-sign_with_schnorr>
+
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::call::{call, call_with_payment128};
+use serde::Serialize;
+
+use crate::identity::{DerivationPath, IdentityError, SchnorrSigner};
+use crate::signing::{AsymmetricKeyError, NostrPubKey, NostrSignature};
+
+/// Key name of the local replica
+pub const DEFAULT_KEY_NAME: &str = "dfx_test_key";
+
+/// Cycles attached to `sign_with_schnorr`, the cost with `key_1` on a 34 node subnet
+///
+/// Unused cycles are refunded.
+pub const DEFAULT_SIGN_CYCLES: u64 = 26_153_846_153;
+
+/// Size of a SEC1 compressed public key
+const SEC1_COMPRESSED_SIZE: usize = 33;
+
+/// Schnorr signature algorithm, only BIP340 is used by Nostr
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchnorrAlgorithm {
+    #[serde(rename = "bip340secp256k1")]
+    Bip340Secp256k1,
+}
+
+/// Threshold key of the subnet
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SchnorrKeyId {
+    pub algorithm: SchnorrAlgorithm,
+    pub name: String,
+}
+
+/// Argument of `schnorr_public_key`
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct SchnorrPublicKeyArgument {
+    pub canister_id: Option<Principal>,
+    pub derivation_path: DerivationPath,
+    pub key_id: SchnorrKeyId,
+}
+
+/// Result of `schnorr_public_key`
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct SchnorrPublicKeyResult {
+    /// SEC1 compressed public key
+    pub public_key: Vec<u8>,
+    pub chain_code: Vec<u8>,
+}
+
+/// Argument of `sign_with_schnorr`
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct SignWithSchnorrArgument {
+    pub message: Vec<u8>,
+    pub derivation_path: DerivationPath,
+    pub key_id: SchnorrKeyId,
+}
+
+/// Result of `sign_with_schnorr`
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct SignWithSchnorrResult {
+    pub signature: Vec<u8>,
+}
+
+/// Target of the threshold signer
+#[derive(CandidType, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignerConfig {
+    /// Canister serving the threshold Schnorr API, the management canister on a real subnet
+    pub canister_id: Principal,
+    /// Key name, `dfx_test_key` locally, `test_key_1` or `key_1` on mainnet
+    pub key_name: String,
+    /// Cycles attached to every signature request
+    pub sign_cycles: u64,
+    /// Principals allowed to sign besides the controllers, none by default
+    #[serde(default)]
+    pub signers: Option<Vec<Principal>>,
+}
+
+impl Default for SignerConfig {
+    fn default() -> Self {
+        Self {
+            canister_id: Principal::management_canister(),
+            key_name: String::from(DEFAULT_KEY_NAME),
+            sign_cycles: DEFAULT_SIGN_CYCLES,
+            signers: None,
+        }
+    }
+}
+
+impl SignerConfig {
+    /// BIP340 key id of the configured key
+    pub fn key_id(&self) -> SchnorrKeyId {
+        SchnorrKeyId {
+            algorithm: SchnorrAlgorithm::Bip340Secp256k1,
+            name: self.key_name.clone(),
+        }
+    }
+
+    /// Check if `principal` is allowed to sign, controllers are checked by the caller
+    pub fn may_sign(&self, principal: &Principal) -> bool {
+        self.signers.as_ref().is_some_and(|signers| signers.contains(principal))
+    }
+}
+
+/// Convert a SEC1 compressed public key into its BIP340 x-only [`NostrPubKey`]
+pub fn x_only_public_key(public_key: &[u8]) -> Result<NostrPubKey, AsymmetricKeyError> {
+    match public_key.len() {
+        SEC1_COMPRESSED_SIZE => NostrPubKey::try_from(&public_key[1..]),
+        _ => Err(AsymmetricKeyError::InvalidPublicKey),
+    }
+}
+
+/// [`SchnorrSigner`] backed by the threshold Schnorr API
+#[derive(Debug, Clone, Default)]
+pub struct ThresholdSchnorrSigner {
+    config: SignerConfig,
+}
+
+impl ThresholdSchnorrSigner {
+    /// New signer calling the canister of `config`
+    #[inline]
+    pub fn new(config: SignerConfig) -> Self {
+        Self { config }
+    }
+
+    /// Get config
+    #[inline]
+    pub fn config(&self) -> &SignerConfig {
+        &self.config
+    }
+}
+
+impl SchnorrSigner for ThresholdSchnorrSigner {
+    async fn public_key(&self, derivation_path: &DerivationPath) -> Result<NostrPubKey, IdentityError> {
+        let argument = SchnorrPublicKeyArgument {
+            canister_id: None,
+            derivation_path: derivation_path.clone(),
+            key_id: self.config.key_id(),
+        };
+        let (result,): (SchnorrPublicKeyResult,) =
+            call(self.config.canister_id, "schnorr_public_key", (argument,)).await?;
+        Ok(x_only_public_key(result.public_key.as_slice())?)
+    }
+
+    async fn sign(&self, message: &[u8], derivation_path: &DerivationPath) -> Result<NostrSignature, IdentityError> {
+        let argument = SignWithSchnorrArgument {
+            message: message.to_vec(),
+            derivation_path: derivation_path.clone(),
+            key_id: self.config.key_id(),
+        };
+        let (result,): (SignWithSchnorrResult,) = call_with_payment128(
+            self.config.canister_id,
+            "sign_with_schnorr",
+            (argument,),
+            u128::from(self.config.sign_cycles),
+        )
+        .await?;
+        Ok(NostrSignature::try_from(result.signature.as_slice())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps};
+
+    #[test]
+    fn test_x_only_public_key() {
+        let ecda = AsymmetricKeyImpl();
+        let secret = ecda.new_secret_key_from_bytes(&[3u8; 32]).unwrap();
+        let keypair = ecda.new_keypair(secret).unwrap();
+        let expected = NostrPubKey(ecda.pubkey_from_pair(&keypair));
+
+        let x_only = ecda.public_key_to_bytes(&expected.0);
+        for parity in [0x02u8, 0x03u8] {
+            let mut sec1 = vec![parity];
+            sec1.extend_from_slice(&x_only);
+            assert_eq!(x_only_public_key(sec1.as_slice()).unwrap(), expected);
+        }
+
+        assert!(x_only_public_key(&x_only).is_err());
+        assert!(x_only_public_key(&[]).is_err());
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = SignerConfig::default();
+        assert_eq!(config.canister_id, Principal::management_canister());
+        assert_eq!(config.key_id().name, DEFAULT_KEY_NAME);
+        assert_eq!(config.key_id().algorithm, SchnorrAlgorithm::Bip340Secp256k1);
+        assert!(!config.may_sign(&Principal::from_slice(&[1])));
+    }
+
+    #[test]
+    fn test_may_sign() {
+        let alice = Principal::from_slice(&[1]);
+        let bob = Principal::from_slice(&[2]);
+        let config = SignerConfig { signers: Some(vec![alice]), ..SignerConfig::default() };
+        assert!(config.may_sign(&alice));
+        assert!(!config.may_sign(&bob));
+        assert!(!config.may_sign(&Principal::anonymous()));
+
+        // Configurations stored before the allow-list deny everyone
+        let stored: SignerConfig = serde_json::from_str(
+            r#"{"canister_id":"aaaaa-aa","key_name":"key_1","sign_cycles":1}"#,
+        ).unwrap();
+        assert_eq!(stored.signers, None);
+        assert!(!stored.may_sign(&alice));
+    }
+}
@@ -1,111 +1,555 @@
-use rng::CryptoHashRng;
+use rng::{CryptoHashRng, RngState};
 use signing::AsymmetricKeyOps;
-use hex_conservative::{DisplayHex, FromHex};
-use candid::{CandidType,  Deserialize};
-use std::{borrow::Borrow, cell::RefCell};
-use rand_core::{CryptoRng,SeedableRng, RngCore};
+use hex_conservative::DisplayHex;
+use candid::{CandidType,  Deserialize, Principal};
+use std::cell::RefCell;
+use std::time::Duration;
+use rand_core::RngCore;
+use ic_stable_structures::{DefaultMemoryImpl, StableCell};
+use api::{ApiError, ApiResult};
+use http::{CertifiedResponses, Expression, HeaderField, HttpContext, HttpRequest, HttpResponse, IcCertifiedData, Nip05Document, RelayInfo, RelayInformationDocument, NIP05_EXPRESSION};
+use http::relay_info::RELAY_INFO_PATH;
+use identity::{principal_derivation_path, EventTemplate, SchnorrSigner, SignerConfig, ThresholdSchnorrSigner};
+use nostr::event_data::EventData;
+use nostr::event_id::EventId;
+use nostr::filter::Filter;
+use signing::NostrPubKey;
+use store::{EventCount, EventStore, KeyBindings, NameRegistry, PublishStatus, QueryOptions};
+use store::name_registry::normalize_name;
+use relay::{AdmissionPolicy, RelayContext, RelayError};
+use store::memory::{StoreMemory, StoreMemoryManager, RELAY_INFO_MEMORY_ID, RELAY_POLICY_MEMORY_ID, RNG_STATE_MEMORY_ID, SIGNER_CONFIG_MEMORY_ID};
+use util::jsonutil::JsonUtil;
+use util::nostrbech32_params::{FromBech32, ToBech32};
+use util::time::{default_time_supplier, DefaultTimeSupplier};
 
 
+mod api;
 mod signing;
 mod encryption;
 mod rng;
 mod util;
 mod nostr;
+mod store;
+mod identity;
+mod http;
+mod relay;
 
-#[derive(CandidType, Deserialize)]
-struct RNG_SEED {
-    inner: crate::rng::CryptoHashSeed
+
+/// Statistics of the expired events purge
+#[derive(CandidType, Deserialize, Clone, Default)]
+struct PurgeStats {
+    runs: u64,
+    purged_total: u64,
+    last_purged: u64,
+    last_run: Option<u64>,
+}
+
+/// Clock of the event store, the IC system clock inside the canister
+type StoreTimeSupplier = DefaultTimeSupplier;
+
+/// Interval between two purges of expired events
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum number of expired events removed by one purge
+const PURGE_BATCH: usize = 500;
+
+/// Interval between two reseeds of the RNG from `raw_rand`
+const RESEED_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum number of names or events certified by one timer while rebuilding the certified responses
+const CERTIFY_BATCH: usize = 1_000;
+
+/// Maximum number of events indexed by one timer while rebuilding the search index
+const REINDEX_BATCH: usize = 1_000;
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<StoreMemoryManager> =
+        RefCell::new(StoreMemoryManager::init(DefaultMemoryImpl::default()));
+
+    static GLOBAL_RNG_STATE: RefCell<StableCell<RngState, StoreMemory>> = RefCell::new(
+        StableCell::init(MEMORY_MANAGER.with_borrow(|manager| manager.get(RNG_STATE_MEMORY_ID)), RngState::default())
+            .expect("RNG state memory")
+    );
+
+    static SIGNER_CONFIG: RefCell<StableCell<SignerConfig, StoreMemory>> = RefCell::new(
+        StableCell::init(MEMORY_MANAGER.with_borrow(|manager| manager.get(SIGNER_CONFIG_MEMORY_ID)), SignerConfig::default())
+            .expect("signer config memory")
+    );
+
+    static RELAY_POLICY: RefCell<StableCell<AdmissionPolicy, StoreMemory>> = RefCell::new(
+        StableCell::init(MEMORY_MANAGER.with_borrow(|manager| manager.get(RELAY_POLICY_MEMORY_ID)), AdmissionPolicy::default())
+            .expect("relay policy memory")
+    );
+
+    static RELAY_INFO: RefCell<StableCell<RelayInfo, StoreMemory>> = RefCell::new(
+        StableCell::init(MEMORY_MANAGER.with_borrow(|manager| manager.get(RELAY_INFO_MEMORY_ID)), RelayInfo::default())
+            .expect("relay info memory")
+    );
+
+    static EVENT_STORE: RefCell<EventStore<StoreMemory, StoreTimeSupplier>> = RefCell::new(
+        MEMORY_MANAGER.with_borrow(|manager| EventStore::init(|id| manager.get(id), default_time_supplier()))
+    );
+
+    static NAME_REGISTRY: RefCell<NameRegistry<StoreMemory>> = RefCell::new(
+        MEMORY_MANAGER.with_borrow(|manager| NameRegistry::init(|id| manager.get(id)))
+    );
+
+    static KEY_BINDINGS: RefCell<KeyBindings<StoreMemory, StoreTimeSupplier>> = RefCell::new(
+        MEMORY_MANAGER.with_borrow(|manager| KeyBindings::init(|id| manager.get(id), default_time_supplier()))
+    );
+
+    static CERTIFIED_RESPONSES: RefCell<CertifiedResponses<IcCertifiedData>> =
+        RefCell::new(CertifiedResponses::new(IcCertifiedData));
+
+    static GLOBAL_PURGE_STATS: RefCell<PurgeStats> = RefCell::new(PurgeStats::default());
+}
+
+/// Timers do not survive upgrades, they are started again in `post_upgrade`
+///
+/// `raw_rand` cannot be awaited in `init`, the first reseed runs from a zero delay timer.
+fn start_timers() {
+    ic_cdk_timers::set_timer_interval(PURGE_INTERVAL, purge_expired_events);
+    ic_cdk_timers::set_timer(Duration::ZERO, || ic_cdk::spawn(reseed_rng()));
+    ic_cdk_timers::set_timer_interval(RESEED_INTERVAL, || ic_cdk::spawn(reseed_rng()));
 }
 
-impl RNG_SEED
+/// Mix fresh entropy from the management canister into the RNG state
+///
+/// A failed call leaves the state untouched, the next interval retries.
+async fn reseed_rng() {
+    if let Ok((entropy,)) = ic_cdk::api::management_canister::main::raw_rand().await {
+        GLOBAL_RNG_STATE.with_borrow_mut(|cell| {
+            let mut state: RngState = cell.get().clone();
+            state.reseed(entropy.as_slice());
+            cell.set(state).expect("RNG state fits its memory");
+        });
+    }
+}
+
+/// Run `f` with the canister RNG, persisting its advanced state
+///
+/// Only meaningful in update calls, a query would discard the new state and replay the same output.
+fn with_rng<F, R>(f: F) -> ApiResult<R>
+where
+    F: FnOnce(&mut CryptoHashRng) -> R,
 {
-    fn new_seed() ->Self {
-        let seed = crate::rng::CryptoHashSeed::default();
-        Self{inner:seed}
-    }
-
-    fn assign_bytes(&mut self, buffer:&[u8])
-    {
-        let target_len = self.inner.len();
-        let lendiff = target_len.min(buffer.len());
-        self.inner[0..lendiff].copy_from_slice(&buffer[0..lendiff]);
-        if lendiff < target_len {
-            self.inner[lendiff..].fill(0);
+    GLOBAL_RNG_STATE.with_borrow_mut(|cell| {
+        let mut state: RngState = cell.get().clone();
+        let result = state.with_rng(f).map_err(|e| ApiError::Internal(e.to_string()))?;
+        cell.set(state).map_err(|e| ApiError::Internal(format!("{:?}", e)))?;
+        Ok(result)
+    })
+}
+
+/// Replace the threshold signer configuration when one is given
+fn update_signer_config(signer_config: Option<SignerConfig>) {
+    if let Some(config) = signer_config {
+        SIGNER_CONFIG.with_borrow_mut(|cell| cell.set(config)).expect("signer config fits its memory");
+    }
+}
+
+/// Signer of the canister-held identities
+fn threshold_signer() -> ThresholdSchnorrSigner {
+    ThresholdSchnorrSigner::new(SIGNER_CONFIG.with_borrow(|cell| cell.get().clone()))
+}
+
+fn purge_expired_events() {
+    let (now, purged) = EVENT_STORE.with_borrow_mut(|store| (store.now(), store.purge_expired(PURGE_BATCH)));
+    certify_events(None);
+    GLOBAL_PURGE_STATS.with_borrow_mut(|stats| {
+        stats.runs += 1;
+        stats.purged_total += purged;
+        stats.last_purged = purged;
+        stats.last_run = Some(now.as_u64());
+    });
+}
+
+/// Certify the NIP11 document, after any change of the relay information or policy
+fn certify_relay_info() {
+    let response = CanisterHttpContext.relay_info().to_response();
+    CERTIFIED_RESPONSES.with_borrow_mut(|responses| responses.certify(RELAY_INFO_PATH, response.body.as_slice()));
+}
+
+/// Certify the NIP05 document of `name`, after it is registered, updated or released
+fn certify_name(name: &str) {
+    let request = http::nip05::name_request(name);
+    let document = CanisterHttpContext.nip05(name);
+    CERTIFIED_RESPONSES.with_borrow_mut(|responses| match document.names.is_empty() {
+        true => responses.remove_request(&NIP05_EXPRESSION, &request),
+        false => responses.certify_request(&NIP05_EXPRESSION, &request, &document.to_response()),
+    });
+}
+
+/// Certify the `stored` event and stop certifying the events removed from the store
+fn certify_events(stored: Option<&EventData>) {
+    let removed: Vec<EventId> = EVENT_STORE.with_borrow_mut(|store| store.take_removed());
+    CERTIFIED_RESPONSES.with_borrow_mut(|responses| responses.certify_events(&removed, stored));
+}
+
+/// Rebuild the certified responses from the canister state
+///
+/// Names and events are certified by zero delay timers, [`CERTIFY_BATCH`] at a time,
+/// so `init` and `post_upgrade` stay within their instruction limit whatever the size of the store.
+/// Changes in between certify themselves.
+fn certify_all() {
+    certify_relay_info();
+    ic_cdk_timers::set_timer(Duration::ZERO, || certify_names_after(None));
+}
+
+/// Certify a batch of names following `after`, then the next batch or the events
+fn certify_names_after(after: Option<String>) {
+    let names: Vec<String> = NAME_REGISTRY.with_borrow(|registry| {
+        registry.iter_after(after.as_deref()).take(CERTIFY_BATCH).map(|(name, _)| name).collect()
+    });
+    for name in names.iter() {
+        certify_name(name);
+    }
+    match names.last() {
+        Some(last) if names.len() == CERTIFY_BATCH => {
+            let last = last.clone();
+            ic_cdk_timers::set_timer(Duration::ZERO, move || certify_names_after(Some(last)));
+        }
+        _ => {
+            ic_cdk_timers::set_timer(Duration::ZERO, || certify_events_after(None));
         }
     }
 }
 
+/// Certify a batch of events following `after`, then the next batch
+fn certify_events_after(after: Option<EventId>) {
+    let events: Vec<EventData> = EVENT_STORE.with_borrow(|store| store.iter_after(after.as_ref()).take(CERTIFY_BATCH).collect());
+    CERTIFIED_RESPONSES.with_borrow_mut(|responses| responses.certify_events(&[], events.iter()));
+    if let Some(last) = events.last().map(|event| event.id).filter(|_| events.len() == CERTIFY_BATCH) {
+        ic_cdk_timers::set_timer(Duration::ZERO, move || certify_events_after(Some(last)));
+    }
+}
 
-/// Signature Info
-#[derive(CandidType, Deserialize)]
-struct SIGNATURE_INFO {
-    signature_str: String,
-    verifying_key: String
+/// Fill the search index again after an upgrade changed its version
+///
+/// Events are indexed by zero delay timers, [`REINDEX_BATCH`] at a time, searches scan the store until the last one.
+fn reindex_search() {
+    if EVENT_STORE.with_borrow(|store| store.is_search_outdated()) {
+        ic_cdk_timers::set_timer(Duration::ZERO, || reindex_search_after(None));
+    }
 }
 
-thread_local! {
-    static GLOBAL_RNG_SEED: RefCell<RNG_SEED> = RefCell::new(RNG_SEED::new_seed());
+/// Index a batch of events following `after`, then the next batch
+fn reindex_search_after(after: Option<EventId>) {
+    let last: Option<EventId> = EVENT_STORE.with_borrow_mut(|store| store.reindex_search_after(after.as_ref(), REINDEX_BATCH));
+    if let Some(last) = last {
+        ic_cdk_timers::set_timer(Duration::ZERO, move || reindex_search_after(Some(last)));
+    }
 }
 
-#[ic_cdk::query]
-fn rng_seed() -> String {
-    GLOBAL_RNG_SEED.with(|rngseed| rngseed.borrow().inner.to_lower_hex_string())
+/// `signer_config` selects the threshold key, the management canister with `dfx_test_key` by default
+#[ic_cdk::init]
+fn init(signer_config: Option<SignerConfig>) {
+    update_signer_config(signer_config);
+    certify_all();
+    start_timers();
 }
 
-#[ic_cdk::update]
-fn update_rng_seed(val:String) {
-    GLOBAL_RNG_SEED.with_borrow_mut(|rngseed| {
-        let buffer = <Vec<u8> as FromHex >::from_hex(val.as_str()).unwrap();
-        rngseed.assign_bytes(buffer.as_slice());
-    })
+/// The event store lives in stable memory, so nothing is saved in `pre_upgrade`.
+/// Reopen it here, trapping rolls back an upgrade with an incompatible layout.
+/// The certified responses live on the heap and are rebuilt.
+///
+/// The signer configuration is kept unless a new one is given.
+#[ic_cdk::post_upgrade]
+fn post_upgrade(signer_config: Option<SignerConfig>) {
+    EVENT_STORE.with_borrow(|store| store.len());
+    update_signer_config(signer_config);
+    certify_all();
+    reindex_search();
+    start_timers();
+}
+
+/// Statistics of the periodic purge of NIP40 expired events since the last upgrade
+#[ic_cdk::query]
+fn purge_stats() -> PurgeStats {
+    GLOBAL_PURGE_STATS.with_borrow(|stats| stats.clone())
 }
 
 
 #[ic_cdk::query]
-fn greet(name: String) -> String {
+fn greet(name: String) -> ApiResult<String> {
     let ecda:signing::AsymmetricKeyImpl = signing::AsymmetricKeyImpl();
 
-    let secret = ecda.parse_secret_key_from_hex(name.as_str()).unwrap();
-    let keypar = ecda.new_keypair(secret).unwrap();
+    let secret = ecda.parse_secret_key_from_hex(name.as_str())?;
+    let keypar = ecda.new_keypair(secret)?;
     let outstr = keypar.verifying_key().to_bytes().to_lower_hex_string();
-    format!("Hello, {}!", outstr)
+    Ok(format!("Hello, {}!", outstr))
 }
 
-#[ic_cdk::query]
-fn generate_key() -> String {
-    let seed = GLOBAL_RNG_SEED.with(|rngseed| rngseed.borrow().inner.clone());
-    let mut rngcore = CryptoHashRng::from_seed(seed);
+#[ic_cdk::update]
+fn generate_key() -> ApiResult<String> {
     let ecda:signing::AsymmetricKeyImpl = signing::AsymmetricKeyImpl();
-    let skey = ecda.generate_secret_key(&mut rngcore).unwrap();
-    skey.to_bytes().to_lower_hex_string()
+    let skey = with_rng(|rngcore| ecda.generate_secret_key(rngcore))??;
+    Ok(skey.to_bytes().to_lower_hex_string())
 }
 
+/// `false` on a wrong signature, malformed keys and signatures are errors
 #[ic_cdk::query]
-fn schnorr_signature(msg:String, skey_str: String) -> SIGNATURE_INFO {
-    let seed = GLOBAL_RNG_SEED.with(|rngseed| rngseed.borrow().inner.clone());    
-    let mut rngcore = CryptoHashRng::from_seed(seed);        
+fn validate_schnorr(msg:String, validating_key_str: String, signature_str: String) -> ApiResult<bool> {    
     let ecda:signing::AsymmetricKeyImpl = signing::AsymmetricKeyImpl();
     
-    let skey = ecda.parse_secret_key(skey_str.as_str()).unwrap();
-    let keypair = ecda.new_keypair(skey).unwrap();
+    let verykey = ecda.parse_public_key(validating_key_str.as_str())?;
+    let signature_data= ecda.parse_signature(signature_str.as_str())?;
 
-    let rk = ecda.generate_signature(msg.as_str(), &keypair, &mut rngcore);
-    let signature_data = rk.unwrap();
+    Ok(ecda.verifying_signature(msg.as_str(),&verykey, &signature_data).is_ok())
+}
 
-    let outpk = keypair.verifying_key().to_bytes().to_lower_hex_string();
-    let outsignature = signature_data.to_bytes().to_lower_hex_string();
+/// Check an event against the admission policy, store and certify it
+fn store_event(event: EventData) -> Result<PublishStatus, RelayError> {
+    RELAY_POLICY.with_borrow(|cell| cell.get().check(&event))?;
+    let status = EVENT_STORE.with_borrow_mut(|store| store.publish(event.clone()))?;
+    certify_events(Some(&event).filter(|_| status != PublishStatus::Ephemeral));
+    Ok(status)
+}
 
-    SIGNATURE_INFO{signature_str:outsignature, verifying_key:outpk}
+/// Verify an event, check it against the admission policy and store it
+#[ic_cdk::update]
+fn publish_event(event: EventData) -> ApiResult<()> {
+    store_event(event)?;
+    Ok(())
 }
 
+/// Admission policy of `publish_event`
 #[ic_cdk::query]
-fn validate_schnorr(msg:String, validating_key_str: String, signature_str: String) -> bool {    
-    let ecda:signing::AsymmetricKeyImpl = signing::AsymmetricKeyImpl();
-    
-    let verykey = ecda.parse_public_key(validating_key_str.as_str()).unwrap();
-    let signature_data= ecda.parse_signature(&signature_str.as_str()).unwrap();
+fn get_relay_policy() -> AdmissionPolicy {
+    RELAY_POLICY.with_borrow(|cell| cell.get().clone())
+}
+
+/// Replace the admission policy, controllers only
+#[ic_cdk::update]
+fn set_relay_policy(policy: AdmissionPolicy) -> ApiResult<()> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err(ApiError::Unauthorized);
+    }
+    RELAY_POLICY.with_borrow_mut(|cell| cell.set(policy)).map_err(|e| ApiError::Internal(format!("{:?}", e)))?;
+    certify_relay_info();
+    Ok(())
+}
+
+/// Relay information of the NIP11 document
+#[ic_cdk::query]
+fn get_relay_info() -> RelayInfo {
+    RELAY_INFO.with_borrow(|cell| cell.get().clone())
+}
+
+/// Replace the relay information, controllers only
+#[ic_cdk::update]
+fn set_relay_info(info: RelayInfo) -> ApiResult<()> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err(ApiError::Unauthorized);
+    }
+    RELAY_INFO.with_borrow_mut(|cell| cell.set(info)).map_err(|e| ApiError::Internal(format!("{:?}", e)))?;
+    certify_relay_info();
+    Ok(())
+}
+
+/// Register a NIP05 name for the caller, replacing the name it held
+///
+/// `public_key` is the canister-held key of the caller, the default, or a key bound to the caller
+/// with `bind_key`. Returns the lowercase name.
+#[ic_cdk::update]
+async fn register_nip05(name: String, public_key: Option<NostrPubKey>, relays: Vec<String>) -> ApiResult<String> {
+    let caller = ic_cdk::caller();
+    let derivation_path = principal_derivation_path(&caller)?;
+    let held = threshold_signer().public_key(&derivation_path).await?;
+    let public_key = match public_key {
+        Some(public_key) if public_key != held => {
+            KEY_BINDINGS.with_borrow(|bindings| bindings.check_bound(&caller, &public_key))?;
+            public_key
+        }
+        _ => held,
+    };
+    let (released, name) = NAME_REGISTRY.with_borrow_mut(|registry| {
+        let released = registry.name_of(&caller);
+        registry.register(caller, name.as_str(), public_key, relays).map(|name| (released, name))
+    })?;
+    certify_name(name.as_str());
+    if let Some(released) = released.filter(|released| *released != name) {
+        certify_name(released.as_str());
+    }
+    Ok(name)
+}
+
+/// Release the NIP05 name of the caller, returning it
+#[ic_cdk::update]
+fn unregister_nip05() -> Option<String> {
+    let caller = ic_cdk::caller();
+    let name = NAME_REGISTRY.with_borrow_mut(|registry| registry.unregister(&caller));
+    if let Some(name) = name.as_deref() {
+        certify_name(name);
+    }
+    name
+}
+
+/// NIP05 name held by `principal`, the caller by default
+#[ic_cdk::query]
+fn get_nip05(principal: Option<Principal>) -> Option<String> {
+    let principal = principal.unwrap_or_else(ic_cdk::caller);
+    NAME_REGISTRY.with_borrow(|registry| registry.name_of(&principal))
+}
+
+/// Documents served over HTTP, read from the canister state
+struct CanisterHttpContext;
+
+impl HttpContext for CanisterHttpContext {
+    fn relay_info(&self) -> RelayInformationDocument {
+        let policy = RELAY_POLICY.with_borrow(|cell| cell.get().clone());
+        RELAY_INFO.with_borrow(|cell| RelayInformationDocument::new(cell.get(), &policy))
+    }
 
-    ecda.verifying_signature(msg.as_str(),&verykey, &signature_data).is_ok()
+    fn nip05(&self, name: &str) -> Nip05Document {
+        let mut document = Nip05Document::default();
+        if let Ok(name) = normalize_name(name) {
+            if let Some(entry) = NAME_REGISTRY.with_borrow(|registry| registry.get(name.as_str())) {
+                document.insert(name, entry.public_key, entry.relays);
+            }
+        }
+        document
+    }
+
+    fn event(&self, id: &EventId) -> Option<EventData> {
+        EVENT_STORE.with_borrow(|store| store.get(id))
+    }
+
+    fn certificate_header(&self, request: &HttpRequest, response: &HttpResponse, expression: Option<&Expression>) -> Option<HeaderField> {
+        let certificate: Vec<u8> = ic_cdk::api::data_certificate()?;
+        CERTIFIED_RESPONSES.with_borrow(|responses| {
+            responses.certificate_header(request, response, expression, certificate.as_slice())
+        })
+    }
+}
+
+/// HTTP gateway entry point, serves the NIP11 document, the NIP05 names and the stored events
+///
+/// Responses are certified, see [`CertifiedResponses`].
+#[ic_cdk::query]
+fn http_request(request: HttpRequest) -> HttpResponse {
+    http::handle_request(&request, &CanisterHttpContext)
+}
+
+/// Stored events matching a JSON array of filters, newest first
+///
+/// Deletion requests are hidden unless asked for by kind or with `include_deletions`.
+#[ic_cdk::query]
+fn query_events(filters_json: String, include_deletions: Option<bool>) -> ApiResult<Vec<EventData>> {
+    let filters: Vec<Filter> = serde_json::from_str(filters_json.as_str())?;
+    let options = QueryOptions { include_deletions: include_deletions.unwrap_or_default() };
+    Ok(EVENT_STORE.with_borrow(|store| store.query_with_options(filters.as_slice(), options)))
+}
+
+/// Event store behind the NIP01 messages, `read_only` in query calls whose changes are dropped
+struct CanisterRelayContext {
+    read_only: bool,
+}
+
+impl RelayContext for CanisterRelayContext {
+    fn publish(&mut self, event: EventData) -> Result<PublishStatus, RelayError> {
+        if self.read_only {
+            return Err(RelayError::ReadOnly);
+        }
+        store_event(event)
+    }
+
+    fn query(&self, filters: &[Filter]) -> Vec<EventData> {
+        EVENT_STORE.with_borrow(|store| store.query(filters))
+    }
+
+    fn count(&self, filters: &[Filter]) -> EventCount {
+        EVENT_STORE.with_borrow(|store| store.count(filters))
+    }
+}
+
+/// Answer a raw NIP01 client message with the JSON relay messages a websocket relay would send
+///
+/// `REQ` returns the stored events then `EOSE`, live subscriptions are up to the caller.
+#[ic_cdk::update]
+fn handle_message(message: String) -> Vec<String> {
+    let mut context = CanisterRelayContext { read_only: false };
+    relay::handle_message(message.as_str(), &mut context).iter().map(|reply| reply.as_json()).collect()
+}
+
+/// `handle_message` as a query, `EVENT` messages are rejected with `restricted`
+#[ic_cdk::query]
+fn handle_query_message(message: String) -> Vec<String> {
+    let mut context = CanisterRelayContext { read_only: true };
+    relay::handle_message(message.as_str(), &mut context).iter().map(|reply| reply.as_json()).collect()
+}
+
+/// Number of stored events matching a JSON array of filters, NIP45
+///
+/// Answered from the indexes when they cover the filters, otherwise `approximate` may be set.
+#[ic_cdk::query]
+fn count_events(filters_json: String) -> ApiResult<EventCount> {
+    let filters: Vec<Filter> = serde_json::from_str(filters_json.as_str())?;
+    Ok(EVENT_STORE.with_borrow(|store| store.count(filters.as_slice())))
+}
+
+/// npub of the canister-held key of `principal`, the caller by default
+#[ic_cdk::update]
+async fn get_npub(principal: Option<Principal>) -> ApiResult<String> {
+    let principal = principal.unwrap_or_else(ic_cdk::caller);
+    let derivation_path = principal_derivation_path(&principal)?;
+    let public_key = threshold_signer().public_key(&derivation_path).await?;
+    Ok(public_key.to_bech32()?)
+}
+
+/// Sign an event template with the canister-held key of the caller
+///
+/// Every signature costs the canister `sign_cycles`, only controllers and the `signers`
+/// of the signer configuration may sign. Returns the signed event, ready for `publish_event`.
+#[ic_cdk::update]
+async fn sign_event(template: EventTemplate) -> ApiResult<EventData> {
+    let caller = ic_cdk::caller();
+    let signer = threshold_signer();
+    if !ic_cdk::api::is_controller(&caller) && !signer.config().may_sign(&caller) {
+        return Err(ApiError::Unauthorized);
+    }
+    let derivation_path = principal_derivation_path(&caller)?;
+    let event = identity::sign_event(&signer, &derivation_path, template.into(), &default_time_supplier()).await?;
+    Ok(event)
+}
+
+/// Challenge to sign for `bind_key`, replacing the pending one of the caller
+#[ic_cdk::update]
+fn bind_challenge() -> ApiResult<String> {
+    let mut nonce = [0u8; 32];
+    with_rng(|rngcore| rngcore.fill_bytes(&mut nonce))?;
+    Ok(KEY_BINDINGS.with_borrow_mut(|bindings| bindings.issue_challenge(ic_cdk::caller(), nonce))?)
+}
+
+/// Bind the author of `proof` to the caller, returns its npub
+///
+/// `proof` is an event signed by the key, its content contains the challenge of `bind_challenge`.
+#[ic_cdk::update]
+fn bind_key(proof: EventData) -> ApiResult<String> {
+    let public_key = KEY_BINDINGS.with_borrow_mut(|bindings| bindings.bind(ic_cdk::caller(), &proof))?;
+    Ok(public_key.to_bech32()?)
+}
+
+/// Remove the binding of `npub` to the caller, `false` when it was not bound to the caller
+#[ic_cdk::update]
+fn unbind_key(npub: String) -> ApiResult<bool> {
+    let public_key = NostrPubKey::from_bech32(npub)?;
+    Ok(KEY_BINDINGS.with_borrow_mut(|bindings| bindings.unbind(&ic_cdk::caller(), &public_key)))
+}
+
+/// npubs bound to `principal`, the caller by default
+#[ic_cdk::query]
+fn list_keys(principal: Option<Principal>) -> ApiResult<Vec<String>> {
+    let principal = principal.unwrap_or_else(ic_cdk::caller);
+    let keys = KEY_BINDINGS.with_borrow(|bindings| bindings.keys_of(&principal));
+    Ok(keys.iter().map(|key| key.to_bech32()).collect::<Result<_, _>>()?)
+}
+
+/// Principal bound to `npub`
+#[ic_cdk::query]
+fn lookup_npub(npub: String) -> ApiResult<Option<Principal>> {
+    let public_key = NostrPubKey::from_bech32(npub)?;
+    Ok(KEY_BINDINGS.with_borrow(|bindings| bindings.principal_of(&public_key)))
 }
 
 // Enable Candid export
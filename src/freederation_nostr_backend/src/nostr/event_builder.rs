@@ -0,0 +1,425 @@
+//! Event builder
+//!
+//! Compose, hash and sign [`EventData`] from a [`Kind`], tags and content.
+
+use crate::encryption::nip04;
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_error::EventDataError;
+use crate::nostr::event_id::EventId;
+use crate::nostr::event_kind::Kind;
+use crate::nostr::metadata::Metadata;
+use crate::nostr::pow::{PowMiner, MAX_POW_DIFFICULTY};
+use crate::nostr::unsigned_event::UnsignedEvent;
+use crate::nostr::tag::TagData;
+use crate::nostr::tag::coordinate::EventIdOrCoordinate;
+use crate::signing::{NostrPubKey, NostrSecretKey, NostrSigningKey, AsymmetricKeyOps, AsymmetricKeyImpl, CryptoRngCore};
+use crate::util::jsonutil::JsonUtil;
+use crate::util::time::{default_time_supplier, TimeSupplier, Timestamp};
+use crate::util::uncheckedurl::UncheckedUrl;
+
+
+/// Contact entry of a contact list
+///
+/// <https://github.com/nostr-protocol/nips/blob/master/02.md>
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Contact {
+    /// Public key
+    pub public_key: NostrPubKey,
+    /// Relay url
+    pub relay_url: Option<UncheckedUrl>,
+    /// Alias
+    pub alias: Option<String>,
+}
+
+impl Contact {
+    /// Create new contact
+    #[inline]
+    pub fn new<S>(public_key: NostrPubKey, relay_url: Option<UncheckedUrl>, alias: Option<S>) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            public_key,
+            relay_url,
+            alias: alias.map(|a| a.into()),
+        }
+    }
+}
+
+
+/// Event builder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventBuilder {
+    kind: Kind,
+    tags: Vec<TagData>,
+    content: String,
+    custom_created_at: Option<Timestamp>,
+    pow: Option<u8>,
+}
+
+impl EventBuilder {
+    /// New event builder
+    pub fn new<S, I>(kind: Kind, content: S, tags: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = TagData>,
+    {
+        Self {
+            kind,
+            tags: tags.into_iter().collect(),
+            content: content.into(),
+            custom_created_at: None,
+            pow: None,
+        }
+    }
+
+    /// Set a custom `created_at` UNIX timestamp
+    ///
+    /// When not set, the time of the [`TimeSupplier`] is used.
+    #[inline]
+    pub fn custom_created_at(mut self, created_at: Timestamp) -> Self {
+        self.custom_created_at = Some(created_at);
+        self
+    }
+
+    /// Mine a NIP13 proof of work of `difficulty` leading zero bits when building the event
+    ///
+    /// `difficulty` is capped at [`MAX_POW_DIFFICULTY`], use [`PowMiner`] to spread a higher one over several calls.
+    #[inline]
+    pub fn pow(mut self, difficulty: u8) -> Self {
+        self.pow = Some(difficulty.min(MAX_POW_DIFFICULTY));
+        self
+    }
+
+    /// Append tags
+    #[inline]
+    pub fn add_tags<I>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = TagData>,
+    {
+        self.tags.extend(tags);
+        self
+    }
+
+    /// Get the [`Kind`] of the event being built
+    #[inline]
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Build [`UnsignedEvent`] for `public_key`
+    #[inline]
+    pub fn into_unsigned_event(self, public_key: NostrPubKey) -> UnsignedEvent {
+        self.into_unsigned_event_with_supplier(public_key, &default_time_supplier())
+    }
+
+    /// Build [`UnsignedEvent`] for `public_key`, `created_at` defaults to the time of `supplier`
+    pub fn into_unsigned_event_with_supplier<T>(self, public_key: NostrPubKey, supplier: &T) -> UnsignedEvent
+    where
+        T: TimeSupplier,
+    {
+        let created_at: Timestamp = self
+            .custom_created_at
+            .unwrap_or_else(|| Timestamp::now_with_supplier(supplier));
+        let event = UnsignedEvent::new(public_key, created_at, self.kind, self.tags, self.content);
+        match self.pow {
+            Some(difficulty) => PowMiner::new(event, difficulty).mine_to_completion(),
+            None => event,
+        }
+    }
+
+    /// Build, hash and sign the event
+    pub fn sign<RG>(self, signer: &NostrSigningKey, rngcore: &mut RG) -> Result<EventData, EventDataError>
+    where RG: CryptoRngCore
+    {
+        let ecda = AsymmetricKeyImpl();
+        let public_key = NostrPubKey(ecda.pubkey_from_pair(signer));
+        self.into_unsigned_event(public_key).sign(signer, rngcore)
+    }
+
+    /// Short text note
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/01.md>
+    #[inline]
+    pub fn text_note<S, I>(content: S, tags: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = TagData>,
+    {
+        Self::new(Kind::TextNote, content, tags)
+    }
+
+    /// Profile metadata
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/01.md>
+    #[inline]
+    pub fn metadata(metadata: &Metadata) -> Self {
+        Self::new(Kind::Metadata, metadata.as_json(), [])
+    }
+
+    /// Contact list
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/02.md>
+    pub fn contact_list<I>(contacts: I) -> Self
+    where
+        I: IntoIterator<Item = Contact>,
+    {
+        let tags = contacts.into_iter().map(|contact| {
+            let mut values: Vec<String> = vec![String::from(&contact.public_key)];
+            match (contact.relay_url, contact.alias) {
+                (Some(relay_url), Some(alias)) => {
+                    values.push(relay_url.to_string());
+                    values.push(alias);
+                }
+                (Some(relay_url), None) => values.push(relay_url.to_string()),
+                (None, Some(alias)) => {
+                    values.push(String::new());
+                    values.push(alias);
+                }
+                (None, None) => (),
+            }
+            TagData::custom("p", values)
+        });
+        Self::new(Kind::ContactList, "", tags)
+    }
+
+    /// Encrypted direct message from `sender` to `receiver`, optionally replying to `reply_to`
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/04.md>
+    pub fn encrypted_direct_msg<S, RG>(
+        sender: &NostrSecretKey,
+        receiver: &NostrPubKey,
+        content: S,
+        reply_to: Option<EventId>,
+        rngcore: &mut RG,
+    ) -> Self
+    where
+        S: AsRef<str>,
+        RG: CryptoRngCore,
+    {
+        let mut tags: Vec<TagData> = vec![TagData::public_key(receiver)];
+        if let Some(reply_to) = reply_to {
+            tags.push(TagData::event(&reply_to));
+        }
+        Self::new(
+            Kind::EncryptedDirectMessage,
+            nip04::encrypt(sender, receiver, content.as_ref(), rngcore),
+            tags,
+        )
+    }
+
+    /// Private direct message rumor to `receivers`, optionally replying to `reply_to`
+    ///
+    /// To be sent unsigned through [`gift_wrap`](crate::nostr::gift_wrap::private_msg).
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/17.md>
+    pub fn private_msg_rumor<I, S>(receivers: I, message: S, reply_to: Option<EventId>) -> Self
+    where
+        I: IntoIterator<Item = NostrPubKey>,
+        S: Into<String>,
+    {
+        let mut tags: Vec<TagData> = receivers
+            .into_iter()
+            .map(|receiver| TagData::public_key(&receiver))
+            .collect();
+        if let Some(reply_to) = reply_to {
+            tags.push(TagData::event(&reply_to));
+        }
+        Self::new(Kind::PrivateDirectMessage, message, tags)
+    }
+
+    /// Reaction to `event`, `+` for like and `-` for dislike
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/25.md>
+    pub fn reaction<S>(event: &EventData, reaction: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::new(
+            Kind::Reaction,
+            reaction,
+            [
+                TagData::event(&event.id),
+                TagData::public_key(&event.pubkey),
+                TagData::custom("k", [event.kind.to_string()]),
+            ],
+        )
+    }
+
+    /// Repost `event`
+    ///
+    /// Text notes are reposted with [`Kind::Repost`], any other kind with [`Kind::GenericRepost`].
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/18.md>
+    pub fn repost(event: &EventData, relay_url: Option<UncheckedUrl>) -> Self {
+        let mut event_tag: Vec<String> = vec![event.id.to_hex()];
+        if let Some(relay_url) = relay_url {
+            event_tag.push(relay_url.to_string());
+        }
+
+        let mut tags: Vec<TagData> = vec![
+            TagData::custom("e", event_tag),
+            TagData::public_key(&event.pubkey),
+        ];
+
+        if event.kind == Kind::TextNote {
+            Self::new(Kind::Repost, event.as_json(), tags)
+        } else {
+            tags.push(TagData::custom("k", [event.kind.to_string()]));
+            Self::new(Kind::GenericRepost, event.as_json(), tags)
+        }
+    }
+
+    /// Event deletion request
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/09.md>
+    pub fn delete<I, S>(targets: I, reason: Option<S>) -> Self
+    where
+        I: IntoIterator<Item = EventIdOrCoordinate>,
+        S: Into<String>,
+    {
+        let tags = targets.into_iter().map(|target| match target {
+            EventIdOrCoordinate::Id(event_id) => TagData::event(&event_id),
+            EventIdOrCoordinate::Coordinate(coordinate) => TagData::coordinate(&coordinate),
+        });
+        Self::new(
+            Kind::EventDeletion,
+            reason.map(|r| r.into()).unwrap_or_default(),
+            tags,
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::rng::CryptoHashRng;
+    use crate::nostr::tag::coordinate::Coordinate;
+    use crate::util::time::MockTimeSupplier;
+
+    const SECRET_KEY_HEX: &str = "6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+
+    fn signer() -> NostrSigningKey {
+        let ecda = AsymmetricKeyImpl();
+        let skey = ecda.parse_secret_key_from_hex(SECRET_KEY_HEX).unwrap();
+        ecda.new_keypair(skey).unwrap()
+    }
+
+    fn rngcore() -> CryptoHashRng {
+        CryptoHashRng::from_seed([7u8; 32])
+    }
+
+    #[test]
+    fn test_text_note_roundtrip() {
+        let event = EventBuilder::text_note("hello nostr", [])
+            .custom_created_at(Timestamp::from(1_700_000_000))
+            .sign(&signer(), &mut rngcore())
+            .unwrap();
+
+        assert_eq!(event.kind, Kind::TextNote);
+        assert_eq!(event.created_at, Timestamp::from(1_700_000_000));
+        assert!(event.verify().is_ok());
+
+        let parsed = EventData::from_json(event.as_json()).unwrap();
+        assert_eq!(parsed, event);
+        assert!(parsed.verify().is_ok());
+    }
+
+    #[test]
+    fn test_tampered_event_fails_verification() {
+        let mut event = EventBuilder::text_note("original", [])
+            .sign(&signer(), &mut rngcore())
+            .unwrap();
+
+        event.content = String::from("tampered");
+        assert!(matches!(event.verify(), Err(EventDataError::InvalidEventHash)));
+    }
+
+    #[test]
+    fn test_created_at_from_supplier() {
+        let supplier = MockTimeSupplier::new(Timestamp::from(1_234_567));
+        let public_key = NostrPubKey(AsymmetricKeyImpl().pubkey_from_pair(&signer()));
+        let unsigned = EventBuilder::text_note("clock", [])
+            .into_unsigned_event_with_supplier(public_key, &supplier);
+        assert_eq!(unsigned.created_at, Timestamp::from(1_234_567));
+    }
+
+    #[test]
+    fn test_pow() {
+        let event = EventBuilder::text_note("mined", [])
+            .pow(12)
+            .sign(&signer(), &mut rngcore())
+            .unwrap();
+        assert!(event.verify().is_ok());
+        assert!(event.check_pow(12));
+        assert!(event.pow_difficulty() >= 12);
+        assert_eq!(event.pow_commitment(), Some(12));
+
+        let plain = EventBuilder::text_note("plain", []).sign(&signer(), &mut rngcore()).unwrap();
+        assert_eq!(plain.pow_commitment(), None);
+
+        assert_eq!(EventBuilder::text_note("hard", []).pow(u8::MAX).pow, Some(MAX_POW_DIFFICULTY));
+    }
+
+    #[test]
+    fn test_sign_with_wrong_key() {
+        let ecda = AsymmetricKeyImpl();
+        let other = ecda.generate_keypair(&mut rngcore()).unwrap();
+        let unsigned = EventBuilder::text_note("mismatch", [])
+            .into_unsigned_event(NostrPubKey(ecda.pubkey_from_pair(&other)));
+
+        assert!(unsigned.sign(&signer(), &mut rngcore()).is_err());
+    }
+
+    #[test]
+    fn test_typed_constructors() {
+        let signer = signer();
+        let mut rng = rngcore();
+        let note = EventBuilder::text_note("note", []).sign(&signer, &mut rng).unwrap();
+
+        let metadata = EventBuilder::metadata(&Metadata::new().name("freederation"))
+            .sign(&signer, &mut rng)
+            .unwrap();
+        assert_eq!(metadata.kind, Kind::Metadata);
+        assert_eq!(metadata.content, r#"{"name":"freederation"}"#);
+
+        let contacts = EventBuilder::contact_list([
+            Contact::new(note.pubkey.clone(), Some(UncheckedUrl::from("wss://relay.damus.io")), Some("me")),
+            Contact::new(note.pubkey.clone(), None, None::<String>),
+        ])
+        .sign(&signer, &mut rng)
+        .unwrap();
+        assert_eq!(contacts.kind, Kind::ContactList);
+        assert_eq!(contacts.tags[0].as_vec().len(), 4);
+        assert_eq!(contacts.tags[1].as_vec().len(), 2);
+
+        let reaction = EventBuilder::reaction(&note, "+").sign(&signer, &mut rng).unwrap();
+        assert_eq!(reaction.kind, Kind::Reaction);
+        assert_eq!(reaction.tags[0].content(), Some(note.id.to_hex().as_str()));
+
+        let repost = EventBuilder::repost(&note, None).sign(&signer, &mut rng).unwrap();
+        assert_eq!(repost.kind, Kind::Repost);
+        assert_eq!(EventData::from_json(&repost.content).unwrap(), note);
+
+        let generic_repost = EventBuilder::repost(&metadata, None).sign(&signer, &mut rng).unwrap();
+        assert_eq!(generic_repost.kind, Kind::GenericRepost);
+
+        let coordinate = Coordinate::new(Kind::LongFormTextNote, note.pubkey.clone()).identifier("article");
+        let deletion = EventBuilder::delete(
+            [EventIdOrCoordinate::from(note.id), EventIdOrCoordinate::from(coordinate)],
+            Some("mistake"),
+        )
+        .sign(&signer, &mut rng)
+        .unwrap();
+        assert_eq!(deletion.kind, Kind::EventDeletion);
+        assert_eq!(deletion.content, "mistake");
+        assert_eq!(deletion.tags[1].kind_str(), Some("a"));
+
+        for event in [metadata, contacts, reaction, repost, generic_repost, deletion] {
+            assert!(event.verify().is_ok());
+        }
+    }
+}
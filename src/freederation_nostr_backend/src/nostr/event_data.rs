@@ -1,3 +1,4 @@
+use candid::CandidType;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use core::cmp::Ordering;
 
@@ -5,7 +6,11 @@ use crate::nostr::event_id::EventId;
 use crate::nostr::event_error::EventDataError;
 use crate::nostr::event_kind::Kind;
 use crate::nostr::tag::TagData;
+use crate::nostr::tag::coordinate::Coordinate;
+use crate::nostr::tag::delegation::DelegationTag;
+use crate::nostr::tag::tagstandard::TagStandard;
 use crate::signing::{NostrPubKey, NostrSignature, AsymmetricKeyOps, AsymmetricKeyImpl, AsymmetricKeyError};
+use crate::util::nostrpow::get_leading_zero_bits;
 use crate::util::time::Timestamp;
 use crate::util::basecore::ParseError;
 use crate::util::jsonutil::JsonUtil;
@@ -13,7 +18,7 @@ use crate::util::jsonutil::JsonUtil;
 
 
 /// Event Intermediate used for de/serialization of [`Event`]
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, CandidType)]
 pub struct EventData {
     /// Id
     pub id: EventId,
@@ -77,7 +82,32 @@ impl EventData {
 
     pub fn verify(&self) -> Result<(), EventDataError>
     {
-        self.verify_id().and_then(|_|self.verify_signature())
+        self.verify_id()
+            .and_then(|_|self.verify_signature())
+            .and_then(|_|self.verify_delegation().map(|_| ()))
+    }
+
+    /// Verify the NIP26 delegation of the first `delegation` tag, if any
+    ///
+    /// Returns the delegator of a delegated event.
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/26.md>
+    pub fn verify_delegation(&self) -> Result<Option<NostrPubKey>, EventDataError>
+    {
+        let Some(tag) = self.tags.iter().find(|tag| tag.kind_str() == Some("delegation")) else {
+            return Ok(None);
+        };
+        let delegation = DelegationTag::try_from(tag.as_vec().to_vec())?;
+        delegation.validate_event(self)?;
+        Ok(Some(delegation.delegator_pubkey()))
+    }
+
+    /// Effective author, the delegator of a valid NIP26 delegation or [`EventData::pubkey`]
+    pub fn author(&self) -> NostrPubKey {
+        match self.verify_delegation() {
+            Ok(Some(delegator)) => delegator,
+            _ => self.pubkey.clone(),
+        }
     }
     
     /// Check POW
@@ -88,6 +118,75 @@ impl EventData {
         self.id.check_pow(difficulty)
     }
 
+    /// Number of leading zero bits of the id
+    #[inline]
+    pub fn pow_difficulty(&self) -> u8 {
+        get_leading_zero_bits(self.id.as_bytes())
+    }
+
+    /// Target difficulty committed by the first `nonce` tag
+    pub fn pow_commitment(&self) -> Option<u8> {
+        self.tags
+            .iter()
+            .find(|tag| tag.kind_str() == Some("nonce"))
+            .and_then(|tag| match tag.as_standardized() {
+                TagStandard::POW { difficulty, .. } => Some(difficulty),
+                _ => None,
+            })
+    }
+
+    /// NIP40 expiration timestamp
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/40.md>
+    pub fn expiration(&self) -> Option<Timestamp> {
+        self.tags
+            .iter()
+            .filter(|tag| tag.kind_str() == Some("expiration"))
+            .find_map(|tag| match tag.as_standardized() {
+                TagStandard::Expiration(timestamp) => Some(timestamp),
+                _ => None,
+            })
+    }
+
+    /// Check if the event is expired at `now`
+    #[inline]
+    pub fn is_expired_at(&self, now: &Timestamp) -> bool {
+        self.expiration().is_some_and(|expiration| expiration <= *now)
+    }
+
+    /// Public keys of the `p` tags
+    pub fn public_keys(&self) -> impl Iterator<Item = NostrPubKey> + '_ {
+        self.tags
+            .iter()
+            .filter(|tag| tag.kind_str() == Some("p"))
+            .filter_map(|tag| match tag.as_standardized() {
+                TagStandard::PublicKey { public_key, .. } => Some(public_key),
+                _ => None,
+            })
+    }
+
+    /// Value of the first `d` tag
+    pub fn identifier(&self) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.kind_str() == Some("d"))
+            .and_then(|tag| tag.content())
+    }
+
+    /// [`Coordinate`] of a replaceable or parameterized replaceable event
+    ///
+    /// A parameterized replaceable event without `d` tag has an empty identifier.
+    pub fn coordinate(&self) -> Option<Coordinate> {
+        if self.kind.is_replaceable() {
+            Some(Coordinate::new(self.kind, self.pubkey.clone()))
+        } else if self.kind.is_parameterized_replaceable() {
+            let identifier: &str = self.identifier().unwrap_or_default();
+            Some(Coordinate::new(self.kind, self.pubkey.clone()).identifier(identifier))
+        } else {
+            None
+        }
+    }
+
 }
 
 impl JsonUtil for EventData {
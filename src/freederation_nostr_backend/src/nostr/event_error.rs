@@ -1,3 +1,4 @@
+use crate::nostr::tag::delegation::ConditionError;
 use crate::signing;
 use crate::util::basecore::ParseError;
 
@@ -39,5 +40,8 @@ pub enum EventDataError {
 
     #[error("Event Pow Validation Error, dificulty {0}")]
     InvalidEventIdPow(u8),
+
+    #[error("Invalid delegation: {0}")]
+    InvalidDelegation(#[from] ConditionError),
 }
 
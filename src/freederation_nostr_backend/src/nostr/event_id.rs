@@ -1,3 +1,4 @@
+use candid::CandidType;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{json, Value};
 
@@ -213,6 +214,20 @@ impl Serialize for EventId {
     }
 }
 
+/// Candid `text` holding the lowercase hex, as in the JSON encoding
+impl CandidType for EventId {
+    fn _ty() -> candid::types::Type {
+        String::ty()
+    }
+
+    fn idl_serialize<S>(&self, serializer: S) -> Result<(), S::Error>
+    where
+        S: candid::types::Serializer,
+    {
+        serializer.serialize_text(self.to_hex().as_str())
+    }
+}
+
 impl<'de> Deserialize<'de> for EventId {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -5,6 +5,7 @@ use core::num::ParseIntError;
 use core::ops::{Add, Range};
 use core::str::FromStr;
 
+use candid::CandidType;
 use serde::de::{Deserialize, Deserializer, Error, Visitor};
 use serde::ser::{Serialize, Serializer};
 
@@ -274,6 +275,20 @@ impl Serialize for Kind {
     }
 }
 
+/// Candid `nat16`, as the JSON number
+impl CandidType for Kind {
+    fn _ty() -> candid::types::Type {
+        u16::ty()
+    }
+
+    fn idl_serialize<S>(&self, serializer: S) -> Result<(), S::Error>
+    where
+        S: candid::types::Serializer,
+    {
+        serializer.serialize_nat16(self.as_u16())
+    }
+}
+
 impl<'de> Deserialize<'de> for Kind {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -0,0 +1,421 @@
+//! Subscription filter
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/01.md>
+//! <https://github.com/nostr-protocol/nips/blob/master/50.md>
+
+use std::collections::{BTreeMap, BTreeSet};
+use core::fmt;
+use core::str::FromStr;
+use serde::de::{Deserializer, MapAccess, Visitor, IgnoredAny};
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_id::EventId;
+use crate::nostr::event_kind::Kind;
+use crate::nostr::search::SearchQuery;
+use crate::nostr::tag::single_letter_tag::{Alphabet, SingleLetterTag};
+use crate::signing::NostrPubKey;
+use crate::util::basecore::ParseError;
+use crate::util::jsonutil::JsonUtil;
+use crate::util::time::Timestamp;
+
+/// Shortest `ids` and `authors` prefix, in hex characters
+pub const MIN_PREFIX_LEN: usize = 4;
+
+/// Generic tag queries, serialized as `#<single-letter>` fields
+pub type GenericTags = BTreeMap<SingleLetterTag, BTreeSet<String>>;
+
+/// Subscription filter
+///
+/// `ids` and `authors` accept full lowercase hex values or hex prefixes, other values are
+/// refused when deserializing, see [`is_hex_prefix`].
+/// `search` is a NIP50 full-text query, see [`SearchQuery`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Filter {
+    /// List of event ids or prefixes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, deserialize_with = "deserialize_prefixes")]
+    pub ids: Option<BTreeSet<String>>,
+    /// List of pubkeys or prefixes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, deserialize_with = "deserialize_prefixes")]
+    pub authors: Option<BTreeSet<String>>,
+    /// List of a kind numbers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub kinds: Option<BTreeSet<Kind>>,
+    /// Generic tag queries
+    #[serde(
+        flatten,
+        serialize_with = "serialize_generic_tags",
+        deserialize_with = "deserialize_generic_tags"
+    )]
+    #[serde(default)]
+    pub generic_tags: GenericTags,
+    /// Events must be newer than this to pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub since: Option<Timestamp>,
+    /// Events must be older than this to pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub until: Option<Timestamp>,
+    /// Maximum number of events to be returned in the initial query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Full-text search query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub search: Option<String>,
+}
+
+impl Filter {
+    /// New empty [`Filter`]
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add event id
+    #[inline]
+    pub fn id(self, id: EventId) -> Self {
+        self.id_prefix(String::from(&id))
+    }
+
+    /// Add event ids
+    #[inline]
+    pub fn ids<I>(self, ids: I) -> Self
+    where
+        I: IntoIterator<Item = EventId>,
+    {
+        ids.into_iter().fold(self, |filter, id| filter.id(id))
+    }
+
+    /// Add event id prefix (hex)
+    pub fn id_prefix<S>(mut self, prefix: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.ids.get_or_insert_with(BTreeSet::new).insert(prefix.into().to_lowercase());
+        self
+    }
+
+    /// Add author
+    #[inline]
+    pub fn author(self, author: NostrPubKey) -> Self {
+        self.author_prefix(String::from(&author))
+    }
+
+    /// Add authors
+    #[inline]
+    pub fn authors<I>(self, authors: I) -> Self
+    where
+        I: IntoIterator<Item = NostrPubKey>,
+    {
+        authors.into_iter().fold(self, |filter, author| filter.author(author))
+    }
+
+    /// Add author prefix (hex)
+    pub fn author_prefix<S>(mut self, prefix: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.authors.get_or_insert_with(BTreeSet::new).insert(prefix.into().to_lowercase());
+        self
+    }
+
+    /// Add kind
+    pub fn kind(mut self, kind: Kind) -> Self {
+        self.kinds.get_or_insert_with(BTreeSet::new).insert(kind);
+        self
+    }
+
+    /// Add kinds
+    #[inline]
+    pub fn kinds<I>(self, kinds: I) -> Self
+    where
+        I: IntoIterator<Item = Kind>,
+    {
+        kinds.into_iter().fold(self, |filter, kind| filter.kind(kind))
+    }
+
+    /// Add `#e` tag query
+    #[inline]
+    pub fn event(self, id: EventId) -> Self {
+        self.custom_tag(SingleLetterTag::lowercase(Alphabet::E), [id.to_hex()])
+    }
+
+    /// Add `#p` tag query
+    #[inline]
+    pub fn pubkey(self, pubkey: NostrPubKey) -> Self {
+        self.custom_tag(SingleLetterTag::lowercase(Alphabet::P), [String::from(&pubkey)])
+    }
+
+    /// Add `#t` tag query
+    #[inline]
+    pub fn hashtag<S>(self, hashtag: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.custom_tag(SingleLetterTag::lowercase(Alphabet::T), [hashtag])
+    }
+
+    /// Add `#d` tag query
+    #[inline]
+    pub fn identifier<S>(self, identifier: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.custom_tag(SingleLetterTag::lowercase(Alphabet::D), [identifier])
+    }
+
+    /// Add custom `#<single-letter>` tag query
+    pub fn custom_tag<I, S>(mut self, tag: SingleLetterTag, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.generic_tags
+            .entry(tag)
+            .or_default()
+            .extend(values.into_iter().map(|v| v.into()));
+        self
+    }
+
+    /// Set since timestamp
+    #[inline]
+    pub fn since(mut self, since: Timestamp) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Set until timestamp
+    #[inline]
+    pub fn until(mut self, until: Timestamp) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Set limit
+    #[inline]
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Set full-text search query
+    #[inline]
+    pub fn search<S>(mut self, search: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.search = Some(search.into());
+        self
+    }
+
+    /// Parsed `search`
+    #[inline]
+    pub fn search_query(&self) -> Option<SearchQuery> {
+        self.search.as_deref().map(SearchQuery::parse)
+    }
+
+    /// Check if the filter has no conditions
+    pub fn is_empty(&self) -> bool {
+        self == &Filter::default()
+    }
+
+    fn ids_match(&self, event: &EventData) -> bool {
+        self.ids.as_ref().is_none_or(|ids| {
+            let id: String = event.id.to_hex();
+            ids.iter().any(|prefix| id.starts_with(prefix.as_str()))
+        })
+    }
+
+    fn authors_match(&self, event: &EventData) -> bool {
+        self.authors.as_ref().is_none_or(|authors| {
+            let pubkey: String = String::from(&event.pubkey);
+            authors.iter().any(|prefix| pubkey.starts_with(prefix.as_str()))
+        })
+    }
+
+    fn kinds_match(&self, event: &EventData) -> bool {
+        self.kinds.as_ref().is_none_or(|kinds| kinds.contains(&event.kind))
+    }
+
+    fn tags_match(&self, event: &EventData) -> bool {
+        self.generic_tags.iter().all(|(single_letter, values)| {
+            let tag_name: String = single_letter.to_string();
+            event.tags.iter().any(|tag| {
+                tag.kind_str() == Some(tag_name.as_str())
+                    && tag.content().is_some_and(|content| values.contains(content))
+            })
+        })
+    }
+
+    fn time_match(&self, event: &EventData) -> bool {
+        self.since.is_none_or(|since| event.created_at >= since)
+            && self.until.is_none_or(|until| event.created_at <= until)
+    }
+
+    /// `domain:` is not checked, see [`SearchQuery::match_event`]
+    fn search_match(&self, event: &EventData) -> bool {
+        self.search_query().is_none_or(|query| query.match_event(event))
+    }
+
+    /// Determine if [`Filter`] match the provided [`EventData`].
+    ///
+    /// `limit` is not taken into account.
+    pub fn match_event(&self, event: &EventData) -> bool {
+        self.ids_match(event)
+            && self.authors_match(event)
+            && self.kinds_match(event)
+            && self.time_match(event)
+            && self.tags_match(event)
+            && self.search_match(event)
+    }
+}
+
+impl JsonUtil for Filter {
+    type Err = ParseError;
+}
+
+/// Check if `prefix` is a lowercase hex prefix of an id or a public key, of at least [`MIN_PREFIX_LEN`] characters
+pub fn is_hex_prefix(prefix: &str) -> bool {
+    (MIN_PREFIX_LEN..=64).contains(&prefix.len()) && prefix.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn deserialize_prefixes<'de, D>(deserializer: D) -> Result<Option<BTreeSet<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let prefixes: Option<BTreeSet<String>> = Option::deserialize(deserializer)?;
+    if let Some(invalid) = prefixes.iter().flatten().find(|prefix| !is_hex_prefix(prefix)) {
+        return Err(serde::de::Error::custom(format!("invalid hex prefix: {invalid}")));
+    }
+    Ok(prefixes)
+}
+
+fn serialize_generic_tags<S>(generic_tags: &GenericTags, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut map = serializer.serialize_map(Some(generic_tags.len()))?;
+    for (tag, values) in generic_tags.iter() {
+        map.serialize_entry(&format!("#{tag}"), values)?;
+    }
+    map.end()
+}
+
+fn deserialize_generic_tags<'de, D>(deserializer: D) -> Result<GenericTags, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct GenericTagsVisitor;
+
+    impl<'de> Visitor<'de> for GenericTagsVisitor {
+        type Value = GenericTags;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("map in which the keys are \"#X\" for some character X")
+        }
+
+        fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            let mut generic_tags = GenericTags::new();
+            while let Some(key) = map.next_key::<String>()? {
+                let tag = key.strip_prefix('#').and_then(|t| SingleLetterTag::from_str(t).ok());
+                match tag {
+                    Some(tag) => {
+                        let values: BTreeSet<String> = map.next_value()?;
+                        generic_tags.insert(tag, values);
+                    }
+                    None => {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+            }
+            Ok(generic_tags)
+        }
+    }
+
+    deserializer.deserialize_map(GenericTagsVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::rng::CryptoHashRng;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::nostr::tag::TagData;
+    use crate::signing::{AsymmetricKeyOps, AsymmetricKeyImpl};
+
+    fn text_note(content: &str, tags: Vec<TagData>, created_at: u64) -> EventData {
+        let ecda = AsymmetricKeyImpl();
+        let mut rng = CryptoHashRng::from_seed([9u8; 32]);
+        let signer = ecda.generate_keypair(&mut rng).unwrap();
+        EventBuilder::text_note(content, tags)
+            .custom_created_at(Timestamp::from(created_at))
+            .sign(&signer, &mut rng)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_filter_serialization() {
+        let filter = Filter::new()
+            .kind(Kind::TextNote)
+            .hashtag("nostr")
+            .since(Timestamp::from(1_600_000_000))
+            .limit(10);
+        assert_eq!(
+            filter.as_json(),
+            r##"{"kinds":[1],"#t":["nostr"],"since":1600000000,"limit":10}"##
+        );
+    }
+
+    #[test]
+    fn test_filter_deserialization() {
+        let json = r##"{"ids":["abcd"],"#e":["378f145897eea948952674269945e88612420db35791784abf0616b4fed56ef7"],"#P":["alias"],"search":"nostr","until":5}"##;
+        let filter = Filter::from_json(json).unwrap();
+
+        assert_eq!(filter.ids, Some(BTreeSet::from([String::from("abcd")])));
+        assert_eq!(filter.until, Some(Timestamp::from(5)));
+        assert_eq!(filter.search.as_deref(), Some("nostr"));
+        assert_eq!(filter.generic_tags.len(), 2);
+        assert!(filter.generic_tags.contains_key(&SingleLetterTag::uppercase(Alphabet::P)));
+        assert_eq!(Filter::from_json(filter.as_json()).unwrap(), filter);
+
+        for invalid in [r#"{"ids":["aé00"]}"#, r#"{"ids":["abc"]}"#, r#"{"authors":["ABCD"]}"#, r#"{"authors":[""]}"#] {
+            assert!(Filter::from_json(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_match_event() {
+        let event = text_note("hello", vec![TagData::custom("t", ["nostr"])], 1_700_000_000);
+        let id_hex = event.id.to_hex();
+        let author_hex = String::from(&event.pubkey);
+
+        assert!(Filter::new().match_event(&event));
+        assert!(Filter::new().id(event.id).match_event(&event));
+        assert!(Filter::new().id_prefix(&id_hex[..6]).match_event(&event));
+        assert!(!Filter::new().id_prefix("zz").match_event(&event));
+        assert!(Filter::new().author_prefix(&author_hex[..8]).match_event(&event));
+        assert!(Filter::new().author(event.pubkey.clone()).kind(Kind::TextNote).match_event(&event));
+        assert!(!Filter::new().kind(Kind::Metadata).match_event(&event));
+        assert!(Filter::new().hashtag("nostr").match_event(&event));
+        assert!(!Filter::new().hashtag("bitcoin").match_event(&event));
+        assert!(!Filter::new().hashtag("nostr").identifier("x").match_event(&event));
+        assert!(Filter::new().since(Timestamp::from(1_700_000_000)).match_event(&event));
+        assert!(!Filter::new().since(Timestamp::from(1_700_000_001)).match_event(&event));
+        assert!(!Filter::new().until(Timestamp::from(1_699_999_999)).match_event(&event));
+        assert!(Filter::new().limit(0).match_event(&event));
+        assert!(Filter::new().search("Hello").match_event(&event));
+        assert!(!Filter::new().search("goodbye").match_event(&event));
+    }
+}
@@ -0,0 +1,319 @@
+//! Gift wrap and seal
+//!
+//! An unsigned rumor is NIP44 encrypted into a [`Kind::Seal`] signed by its author, the seal
+//! is NIP44 encrypted into a [`Kind::GiftWrap`] signed by a single-use key. Both timestamps
+//! are moved up to two days in the past to hide the real one.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/59.md>
+//!
+//! Private direct messages are kind 14 rumors, wrapped once for every participant.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/17.md>
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use crate::encryption::nip44::{self, ConversationKey, Nip44Error};
+use crate::nostr::event_builder::EventBuilder;
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_error::EventDataError;
+use crate::nostr::event_id::EventId;
+use crate::nostr::event_kind::Kind;
+use crate::nostr::tag::TagData;
+use crate::nostr::tag::tagstandard::TagStandard;
+use crate::nostr::unsigned_event::UnsignedEvent;
+use crate::signing::{
+    AsymmetricKeyError, AsymmetricKeyImpl, AsymmetricKeyOps, CryptoRngCore, DataField, NostrPubKey, NostrSecretKey,
+    NostrSigningKey,
+};
+use crate::util::jsonutil::JsonUtil;
+use crate::util::time::{default_time_supplier, Timestamp};
+
+/// Seconds removed at most from the seal and gift wrap `created_at`, two days
+pub const RANGE_RANDOM_TIMESTAMP_TWEAK: Range<u64> = 0..172_800;
+
+/// Gift wrap error
+#[derive(thiserror::Error, Debug)]
+pub enum GiftWrapError {
+
+    #[error("Encryption error: {0}")]
+    Nip44(#[from] Nip44Error),
+
+    #[error("Invalid event: {0}")]
+    Event(#[from] EventDataError),
+
+    #[error("Keys error: {0}")]
+    Keys(#[from] AsymmetricKeyError),
+
+    #[error("Expected kind {expected}, found {found}")]
+    WrongKind { expected: Kind, found: Kind },
+
+    #[error("Rumor author does not match the seal signer")]
+    SenderMismatch,
+}
+
+/// Content of an opened gift wrap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnwrappedGift {
+    /// Signer of the seal, the authenticated author of the rumor
+    pub sender: NostrPubKey,
+    /// Unsigned rumor
+    pub rumor: UnsignedEvent,
+}
+
+fn signing_key(secret_key: &NostrSecretKey) -> Result<NostrSigningKey, AsymmetricKeyError> {
+    AsymmetricKeyImpl().new_keypair(secret_key.0.clone())
+}
+
+fn public_key(signing_key: &NostrSigningKey) -> NostrPubKey {
+    NostrPubKey(AsymmetricKeyImpl().pubkey_from_pair(signing_key))
+}
+
+/// Single-use secret key of a gift wrap
+fn ephemeral_secret_key<RG>(rngcore: &mut RG) -> NostrSecretKey
+where RG: CryptoRngCore
+{
+    let ecda = AsymmetricKeyImpl();
+    loop {
+        let mut buffer: DataField = [0u8; 32];
+        rngcore.fill_bytes(&mut buffer);
+        if let Ok(secret_key) = ecda.new_secret_key_from_bytes(&buffer) {
+            return NostrSecretKey(secret_key);
+        }
+    }
+}
+
+fn check_kind(event: &EventData, expected: Kind) -> Result<(), GiftWrapError> {
+    if event.kind != expected {
+        return Err(GiftWrapError::WrongKind { expected, found: event.kind });
+    }
+    Ok(())
+}
+
+/// Encrypt `rumor` to `receiver` in a [`Kind::Seal`] signed by `sender`
+///
+/// `rumor` must be authored by `sender`.
+pub fn seal<RG>(sender: &NostrSecretKey, receiver: &NostrPubKey, rumor: &UnsignedEvent, rngcore: &mut RG) -> Result<EventData, GiftWrapError>
+where RG: CryptoRngCore
+{
+    let signer = signing_key(sender)?;
+    if public_key(&signer) != rumor.pubkey {
+        return Err(GiftWrapError::SenderMismatch);
+    }
+
+    let content = nip44::encrypt(&ConversationKey::derive(sender, receiver), rumor.as_json().as_str(), rngcore)?;
+    let created_at = Timestamp::tweaked_with_supplier_and_rng(&default_time_supplier(), rngcore, RANGE_RANDOM_TIMESTAMP_TWEAK);
+    Ok(EventBuilder::new(Kind::Seal, content, [])
+        .custom_created_at(created_at)
+        .sign(&signer, rngcore)?)
+}
+
+/// Encrypt `seal` to `receiver` in a [`Kind::GiftWrap`] signed by a single-use key
+///
+/// `extra_tags` are appended after the `p` tag of `receiver`, e.g. a NIP40 expiration.
+pub fn gift_wrap<RG, I>(receiver: &NostrPubKey, seal: &EventData, extra_tags: I, rngcore: &mut RG) -> Result<EventData, GiftWrapError>
+where
+    RG: CryptoRngCore,
+    I: IntoIterator<Item = TagData>,
+{
+    check_kind(seal, Kind::Seal)?;
+
+    let ephemeral = ephemeral_secret_key(rngcore);
+    let content = nip44::encrypt(&ConversationKey::derive(&ephemeral, receiver), seal.as_json().as_str(), rngcore)?;
+    let created_at = Timestamp::tweaked_with_supplier_and_rng(&default_time_supplier(), rngcore, RANGE_RANDOM_TIMESTAMP_TWEAK);
+    Ok(EventBuilder::new(Kind::GiftWrap, content, [TagData::public_key(receiver)])
+        .add_tags(extra_tags)
+        .custom_created_at(created_at)
+        .sign(&signing_key(&ephemeral)?, rngcore)?)
+}
+
+/// Seal `rumor` and gift wrap it to `receiver`
+pub fn wrap_rumor<RG>(sender: &NostrSecretKey, receiver: &NostrPubKey, rumor: &UnsignedEvent, rngcore: &mut RG) -> Result<EventData, GiftWrapError>
+where RG: CryptoRngCore
+{
+    let seal = seal(sender, receiver, rumor, rngcore)?;
+    gift_wrap(receiver, &seal, [], rngcore)
+}
+
+/// Open a gift wrap addressed to `receiver`
+///
+/// Both layers are verified and the rumor must be authored by the signer of the seal.
+pub fn unwrap(receiver: &NostrSecretKey, gift_wrap: &EventData) -> Result<UnwrappedGift, GiftWrapError>
+{
+    check_kind(gift_wrap, Kind::GiftWrap)?;
+    gift_wrap.verify()?;
+    let seal_json = nip44::decrypt(&ConversationKey::derive(receiver, &gift_wrap.pubkey), gift_wrap.content.as_str())?;
+    let seal = EventData::from_json(seal_json).map_err(EventDataError::from)?;
+
+    check_kind(&seal, Kind::Seal)?;
+    seal.verify()?;
+    let rumor_json = nip44::decrypt(&ConversationKey::derive(receiver, &seal.pubkey), seal.content.as_str())?;
+    let rumor = UnsignedEvent::from_json(rumor_json).map_err(EventDataError::from)?;
+
+    rumor.verify_id()?;
+    if rumor.pubkey != seal.pubkey {
+        return Err(GiftWrapError::SenderMismatch);
+    }
+    Ok(UnwrappedGift { sender: seal.pubkey, rumor })
+}
+
+/// Participants of a private conversation, the author and the `p` tags of the rumor
+///
+/// The same set of participants identifies the same chat room.
+pub fn chat_participants(rumor: &UnsignedEvent) -> BTreeSet<NostrPubKey>
+{
+    let mut participants: BTreeSet<NostrPubKey> = rumor
+        .tags
+        .iter()
+        .filter(|tag| tag.kind_str() == Some("p"))
+        .filter_map(|tag| match tag.as_standardized() {
+            TagStandard::PublicKey { public_key, .. } => Some(public_key),
+            _ => None,
+        })
+        .collect();
+    participants.insert(rumor.pubkey.clone());
+    participants
+}
+
+/// Private direct message from `sender` to `receivers`, optionally replying to `reply_to`
+///
+/// Returns one gift wrap per receiver and one to `sender`, so the message shows up in the
+/// history of every participant.
+pub fn private_msg<RG, S>(
+    sender: &NostrSecretKey,
+    receivers: &[NostrPubKey],
+    message: S,
+    reply_to: Option<EventId>,
+    rngcore: &mut RG,
+) -> Result<Vec<EventData>, GiftWrapError>
+where
+    RG: CryptoRngCore,
+    S: Into<String>,
+{
+    let sender_key = public_key(&signing_key(sender)?);
+    let rumor = EventBuilder::private_msg_rumor(receivers.iter().cloned(), message, reply_to)
+        .into_unsigned_event(sender_key.clone());
+
+    let mut recipients: BTreeSet<NostrPubKey> = receivers.iter().cloned().collect();
+    recipients.insert(sender_key);
+    recipients
+        .iter()
+        .map(|receiver| wrap_rumor(sender, receiver, &rumor, rngcore))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::rng::CryptoHashRng;
+
+    const ALICE: &str = "6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+    const BOB: &str = "7b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+    const CAROL: &str = "8b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e";
+
+    fn keys(secret_key_hex: &str) -> (NostrSecretKey, NostrPubKey) {
+        let secret_key = NostrSecretKey(AsymmetricKeyImpl().parse_secret_key_from_hex(secret_key_hex).unwrap());
+        let public_key = public_key(&signing_key(&secret_key).unwrap());
+        (secret_key, public_key)
+    }
+
+    fn rngcore() -> CryptoHashRng {
+        CryptoHashRng::from_seed([6u8; 32])
+    }
+
+    #[test]
+    fn test_wrap_and_unwrap() {
+        let (alice_sk, alice_pk) = keys(ALICE);
+        let (bob_sk, bob_pk) = keys(BOB);
+        let (carol_sk, _) = keys(CAROL);
+        let mut rng = rngcore();
+
+        let rumor = EventBuilder::text_note("secret note", []).into_unsigned_event(alice_pk.clone());
+        let seal = seal(&alice_sk, &bob_pk, &rumor, &mut rng).unwrap();
+        assert_eq!(seal.kind, Kind::Seal);
+        assert_eq!(seal.pubkey, alice_pk);
+        assert!(seal.tags.is_empty());
+        assert!(seal.created_at <= Timestamp::now());
+        assert!(seal.created_at.as_u64() + RANGE_RANDOM_TIMESTAMP_TWEAK.end >= rumor.created_at.as_u64());
+
+        let expiration = TagData::custom("expiration", ["4102444800"]);
+        let wrapped = gift_wrap(&bob_pk, &seal, [expiration], &mut rng).unwrap();
+        assert_eq!(wrapped.kind, Kind::GiftWrap);
+        assert_ne!(wrapped.pubkey, alice_pk);
+        assert_eq!(wrapped.public_keys().collect::<Vec<_>>(), vec![bob_pk.clone()]);
+        assert!(wrapped.expiration().is_some());
+
+        let unwrapped = unwrap(&bob_sk, &wrapped).unwrap();
+        assert_eq!(unwrapped.sender, alice_pk);
+        assert_eq!(unwrapped.rumor, rumor);
+
+        assert!(unwrap(&carol_sk, &wrapped).is_err());
+        assert!(matches!(
+            gift_wrap(&bob_pk, &wrapped, [], &mut rng),
+            Err(GiftWrapError::WrongKind { expected: Kind::Seal, .. })
+        ));
+        assert!(matches!(
+            unwrap(&bob_sk, &seal),
+            Err(GiftWrapError::WrongKind { expected: Kind::GiftWrap, .. })
+        ));
+    }
+
+    #[test]
+    fn test_forged_rumor_is_refused() {
+        let (alice_sk, _) = keys(ALICE);
+        let (bob_sk, bob_pk) = keys(BOB);
+        let (_, carol_pk) = keys(CAROL);
+        let mut rng = rngcore();
+
+        let forged = EventBuilder::text_note("from carol", []).into_unsigned_event(carol_pk);
+        assert!(matches!(seal(&alice_sk, &bob_pk, &forged, &mut rng), Err(GiftWrapError::SenderMismatch)));
+
+        let content = nip44::encrypt(&ConversationKey::derive(&alice_sk, &bob_pk), forged.as_json().as_str(), &mut rng).unwrap();
+        let forged_seal = EventBuilder::new(Kind::Seal, content, [])
+            .sign(&signing_key(&alice_sk).unwrap(), &mut rng)
+            .unwrap();
+        let wrapped = gift_wrap(&bob_pk, &forged_seal, [], &mut rng).unwrap();
+        assert!(matches!(unwrap(&bob_sk, &wrapped), Err(GiftWrapError::SenderMismatch)));
+
+        let mut tampered = wrapped.clone();
+        tampered.content = String::from("tampered");
+        assert!(matches!(unwrap(&bob_sk, &tampered), Err(GiftWrapError::Event(_))));
+    }
+
+    #[test]
+    fn test_private_msg() {
+        let (alice_sk, alice_pk) = keys(ALICE);
+        let (bob_sk, bob_pk) = keys(BOB);
+        let (carol_sk, carol_pk) = keys(CAROL);
+        let mut rng = rngcore();
+
+        let wraps = private_msg(&alice_sk, &[bob_pk.clone(), carol_pk.clone()], "hi both", None, &mut rng).unwrap();
+        assert_eq!(wraps.len(), 3);
+
+        let opened: Vec<UnwrappedGift> = [&alice_sk, &bob_sk, &carol_sk]
+            .iter()
+            .map(|secret_key| {
+                wraps
+                    .iter()
+                    .find_map(|wrapped| unwrap(secret_key, wrapped).ok())
+                    .unwrap()
+            })
+            .collect();
+        for gift in opened.iter() {
+            assert_eq!(gift.sender, alice_pk);
+            assert_eq!(gift.rumor, opened[0].rumor);
+            assert_eq!(gift.rumor.kind, Kind::PrivateDirectMessage);
+            assert_eq!(gift.rumor.content, "hi both");
+        }
+
+        let room = chat_participants(&opened[1].rumor);
+        assert_eq!(room, BTreeSet::from([alice_pk.clone(), bob_pk.clone(), carol_pk.clone()]));
+
+        let reply = private_msg(&bob_sk, &[alice_pk.clone(), carol_pk], "hello", Some(opened[1].rumor.id), &mut rng).unwrap();
+        let answer = unwrap(&alice_sk, reply.iter().find(|w| unwrap(&alice_sk, w).is_ok()).unwrap()).unwrap();
+        assert_eq!(chat_participants(&answer.rumor), room);
+        assert!(answer.rumor.tags.iter().any(|tag| tag.kind_str() == Some("e")));
+    }
+}
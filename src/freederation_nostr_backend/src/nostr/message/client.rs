@@ -0,0 +1,183 @@
+//! Messages sent by clients
+
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::nostr::event_data::EventData;
+use crate::nostr::filter::Filter;
+use crate::nostr::message::{single, split_message, MessageError, SubscriptionId};
+use crate::util::jsonutil::JsonUtil;
+
+/// Client message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientMessage {
+    /// `["EVENT", <event>]`, publish an event
+    Event(Box<EventData>),
+    /// `["REQ", <subscription_id>, <filter>, ...]`, request events and subscribe to new ones
+    Req {
+        subscription_id: SubscriptionId,
+        filters: Vec<Filter>,
+    },
+    /// `["COUNT", <subscription_id>, <filter>, ...]`, count the events matching the filters
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/45.md>
+    Count {
+        subscription_id: SubscriptionId,
+        filters: Vec<Filter>,
+    },
+    /// `["CLOSE", <subscription_id>]`, stop a subscription
+    Close(SubscriptionId),
+    /// `["AUTH", <event>]`, answer an authentication challenge
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/42.md>
+    Auth(Box<EventData>),
+}
+
+impl ClientMessage {
+    /// Publish `event`
+    #[inline]
+    pub fn event(event: EventData) -> Self {
+        Self::Event(Box::new(event))
+    }
+
+    /// Subscribe to `filters`
+    #[inline]
+    pub fn req(subscription_id: SubscriptionId, filters: Vec<Filter>) -> Self {
+        Self::Req { subscription_id, filters }
+    }
+
+    /// Count the events matching `filters`
+    #[inline]
+    pub fn count(subscription_id: SubscriptionId, filters: Vec<Filter>) -> Self {
+        Self::Count { subscription_id, filters }
+    }
+
+    /// Type of the message, first element of the array
+    pub fn message_type(&self) -> &'static str {
+        match self {
+            Self::Event(..) => "EVENT",
+            Self::Req { .. } => "REQ",
+            Self::Count { .. } => "COUNT",
+            Self::Close(..) => "CLOSE",
+            Self::Auth(..) => "AUTH",
+        }
+    }
+
+    /// JSON array of the message
+    pub fn as_value(&self) -> Value {
+        match self {
+            Self::Event(event) | Self::Auth(event) => json!([self.message_type(), event]),
+            Self::Req { subscription_id, filters } | Self::Count { subscription_id, filters } => {
+                let mut array: Vec<Value> = vec![json!(self.message_type()), json!(subscription_id)];
+                array.extend(filters.iter().map(|filter| json!(filter)));
+                Value::Array(array)
+            }
+            Self::Close(subscription_id) => json!([self.message_type(), subscription_id]),
+        }
+    }
+
+    /// Parse a JSON array
+    pub fn from_value(value: Value) -> Result<Self, MessageError> {
+        let (message_type, elements) = split_message(value)?;
+        match message_type.as_str() {
+            "EVENT" => Ok(Self::Event(Box::new(single("EVENT", elements)?))),
+            "REQ" => {
+                let (subscription_id, filters) = subscription("REQ", elements)?;
+                Ok(Self::Req { subscription_id, filters })
+            }
+            "COUNT" => {
+                let (subscription_id, filters) = subscription("COUNT", elements)?;
+                Ok(Self::Count { subscription_id, filters })
+            }
+            "CLOSE" => Ok(Self::Close(single("CLOSE", elements)?)),
+            "AUTH" => Ok(Self::Auth(Box::new(single("AUTH", elements)?))),
+            _ => Err(MessageError::UnknownType(message_type)),
+        }
+    }
+}
+
+/// Subscription id and at least one filter
+fn subscription(message_type: &'static str, mut elements: Vec<Value>) -> Result<(SubscriptionId, Vec<Filter>), MessageError> {
+    if elements.len() < 2 {
+        return Err(MessageError::WrongLength(message_type));
+    }
+    let subscription_id: SubscriptionId = serde_json::from_value(elements.remove(0))?;
+    let filters: Vec<Filter> = elements
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<_, _>>()?;
+    Ok((subscription_id, filters))
+}
+
+impl Serialize for ClientMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.as_value().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ClientMessage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value: Value = Value::deserialize(deserializer)?;
+        Self::from_value(value).map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonUtil for ClientMessage {
+    type Err = MessageError;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::event_kind::Kind;
+
+    const EVENT: &str = r#"{"id":"2be17aa3031bdcb006f0fce80c146dea9c1c0268b0af2398bb673365c6444d45","pubkey":"f86c44a2de95d9149b51c6a29afeabba264c18e2fa7c49de93424a0c56947785","created_at":1640839235,"kind":4,"tags":[["p","13adc511de7e1cfcf1c6b7f6365fb5a03442d7bcacf565ea57fa7770912c023d"]],"content":"uRuvYr585B80L6rSJiHocw==?iv=oh6LVqdsYYol3JfFnXTbPA==","sig":"a5d9290ef9659083c490b303eb7ee41356d8778ff19f2f91776c8dc4443388a64ffcf336e61af4c25c05ac3ae952d1ced889ed655b67790891222aaa15b99fdd"}"#;
+
+    #[test]
+    fn test_wire_format() {
+        let event = EventData::from_json(EVENT).unwrap();
+        let message = ClientMessage::event(event.clone());
+        assert_eq!(message.as_value(), json!(["EVENT", serde_json::from_str::<Value>(EVENT).unwrap()]));
+        assert_eq!(ClientMessage::from_json(message.as_json()).unwrap(), message);
+
+        let req = ClientMessage::from_json(r##"["REQ","sub",{"kinds":[4]},{"#p":["13adc511de7e1cfcf1c6b7f6365fb5a03442d7bcacf565ea57fa7770912c023d"],"limit":10}]"##).unwrap();
+        let ClientMessage::Req { subscription_id, filters } = &req else { panic!("not a REQ") };
+        assert_eq!(subscription_id.as_str(), "sub");
+        assert_eq!(filters[0], Filter::new().kind(Kind::EncryptedDirectMessage));
+        assert!(filters[1].match_event(&event));
+        assert_eq!(ClientMessage::from_json(req.as_json()).unwrap(), req);
+
+        let count = ClientMessage::from_json(r#"["COUNT","sub",{"kinds":[4]}]"#).unwrap();
+        assert_eq!(count, ClientMessage::count(SubscriptionId::new("sub").unwrap(), vec![Filter::new().kind(Kind::EncryptedDirectMessage)]));
+        assert_eq!(ClientMessage::from_json(count.as_json()).unwrap(), count);
+
+        let close = ClientMessage::from_json(r#"["CLOSE","sub"]"#).unwrap();
+        assert_eq!(close, ClientMessage::Close(SubscriptionId::new("sub").unwrap()));
+        assert_eq!(close.as_json(), r#"["CLOSE","sub"]"#);
+
+        let auth = ClientMessage::from_json(format!(r#"["AUTH",{EVENT}]"#)).unwrap();
+        assert_eq!(auth, ClientMessage::Auth(Box::new(event)));
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        assert!(matches!(ClientMessage::from_json(r#"{"type":"EVENT"}"#), Err(MessageError::Json(_))));
+        assert!(matches!(ClientMessage::from_value(json!({})), Err(MessageError::InvalidFormat)));
+        assert!(matches!(ClientMessage::from_value(json!([])), Err(MessageError::InvalidFormat)));
+        assert!(matches!(ClientMessage::from_value(json!([1, "sub"])), Err(MessageError::InvalidFormat)));
+        assert!(matches!(ClientMessage::from_value(json!(["PING"])), Err(MessageError::UnknownType(_))));
+        assert!(matches!(ClientMessage::from_value(json!(["REQ", "sub"])), Err(MessageError::WrongLength("REQ"))));
+        assert!(matches!(ClientMessage::from_value(json!(["COUNT", "sub"])), Err(MessageError::WrongLength("COUNT"))));
+        assert!(matches!(ClientMessage::from_value(json!(["CLOSE", "sub", "extra"])), Err(MessageError::WrongLength("CLOSE"))));
+        assert!(matches!(ClientMessage::from_value(json!(["CLOSE", ""])), Err(MessageError::Json(_))));
+        assert!(matches!(ClientMessage::from_value(json!(["EVENT", {"id": "zz"}])), Err(MessageError::Json(_))));
+    }
+}
@@ -0,0 +1,204 @@
+//! Messages exchanged between clients and relays
+//!
+//! Every message is a JSON array whose first element is its type.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/01.md>
+
+use core::fmt;
+use core::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub mod client;
+pub mod relay;
+
+pub use self::client::ClientMessage;
+pub use self::relay::RelayMessage;
+
+/// Longest subscription id
+pub const MAX_SUBSCRIPTION_ID_LENGTH: usize = 64;
+
+/// Message error
+#[derive(thiserror::Error, Debug)]
+pub enum MessageError {
+    #[error("Json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Message must be a JSON array starting with its type")]
+    InvalidFormat,
+
+    #[error("Unknown message type: {0}")]
+    UnknownType(String),
+
+    #[error("Wrong number of elements in {0} message")]
+    WrongLength(&'static str),
+
+    #[error("Invalid subscription id: {0}")]
+    InvalidSubscriptionId(String),
+}
+
+/// Subscription id chosen by the client, non-empty and at most [`MAX_SUBSCRIPTION_ID_LENGTH`] chars
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct SubscriptionId(String);
+
+impl SubscriptionId {
+    /// Check the length of `id`
+    pub fn new<S>(id: S) -> Result<Self, MessageError>
+    where
+        S: Into<String>,
+    {
+        let id: String = id.into();
+        match !id.is_empty() && id.chars().count() <= MAX_SUBSCRIPTION_ID_LENGTH {
+            true => Ok(Self(id)),
+            false => Err(MessageError::InvalidSubscriptionId(id)),
+        }
+    }
+
+    /// Get as `&str`
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Display for SubscriptionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for SubscriptionId {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let id: String = String::deserialize(deserializer)?;
+        Self::new(id).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Machine-readable prefix of `OK` and `CLOSED` messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MachineReadablePrefix {
+    Duplicate,
+    Pow,
+    Blocked,
+    RateLimited,
+    Invalid,
+    Restricted,
+    AuthRequired,
+    Error,
+}
+
+impl MachineReadablePrefix {
+    /// Prefix without the colon
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Duplicate => "duplicate",
+            Self::Pow => "pow",
+            Self::Blocked => "blocked",
+            Self::RateLimited => "rate-limited",
+            Self::Invalid => "invalid",
+            Self::Restricted => "restricted",
+            Self::AuthRequired => "auth-required",
+            Self::Error => "error",
+        }
+    }
+
+    /// `<prefix>: <message>`
+    pub fn message<S>(&self, message: S) -> String
+    where
+        S: AsRef<str>,
+    {
+        format!("{}: {}", self.as_str(), message.as_ref())
+    }
+
+    /// Prefix of a `message`
+    pub fn parse_message(message: &str) -> Option<Self> {
+        message.split_once(':').and_then(|(prefix, _)| Self::from_str(prefix).ok())
+    }
+}
+
+impl fmt::Display for MachineReadablePrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for MachineReadablePrefix {
+    type Err = MessageError;
+
+    fn from_str(prefix: &str) -> Result<Self, Self::Err> {
+        match prefix {
+            "duplicate" => Ok(Self::Duplicate),
+            "pow" => Ok(Self::Pow),
+            "blocked" => Ok(Self::Blocked),
+            "rate-limited" => Ok(Self::RateLimited),
+            "invalid" => Ok(Self::Invalid),
+            "restricted" => Ok(Self::Restricted),
+            "auth-required" => Ok(Self::AuthRequired),
+            "error" => Ok(Self::Error),
+            _ => Err(MessageError::UnknownType(String::from(prefix))),
+        }
+    }
+}
+
+/// Type and elements of a message array
+fn split_message(value: Value) -> Result<(String, Vec<Value>), MessageError> {
+    let Value::Array(mut array) = value else {
+        return Err(MessageError::InvalidFormat);
+    };
+    if array.is_empty() {
+        return Err(MessageError::InvalidFormat);
+    }
+    match array.remove(0) {
+        Value::String(message_type) => Ok((message_type, array)),
+        _ => Err(MessageError::InvalidFormat),
+    }
+}
+
+/// Deserialize the single element of a `message_type` message
+fn single<T>(message_type: &'static str, elements: Vec<Value>) -> Result<T, MessageError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let [element]: [Value; 1] = elements.try_into().map_err(|_| MessageError::WrongLength(message_type))?;
+    Ok(serde_json::from_value(element)?)
+}
+
+/// Deserialize the `count` elements of a `message_type` message as a tuple
+fn tuple<T>(message_type: &'static str, elements: Vec<Value>, count: usize) -> Result<T, MessageError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    if elements.len() != count {
+        return Err(MessageError::WrongLength(message_type));
+    }
+    Ok(serde_json::from_value(Value::Array(elements))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_id() {
+        assert_eq!(SubscriptionId::new("sub").unwrap().as_str(), "sub");
+        assert!(SubscriptionId::new("").is_err());
+        assert!(SubscriptionId::new("a".repeat(MAX_SUBSCRIPTION_ID_LENGTH)).is_ok());
+        assert!(SubscriptionId::new("a".repeat(MAX_SUBSCRIPTION_ID_LENGTH + 1)).is_err());
+        assert!(serde_json::from_str::<SubscriptionId>(r#""""#).is_err());
+    }
+
+    #[test]
+    fn test_prefix() {
+        let message = MachineReadablePrefix::RateLimited.message("slow down");
+        assert_eq!(message, "rate-limited: slow down");
+        assert_eq!(MachineReadablePrefix::parse_message(&message), Some(MachineReadablePrefix::RateLimited));
+        assert_eq!(MachineReadablePrefix::parse_message("pow: difficulty 8 is required"), Some(MachineReadablePrefix::Pow));
+        assert_eq!(MachineReadablePrefix::parse_message("auth-required: sign in"), Some(MachineReadablePrefix::AuthRequired));
+        assert_eq!(MachineReadablePrefix::parse_message("no prefix"), None);
+        assert_eq!(MachineReadablePrefix::parse_message("unknown: prefix"), None);
+    }
+}
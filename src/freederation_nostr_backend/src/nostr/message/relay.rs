@@ -0,0 +1,245 @@
+//! Messages sent by relays
+
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_id::EventId;
+use crate::nostr::message::{single, split_message, tuple, MachineReadablePrefix, MessageError, SubscriptionId};
+use crate::util::jsonutil::JsonUtil;
+
+/// Relay message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayMessage {
+    /// `["EVENT", <subscription_id>, <event>]`, event matching a subscription
+    Event {
+        subscription_id: SubscriptionId,
+        event: Box<EventData>,
+    },
+    /// `["OK", <event_id>, <true|false>, <message>]`, outcome of an `EVENT`
+    Ok {
+        event_id: EventId,
+        status: bool,
+        message: String,
+    },
+    /// `["EOSE", <subscription_id>]`, end of the stored events of a subscription
+    EndOfStoredEvents(SubscriptionId),
+    /// `["NOTICE", <message>]`, human-readable message
+    Notice(String),
+    /// `["CLOSED", <subscription_id>, <message>]`, subscription ended by the relay
+    Closed {
+        subscription_id: SubscriptionId,
+        message: String,
+    },
+    /// `["COUNT", <subscription_id>, {"count": <count>}]`, outcome of a `COUNT`
+    ///
+    /// `"approximate": true` is added to estimated counts.
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/45.md>
+    Count {
+        subscription_id: SubscriptionId,
+        count: u64,
+        approximate: bool,
+    },
+    /// `["AUTH", <challenge>]`, authentication challenge
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/42.md>
+    Auth(String),
+}
+
+impl RelayMessage {
+    /// `event` matching `subscription_id`
+    #[inline]
+    pub fn event(subscription_id: SubscriptionId, event: EventData) -> Self {
+        Self::Event { subscription_id, event: Box::new(event) }
+    }
+
+    /// Accepted event, `message` may be empty
+    pub fn accepted<S>(event_id: EventId, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::Ok { event_id, status: true, message: message.into() }
+    }
+
+    /// Rejected event, `message` gets the machine-readable `prefix`
+    pub fn rejected<S>(event_id: EventId, prefix: MachineReadablePrefix, message: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self::Ok { event_id, status: false, message: prefix.message(message) }
+    }
+
+    /// Subscription closed by the relay, `message` gets the machine-readable `prefix`
+    pub fn closed<S>(subscription_id: SubscriptionId, prefix: MachineReadablePrefix, message: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self::Closed { subscription_id, message: prefix.message(message) }
+    }
+
+    /// Machine-readable prefix of an `OK` or `CLOSED` message
+    pub fn prefix(&self) -> Option<MachineReadablePrefix> {
+        match self {
+            Self::Ok { message, .. } | Self::Closed { message, .. } => MachineReadablePrefix::parse_message(message),
+            _ => None,
+        }
+    }
+
+    /// Type of the message, first element of the array
+    pub fn message_type(&self) -> &'static str {
+        match self {
+            Self::Event { .. } => "EVENT",
+            Self::Ok { .. } => "OK",
+            Self::EndOfStoredEvents(..) => "EOSE",
+            Self::Notice(..) => "NOTICE",
+            Self::Closed { .. } => "CLOSED",
+            Self::Count { .. } => "COUNT",
+            Self::Auth(..) => "AUTH",
+        }
+    }
+
+    /// JSON array of the message
+    pub fn as_value(&self) -> Value {
+        let message_type: &str = self.message_type();
+        match self {
+            Self::Event { subscription_id, event } => json!([message_type, subscription_id, event]),
+            Self::Ok { event_id, status, message } => json!([message_type, event_id, status, message]),
+            Self::EndOfStoredEvents(subscription_id) => json!([message_type, subscription_id]),
+            Self::Notice(message) => json!([message_type, message]),
+            Self::Closed { subscription_id, message } => json!([message_type, subscription_id, message]),
+            Self::Count { subscription_id, count, approximate: false } => json!([message_type, subscription_id, { "count": count }]),
+            Self::Count { subscription_id, count, approximate: true } => {
+                json!([message_type, subscription_id, { "count": count, "approximate": true }])
+            }
+            Self::Auth(challenge) => json!([message_type, challenge]),
+        }
+    }
+
+    /// Parse a JSON array
+    pub fn from_value(value: Value) -> Result<Self, MessageError> {
+        let (message_type, elements) = split_message(value)?;
+        match message_type.as_str() {
+            "EVENT" => {
+                let (subscription_id, event): (SubscriptionId, EventData) = tuple("EVENT", elements, 2)?;
+                Ok(Self::event(subscription_id, event))
+            }
+            "OK" => {
+                let (event_id, status, message) = tuple("OK", elements, 3)?;
+                Ok(Self::Ok { event_id, status, message })
+            }
+            "EOSE" => Ok(Self::EndOfStoredEvents(single("EOSE", elements)?)),
+            "NOTICE" => Ok(Self::Notice(single("NOTICE", elements)?)),
+            "CLOSED" => {
+                let (subscription_id, message) = tuple("CLOSED", elements, 2)?;
+                Ok(Self::Closed { subscription_id, message })
+            }
+            "COUNT" => {
+                let (subscription_id, count): (SubscriptionId, CountResult) = tuple("COUNT", elements, 2)?;
+                Ok(Self::Count { subscription_id, count: count.count, approximate: count.approximate })
+            }
+            "AUTH" => Ok(Self::Auth(single("AUTH", elements)?)),
+            _ => Err(MessageError::UnknownType(message_type)),
+        }
+    }
+}
+
+/// Object of a `COUNT` message
+#[derive(Deserialize)]
+struct CountResult {
+    count: u64,
+    #[serde(default)]
+    approximate: bool,
+}
+
+impl Serialize for RelayMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.as_value().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RelayMessage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value: Value = Value::deserialize(deserializer)?;
+        Self::from_value(value).map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonUtil for RelayMessage {
+    type Err = MessageError;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT_ID: &str = "2be17aa3031bdcb006f0fce80c146dea9c1c0268b0af2398bb673365c6444d45";
+
+    fn sub() -> SubscriptionId {
+        SubscriptionId::new("sub").unwrap()
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let event_id = EventId::from_hex(EVENT_ID).unwrap();
+        let cases = [
+            (RelayMessage::accepted(event_id, ""), format!(r#"["OK","{EVENT_ID}",true,""]"#)),
+            (
+                RelayMessage::rejected(event_id, MachineReadablePrefix::Duplicate, "already have this event"),
+                format!(r#"["OK","{EVENT_ID}",false,"duplicate: already have this event"]"#),
+            ),
+            (RelayMessage::EndOfStoredEvents(sub()), String::from(r#"["EOSE","sub"]"#)),
+            (RelayMessage::Notice(String::from("hello")), String::from(r#"["NOTICE","hello"]"#)),
+            (
+                RelayMessage::closed(sub(), MachineReadablePrefix::AuthRequired, "sign in first"),
+                String::from(r#"["CLOSED","sub","auth-required: sign in first"]"#),
+            ),
+            (
+                RelayMessage::Count { subscription_id: sub(), count: 42, approximate: false },
+                String::from(r#"["COUNT","sub",{"count":42}]"#),
+            ),
+            (
+                RelayMessage::Count { subscription_id: sub(), count: 1_000, approximate: true },
+                String::from(r#"["COUNT","sub",{"approximate":true,"count":1000}]"#),
+            ),
+            (RelayMessage::Auth(String::from("challenge")), String::from(r#"["AUTH","challenge"]"#)),
+        ];
+        for (message, json) in cases {
+            assert_eq!(message.as_json(), json);
+            assert_eq!(RelayMessage::from_json(json).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_event_message() {
+        let event = r#"{"id":"2be17aa3031bdcb006f0fce80c146dea9c1c0268b0af2398bb673365c6444d45","pubkey":"f86c44a2de95d9149b51c6a29afeabba264c18e2fa7c49de93424a0c56947785","created_at":1640839235,"kind":4,"tags":[["p","13adc511de7e1cfcf1c6b7f6365fb5a03442d7bcacf565ea57fa7770912c023d"]],"content":"uRuvYr585B80L6rSJiHocw==?iv=oh6LVqdsYYol3JfFnXTbPA==","sig":"a5d9290ef9659083c490b303eb7ee41356d8778ff19f2f91776c8dc4443388a64ffcf336e61af4c25c05ac3ae952d1ced889ed655b67790891222aaa15b99fdd"}"#;
+        let json = format!(r#"["EVENT","sub",{event}]"#);
+        let message = RelayMessage::from_json(json.as_str()).unwrap();
+        assert_eq!(message, RelayMessage::event(sub(), EventData::from_json(event).unwrap()));
+        assert_eq!(message.as_value(), serde_json::from_str::<Value>(json.as_str()).unwrap());
+    }
+
+    #[test]
+    fn test_prefix_and_errors() {
+        let event_id = EventId::from_hex(EVENT_ID).unwrap();
+        let rejected = RelayMessage::rejected(event_id, MachineReadablePrefix::Pow, "difficulty 20 is required");
+        assert_eq!(rejected.prefix(), Some(MachineReadablePrefix::Pow));
+        assert_eq!(RelayMessage::accepted(event_id, "").prefix(), None);
+        assert_eq!(RelayMessage::Notice(String::from("error: not a prefix here")).prefix(), None);
+
+        assert!(matches!(RelayMessage::from_value(json!(["OK", EVENT_ID, true])), Err(MessageError::WrongLength("OK"))));
+        assert!(matches!(RelayMessage::from_value(json!(["OK", EVENT_ID, "true", ""])), Err(MessageError::Json(_))));
+        assert!(matches!(RelayMessage::from_value(json!(["CLOSED", "sub"])), Err(MessageError::WrongLength("CLOSED"))));
+        assert!(matches!(RelayMessage::from_value(json!(["COUNT", "sub", 42])), Err(MessageError::Json(_))));
+        assert!(matches!(RelayMessage::from_value(json!(["EOSE"])), Err(MessageError::WrongLength("EOSE"))));
+        assert!(matches!(RelayMessage::from_value(json!(["REQ", "sub", {}])), Err(MessageError::UnknownType(_))));
+    }
+}
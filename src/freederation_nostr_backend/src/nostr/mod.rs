@@ -4,4 +4,11 @@ pub mod event_error;
 pub mod tag;
 pub mod metadata;
 pub mod event_data;
+pub mod unsigned_event;
+pub mod event_builder;
+pub mod gift_wrap;
+pub mod pow;
+pub mod filter;
+pub mod search;
+pub mod message;
 // pub mod nostrevent;
\ No newline at end of file
@@ -0,0 +1,146 @@
+//! NIP13 proof of work miner
+//!
+//! Increments the `nonce` tag of an [`UnsignedEvent`] until its id has the target number of
+//! leading zero bits. [`PowMiner::mine`] tries a bounded number of nonces per call, so a canister
+//! can spread the search over several messages and stay under the instruction limit.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/13.md>
+
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::tag::tagkind::TagKind;
+use crate::nostr::tag::TagData;
+use crate::nostr::unsigned_event::UnsignedEvent;
+
+/// Highest difficulty mined in a single call by [`EventBuilder::pow`](crate::nostr::event_builder::EventBuilder::pow)
+///
+/// About 2^24 hashes are expected, higher targets go through [`PowMiner::mine`] over several calls.
+pub const MAX_POW_DIFFICULTY: u8 = 24;
+
+/// Compose `nonce` tag
+///
+/// JSON: `["nonce", "<nonce>", "<target-difficulty>"]`
+fn nonce_tag(nonce: u128, difficulty: u8) -> TagData {
+    TagData::custom(TagKind::Nonce.to_string(), [nonce.to_string(), difficulty.to_string()])
+}
+
+/// Resumable proof of work search
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowMiner {
+    /// Event whose last tag is the `nonce` tag
+    event: UnsignedEvent,
+    difficulty: u8,
+    /// Next nonce to try
+    nonce: u128,
+}
+
+impl PowMiner {
+    /// Mine `event` to `difficulty` leading zero bits
+    ///
+    /// Existing `nonce` tags are replaced by one committing to `difficulty`.
+    pub fn new(mut event: UnsignedEvent, difficulty: u8) -> Self {
+        event.tags.retain(|tag| tag.kind_str() != Some(TagKind::Nonce.to_string().as_str()));
+        event.tags.push(nonce_tag(0, difficulty));
+        Self { event, difficulty, nonce: 0 }
+    }
+
+    /// Get target difficulty
+    #[inline]
+    pub fn difficulty(&self) -> u8 {
+        self.difficulty
+    }
+
+    /// Number of nonces tried so far
+    #[inline]
+    pub fn attempts(&self) -> u128 {
+        self.nonce
+    }
+
+    /// Try at most `max_iterations` nonces
+    ///
+    /// Returns the mined event, or `None` when the search has to go on with another call.
+    pub fn mine(&mut self, max_iterations: u64) -> Option<UnsignedEvent> {
+        let last: usize = self.event.tags.len() - 1;
+        for _ in 0..max_iterations {
+            self.event.tags[last] = nonce_tag(self.nonce, self.difficulty);
+            self.event.compute_id();
+            self.nonce += 1;
+            if self.event.id.check_pow(self.difficulty) {
+                return Some(self.event.clone());
+            }
+        }
+        None
+    }
+
+    /// Mine without bound
+    pub fn mine_to_completion(mut self) -> UnsignedEvent {
+        loop {
+            if let Some(event) = self.mine(u64::MAX) {
+                return event;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::str::FromStr;
+
+    use crate::nostr::event_kind::Kind;
+    use crate::nostr::tag::tagstandard::TagStandard;
+    use crate::signing::NostrPubKey;
+    use crate::util::jsonutil::JsonUtil;
+    use crate::util::nostrpow::get_leading_zero_bits;
+    use crate::util::time::Timestamp;
+
+    fn unsigned_event() -> UnsignedEvent {
+        UnsignedEvent::new(
+            NostrPubKey::from_str("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap(),
+            Timestamp::from(1_700_000_000),
+            Kind::TextNote,
+            [TagData::custom("nonce", ["42", "30"]), TagData::custom("t", ["pow"])],
+            "It's just me mining my own business",
+        )
+    }
+
+    #[test]
+    fn test_mine_in_chunks() {
+        let mut miner = PowMiner::new(unsigned_event(), 10);
+        let mut chunks: u32 = 0;
+        let event = loop {
+            chunks += 1;
+            if let Some(event) = miner.mine(64) {
+                break event;
+            }
+        };
+        assert!(chunks > 1);
+        assert!(event.verify_id().is_ok());
+        assert!(get_leading_zero_bits(event.id.as_bytes()) >= 10);
+
+        let nonces: Vec<&TagData> = event.tags.iter().filter(|tag| tag.kind_str() == Some("nonce")).collect();
+        assert_eq!(nonces.len(), 1);
+        assert_eq!(
+            nonces[0].as_standardized(),
+            TagStandard::POW { nonce: miner.attempts() - 1, difficulty: 10 }
+        );
+        assert_eq!(event.tags[0], TagData::custom("t", ["pow"]));
+    }
+
+    #[test]
+    fn test_resume_after_serialization() {
+        let mut miner = PowMiner::new(unsigned_event(), 8);
+        assert_eq!(miner.difficulty(), 8);
+        let found = miner.clone().mine_to_completion();
+
+        let _ = miner.mine(1);
+        let json = serde_json::to_string(&miner).unwrap();
+        let resumed: PowMiner = serde_json::from_str(json.as_str()).unwrap();
+        assert_eq!(resumed.attempts(), 1);
+        assert_eq!(resumed.mine_to_completion().as_json(), found.as_json());
+
+        let trivial = PowMiner::new(unsigned_event(), 0).mine(1).unwrap();
+        assert!(trivial.verify_id().is_ok());
+    }
+}
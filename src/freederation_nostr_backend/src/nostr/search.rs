@@ -0,0 +1,233 @@
+//! Full-text search
+//!
+//! The `search` field of a [`Filter`](crate::nostr::filter::Filter) holds words, quoted phrases
+//! and `key:value` extensions. An event matches when each word is one of its terms, each phrase
+//! appears in one of its searchable texts and the supported extensions hold.
+//!
+//! Searchable texts are the `title`, `subject` and `summary` tags and the content, see [`searchable_texts`].
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/50.md>
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_kind::Kind;
+use crate::nostr::metadata::Metadata;
+use crate::util::jsonutil::JsonUtil;
+
+/// Shortest term, in characters
+pub const MIN_TERM_CHARS: usize = 2;
+
+/// Longest term, in characters, longer words are mostly urls, keys or encoded data
+pub const MAX_TERM_CHARS: usize = 32;
+
+/// Distinct terms of an event, the first ones in the order of [`searchable_texts`]
+pub const MAX_EVENT_TERMS: usize = 256;
+
+/// NIP32 namespace of the `l` tags matched by the `language:` extension
+pub const LANGUAGE_NAMESPACE: &str = "ISO-639-1";
+
+/// Frequent English words, too common to be terms
+const STOP_WORDS: &[&str] = &[
+    "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it", "no", "not", "of",
+    "on", "or", "such", "that", "the", "their", "then", "there", "these", "they", "this", "to", "was", "will", "with",
+];
+
+/// Weights of the `title`, `subject` and `summary` tags, the content weighs 1
+const TAG_WEIGHTS: [(&str, u16); 3] = [("title", 3), ("subject", 3), ("summary", 2)];
+
+/// Parsed `search` field
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// Terms of the words and phrases
+    pub terms: BTreeSet<String>,
+    /// Quoted phrases of more than one word
+    pub phrases: Vec<Vec<String>>,
+    /// `key:value` extensions, by lowercase key
+    pub extensions: BTreeMap<String, String>,
+}
+
+impl SearchQuery {
+    /// Parse a `search` field, an unterminated quote runs to the end
+    pub fn parse(search: &str) -> Self {
+        let mut query = Self::default();
+        for (index, part) in search.split('"').enumerate() {
+            if index % 2 == 1 {
+                let phrase: Vec<String> = words(part).collect();
+                query.terms.extend(phrase.iter().filter(|word| is_term(word)).cloned());
+                if phrase.len() > 1 {
+                    query.phrases.push(phrase);
+                }
+                continue;
+            }
+            for token in part.split_whitespace() {
+                match token.split_once(':') {
+                    Some((key, value)) if !key.is_empty() && !value.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic()) => {
+                        query.extensions.insert(key.to_ascii_lowercase(), String::from(value));
+                    }
+                    _ => query.terms.extend(words(token).filter(|word| is_term(word))),
+                }
+            }
+        }
+        query
+    }
+
+    /// Check if the query has terms to look up in an index
+    #[inline]
+    pub fn has_terms(&self) -> bool {
+        !self.terms.is_empty()
+    }
+
+    /// `language:` extension, an ISO 639-1 code
+    #[inline]
+    pub fn language(&self) -> Option<&str> {
+        self.extensions.get("language").map(String::as_str)
+    }
+
+    /// `domain:` extension, the domain of the NIP05 identifier of the author
+    #[inline]
+    pub fn domain(&self) -> Option<&str> {
+        self.extensions.get("domain").map(String::as_str)
+    }
+
+    /// Check if `event` has the terms and phrases of the query, and the language if asked for
+    ///
+    /// `domain:` depends on the metadata of the author and is left to the caller,
+    /// unsupported extensions are ignored.
+    pub fn match_event(&self, event: &EventData) -> bool {
+        let texts: Vec<Vec<String>> = searchable_texts(event).iter().map(|(text, _)| words(text).collect()).collect();
+        self.terms.iter().all(|term| texts.iter().any(|words| words.contains(term)))
+            && self.phrases.iter().all(|phrase| {
+                texts.iter().any(|words| words.windows(phrase.len()).any(|window| window == phrase.as_slice()))
+            })
+            && self.language().is_none_or(|language| has_language(event, language))
+    }
+}
+
+/// Lowercase alphanumeric words of `text`
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(str::to_lowercase)
+}
+
+/// Check if a word of [`words`] is a term
+pub fn is_term(word: &str) -> bool {
+    (MIN_TERM_CHARS..=MAX_TERM_CHARS).contains(&word.chars().count()) && !STOP_WORDS.contains(&word)
+}
+
+/// Searchable texts of an event with their weight
+///
+/// Encrypted contents are left out, the content of [`Kind::Metadata`] is reduced to the names,
+/// `about` and `nip05`.
+pub fn searchable_texts(event: &EventData) -> Vec<(String, u16)> {
+    let mut texts: Vec<(String, u16)> = Vec::new();
+    for (name, weight) in TAG_WEIGHTS {
+        texts.extend(
+            event.tags.iter()
+                .filter(|tag| tag.kind_str() == Some(name))
+                .filter_map(|tag| tag.content())
+                .map(|value| (String::from(value), weight)),
+        );
+    }
+    match event.kind {
+        Kind::EncryptedDirectMessage | Kind::Seal | Kind::GiftWrap => {}
+        Kind::Metadata => {
+            if let Ok(metadata) = Metadata::from_json(&event.content) {
+                texts.extend(
+                    [metadata.name, metadata.display_name, metadata.about, metadata.nip05]
+                        .into_iter()
+                        .flatten()
+                        .map(|value| (value, 1)),
+                );
+            }
+        }
+        _ => texts.push((event.content.clone(), 1)),
+    }
+    texts
+}
+
+/// Terms of an event with their weighted frequency, at most [`MAX_EVENT_TERMS`]
+pub fn event_terms(event: &EventData) -> BTreeMap<String, u16> {
+    let mut terms: BTreeMap<String, u16> = BTreeMap::new();
+    for (text, weight) in searchable_texts(event) {
+        for word in words(&text).filter(|word| is_term(word)) {
+            if terms.len() >= MAX_EVENT_TERMS && !terms.contains_key(&word) {
+                continue;
+            }
+            let frequency = terms.entry(word).or_default();
+            *frequency = frequency.saturating_add(weight);
+        }
+    }
+    terms
+}
+
+/// Check if `event` has a NIP32 `l` label of `language`
+fn has_language(event: &EventData, language: &str) -> bool {
+    event.tags.iter().any(|tag| {
+        let values: &[String] = tag.as_vec();
+        tag.kind_str() == Some("l")
+            && tag.content().is_some_and(|value| value.eq_ignore_ascii_case(language))
+            && values.get(2).is_none_or(|namespace| namespace == LANGUAGE_NAMESPACE)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::rng::CryptoHashRng;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::nostr::tag::TagData;
+    use crate::signing::{AsymmetricKeyOps, AsymmetricKeyImpl};
+
+    fn event(builder: EventBuilder) -> EventData {
+        let ecda = AsymmetricKeyImpl();
+        let mut rng = CryptoHashRng::from_seed([5u8; 32]);
+        let signer = ecda.generate_keypair(&mut rng).unwrap();
+        builder.sign(&signer, &mut rng).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let query = SearchQuery::parse(r#"Nostr relays "state of the art" language:en include:spam a https://x.y"#);
+        assert_eq!(query.terms, BTreeSet::from(["nostr", "relays", "state", "art"].map(String::from)));
+        assert_eq!(query.phrases, [["state", "of", "the", "art"].map(String::from)]);
+        assert_eq!(query.language(), Some("en"));
+        assert_eq!(query.extensions.get("include").map(String::as_str), Some("spam"));
+        assert_eq!(query.extensions.get("https").map(String::as_str), Some("//x.y"));
+        assert_eq!(query.domain(), None);
+
+        let query = SearchQuery::parse(r#"domain:Example.com "unterminated phrase"#);
+        assert_eq!(query.domain(), Some("Example.com"));
+        assert_eq!(query.phrases, [["unterminated", "phrase"].map(String::from)]);
+        assert!(!SearchQuery::parse("the a language:fr").has_terms());
+    }
+
+    #[test]
+    fn test_match_event() {
+        let tags = [
+            TagData::custom("title", ["Relays on the Internet Computer"]),
+            TagData::custom("l", ["en", LANGUAGE_NAMESPACE]),
+        ];
+        let note = event(EventBuilder::text_note("Stable memory is the state of the art.", tags));
+
+        assert!(SearchQuery::parse("internet STABLE").match_event(&note));
+        assert!(SearchQuery::parse(r#""state of the art" language:EN nsfw:false"#).match_event(&note));
+        assert!(!SearchQuery::parse(r#""art of the state""#).match_event(&note));
+        assert!(!SearchQuery::parse(r#""computer stable""#).match_event(&note));
+        assert!(!SearchQuery::parse("internet bitcoin").match_event(&note));
+        assert!(!SearchQuery::parse("language:de").match_event(&note));
+
+        let terms = event_terms(&note);
+        assert_eq!(terms.get("relays"), Some(&3));
+        assert_eq!(terms.get("state"), Some(&1));
+        assert_eq!(terms.get("the"), None);
+
+        let dm = event(EventBuilder::new(Kind::EncryptedDirectMessage, "secret words", [TagData::custom("subject", ["plans"])]));
+        assert_eq!(event_terms(&dm).into_keys().collect::<Vec<String>>(), ["plans"]);
+
+        let profile = event(EventBuilder::metadata(&Metadata::new().name("alice").about("Rust developer")));
+        assert!(SearchQuery::parse("alice rust").match_event(&profile));
+        assert!(!SearchQuery::parse("about").match_event(&profile));
+    }
+}
@@ -7,6 +7,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{json, Value};
 use serde::de::Error as DeserializerError;
 
+use crate::nostr::event_data::EventData;
 use crate::signing::{AsymmetricKeyError, NostrPubKey, NostrSignature, NostrSecretKey, AsymmetricKeyOps, AsymmetricKeyImpl};
 pub use crate::rng::CryptoRngCore as DelegationRngCore;
 use crate::util::basecore::ParseError;
@@ -81,13 +82,13 @@ impl EventProperties {
         }
     }
 
-    /*
-    pub fn from_event(event: &Event) -> Self {
+    /// Properties of `event`
+    pub fn from_event(event: &EventData) -> Self {
         Self {
-            kind: event.kind().as_u16(),
-            created_time: event.created_at().as_u64(),
+            kind: event.kind.as_u16(),
+            created_time: event.created_at.as_u64(),
         }
-    } */
+    }
 }
 
 impl Condition {
@@ -252,16 +253,18 @@ impl DelegationToken {
         self.token.as_bytes()
     }
 
+    /// Sign the token, NIP26 signs `sha256(token)` and not the raw token
     pub fn generate_signature<RG>(&self, delegator_skey: &DelegationSigningKey, rngcore:&mut RG) -> Result<NostrSignature, AsymmetricKeyError>
     where RG: DelegationRngCore
     {
-        let ecda = AsymmetricKeyImpl();        
-        Ok(NostrSignature(ecda.generate_signature_from_bytes(self.as_bytes(), &delegator_skey, rngcore)?))
+        let ecda = AsymmetricKeyImpl();
+        Ok(NostrSignature(ecda.generate_signature(self.token.as_str(), delegator_skey, rngcore)?))
     }
 
+    /// Verify a signature over `sha256(token)`
     pub fn verify_signature(&self, delegator_pkey: &NostrPubKey, signature: &NostrSignature) -> Result<(), AsymmetricKeyError> {
         let ecda = AsymmetricKeyImpl();
-        ecda.verifying_signature_from_bytes(self.as_bytes(), &delegator_pkey.0, &signature.0)
+        ecda.verifying_signature(self.token.as_str(), &delegator_pkey.0, &signature.0)
     }
 }
 
@@ -304,6 +307,17 @@ impl DelegationTag
         dtoken.verify_signature(&self.delegator_pubkey, &self.signature).map_err(|e|e.into())
     }
 
+    /// Validate the delegation of `event`
+    ///
+    /// The token must be signed by the delegator for the event author and the event must
+    /// satisfy every [`Condition`].
+    pub fn validate_event(&self, event: &EventData) -> Result<(), ConditionError> {
+        self.validate(&event.pubkey, &self.conditions)
+            .map_err(|_| ConditionError::ConditionsValidation(ValidationError::InvalidSignature))?;
+        self.conditions.evaluate(&EventProperties::from_event(event))?;
+        Ok(())
+    }
+
     /// Convert to JSON string.
     pub fn as_json(&self) -> String {
         let tag = json!([
@@ -372,4 +386,116 @@ impl FromStr for DelegationTag {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Self::from_json(s)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::nostr::event_error::EventDataError;
+    use crate::nostr::event_kind::Kind;
+    use crate::nostr::tag::TagData;
+    use crate::rng::CryptoHashRng;
+    use crate::util::time::Timestamp;
+
+    /// NIP26 example keys and conditions
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/26.md>
+    const DELEGATOR_SECRET_KEY: &str = "ee35e8bb71131c02c1d7e73231daa48e9953d329a4b701f7133c8f46dd21139c";
+    const DELEGATOR_PUBLIC_KEY: &str = "8e0d3d3eb2881ec137a11debe736a9086715a8c8beeeda615780064d68bc25dd";
+    const DELEGATEE_SECRET_KEY: &str = "777e4f60b4aa87937e13acc84f7abcc3c93cc035cb4c1e9f7a9086dd78fffce1";
+    const DELEGATEE_PUBLIC_KEY: &str = "477318cfb5427b9cfc66a9fa376150c1ddbc62115ae27cef72417eb959691396";
+    const CONDITIONS: &str = "kind=1&created_at>1674834236&created_at<1677426236";
+    /// Signature of `sha256(token)` by the delegator, published in the NIP26 example
+    const SPEC_TOKEN_SIGNATURE: &str = "6f44d7fe4f1c09f3954640fb58bd12bae8bb8ff4120853c4693106c82e920e2b898f1f9ba9bd65449a987c39c0423426ab7b53910c0c6abfb41b30bc16e5f524";
+    /// Signature of `sha256(token)` by the delegator, generated with another nonce
+    const TOKEN_SIGNATURE: &str = "f9f00fcf8480686d9da6dfde1187d4ba19c54f6ace4c73361a14db429c4b96eb30b29283d6ea1f06ba9e18e06e408244c689039ddadbacffc56060f3da5b04b8";
+
+    fn signing_key(secret_key_hex: &str) -> DelegationSigningKey {
+        let ecda = AsymmetricKeyImpl();
+        ecda.new_keypair(ecda.parse_secret_key_from_hex(secret_key_hex).unwrap()).unwrap()
+    }
+
+    /// Event signed by the delegatee, signing fails when the delegation does not hold
+    fn delegated_event(tag: &DelegationTag, kind: Kind, created_at: u64) -> Result<EventData, EventDataError> {
+        let tag = TagData::parse(&[
+            String::from(DELEGATION_KEYWORD),
+            tag.delegator_pubkey().to_string(),
+            tag.conditions().to_string(),
+            tag.signature().to_string(),
+        ])
+        .unwrap();
+        EventBuilder::new(kind, "delegated", [tag])
+            .custom_created_at(Timestamp::from(created_at))
+            .sign(&signing_key(DELEGATEE_SECRET_KEY), &mut CryptoHashRng::from_seed([8u8; 32]))
+    }
+
+    #[test]
+    fn test_token_vector() {
+        let delegatee = NostrPubKey::from_str(DELEGATEE_PUBLIC_KEY).unwrap();
+        let delegator = NostrPubKey::from_str(DELEGATOR_PUBLIC_KEY).unwrap();
+        let conditions = Conditions::from_str(CONDITIONS).unwrap();
+        let token = DelegationToken::new(&delegatee, &conditions).unwrap();
+        assert_eq!(token.to_string(), format!("nostr:delegation:{DELEGATEE_PUBLIC_KEY}:{CONDITIONS}"));
+
+        for signature in [SPEC_TOKEN_SIGNATURE, TOKEN_SIGNATURE] {
+            let signature = NostrSignature::from_str(signature).unwrap();
+            assert!(token.verify_signature(&delegator, &signature).is_ok());
+            assert!(token.verify_signature(&delegatee, &signature).is_err());
+        }
+
+        let tag = DelegationTag::try_from(vec![
+            String::from(DELEGATION_KEYWORD),
+            String::from(DELEGATOR_PUBLIC_KEY),
+            String::from(CONDITIONS),
+            String::from(TOKEN_SIGNATURE),
+        ])
+        .unwrap();
+        assert!(tag.validate(&delegatee, &conditions).is_ok());
+        assert!(tag.validate(&delegator, &conditions).is_err());
+    }
+
+    #[test]
+    fn test_delegated_event() {
+        let mut rng = CryptoHashRng::from_seed([9u8; 32]);
+        let delegatee = NostrPubKey::from_str(DELEGATEE_PUBLIC_KEY).unwrap();
+        let delegator = NostrPubKey::from_str(DELEGATOR_PUBLIC_KEY).unwrap();
+        let conditions = Conditions::from_str(CONDITIONS).unwrap();
+        let tag = DelegationTag::new(&signing_key(DELEGATOR_SECRET_KEY), delegatee.clone(), conditions, &mut rng).unwrap();
+        assert_eq!(tag.delegator_pubkey(), delegator);
+
+        let event = delegated_event(&tag, Kind::TextNote, 1_676_000_000).unwrap();
+        assert!(event.verify().is_ok());
+        assert_eq!(event.verify_delegation().unwrap(), Some(delegator.clone()));
+        assert_eq!(event.author(), delegator);
+
+        assert!(matches!(
+            delegated_event(&tag, Kind::Reaction, 1_676_000_000),
+            Err(EventDataError::InvalidDelegation(ConditionError::ConditionsValidation(ValidationError::InvalidKind)))
+        ));
+        assert!(matches!(
+            delegated_event(&tag, Kind::TextNote, 1_674_834_236),
+            Err(EventDataError::InvalidDelegation(ConditionError::ConditionsValidation(ValidationError::CreatedTooEarly)))
+        ));
+        assert!(matches!(
+            delegated_event(&tag, Kind::TextNote, 1_677_426_236),
+            Err(EventDataError::InvalidDelegation(ConditionError::ConditionsValidation(ValidationError::CreatedTooLate)))
+        ));
+
+        let forged = DelegationTag {
+            delegator_pubkey: delegator.clone(),
+            conditions: Conditions::from_str("kind=1").unwrap(),
+            signature: tag.signature(),
+        };
+        assert!(matches!(
+            delegated_event(&forged, Kind::TextNote, 1_676_000_000),
+            Err(EventDataError::InvalidDelegation(ConditionError::ConditionsValidation(ValidationError::InvalidSignature)))
+        ));
+
+        let plain = EventBuilder::text_note("plain", []).sign(&signing_key(DELEGATEE_SECRET_KEY), &mut rng).unwrap();
+        assert_eq!(plain.verify_delegation().unwrap(), None);
+        assert_eq!(plain.author(), delegatee);
+    }
+}
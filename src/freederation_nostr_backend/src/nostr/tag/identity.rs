@@ -0,0 +1,81 @@
+//! NIP39
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/39.md>
+
+use std::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+use crate::nostr::tag::TagError;
+
+/// Supported external identity providers
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExternalIdentity {
+    /// github.com
+    GitHub,
+    /// twitter.com
+    Twitter,
+    /// mastodon.social
+    Mastodon,
+    /// telegram.org
+    Telegram,
+}
+
+impl fmt::Display for ExternalIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::GitHub => write!(f, "github"),
+            Self::Twitter => write!(f, "twitter"),
+            Self::Mastodon => write!(f, "mastodon"),
+            Self::Telegram => write!(f, "telegram"),
+        }
+    }
+}
+
+impl FromStr for ExternalIdentity {
+    type Err = TagError;
+
+    /// Other platforms, like the NIP73 external content ids sharing the `i` tag, are not standardized
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Self::GitHub),
+            "twitter" => Ok(Self::Twitter),
+            "mastodon" => Ok(Self::Mastodon),
+            "telegram" => Ok(Self::Telegram),
+            _ => Err(TagError::UnknownStardardizedTag),
+        }
+    }
+}
+
+/// External identity claimed in an `i` tag
+///
+/// JSON: `["i", "<platform>:<identity>", "<proof>"]`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Identity {
+    pub platform: ExternalIdentity,
+    /// Username on the platform
+    pub ident: String,
+    /// String or object pointing to the proof, e.g. a gist id
+    pub proof: String,
+}
+
+impl Identity {
+    /// Identity from the `<platform>:<identity>` value and the proof of an `i` tag
+    pub fn new<S1, S2>(platform_ident: S1, proof: S2) -> Result<Self, TagError>
+    where
+        S1: AsRef<str>,
+        S2: Into<String>,
+    {
+        let (platform, ident) = platform_ident.as_ref().split_once(':').ok_or(TagError::InvalidIdentity)?;
+        Ok(Self {
+            platform: ExternalIdentity::from_str(platform)?,
+            ident: ident.to_string(),
+            proof: proof.into(),
+        })
+    }
+
+    /// Value of the tag, `<platform>:<identity>`
+    pub fn platform_ident(&self) -> String {
+        format!("{}:{}", self.platform, self.ident)
+    }
+}
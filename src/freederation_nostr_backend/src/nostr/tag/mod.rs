@@ -9,6 +9,8 @@ pub mod nostruri;
 pub mod single_letter_tag;
 pub mod tagkind;
 pub mod report;
+pub mod identity;
+pub mod status;
 pub mod relaymetadata;
 pub mod delegation;
 pub mod tagstandard;
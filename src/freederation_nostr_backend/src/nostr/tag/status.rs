@@ -0,0 +1,89 @@
+//! Values of the `status` tag
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/53.md> and <https://github.com/nostr-protocol/nips/blob/master/90.md>
+
+use std::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+use crate::nostr::tag::TagError;
+
+/// Status of a job feedback (NIP90)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DataVendingMachineStatus {
+    /// The service provider requires payment before continuing
+    PaymentRequired,
+    /// The service provider is processing the job
+    Processing,
+    /// The service provider was unable to process the job
+    Error,
+    /// The service provider successfully processed the job
+    Success,
+    /// The service provider partially processed the job
+    Partial,
+}
+
+impl fmt::Display for DataVendingMachineStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::PaymentRequired => write!(f, "payment-required"),
+            Self::Processing => write!(f, "processing"),
+            Self::Error => write!(f, "error"),
+            Self::Success => write!(f, "success"),
+            Self::Partial => write!(f, "partial"),
+        }
+    }
+}
+
+impl FromStr for DataVendingMachineStatus {
+    type Err = TagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "payment-required" => Ok(Self::PaymentRequired),
+            "processing" => Ok(Self::Processing),
+            "error" => Ok(Self::Error),
+            "success" => Ok(Self::Success),
+            "partial" => Ok(Self::Partial),
+            s => Err(TagError::InvalidTagField(s.to_string())),
+        }
+    }
+}
+
+/// Status of a live event (NIP53)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LiveEventStatus {
+    /// Planned
+    Planned,
+    /// Live
+    Live,
+    /// Ended
+    Ended,
+    /// Custom
+    Custom(String),
+}
+
+impl fmt::Display for LiveEventStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Planned => write!(f, "planned"),
+            Self::Live => write!(f, "live"),
+            Self::Ended => write!(f, "ended"),
+            Self::Custom(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl<S> From<S> for LiveEventStatus
+where
+    S: AsRef<str>,
+{
+    fn from(s: S) -> Self {
+        match s.as_ref() {
+            "planned" => Self::Planned,
+            "live" => Self::Live,
+            "ended" => Self::Ended,
+            s => Self::Custom(s.to_string()),
+        }
+    }
+}
@@ -2,11 +2,16 @@ use std::string::{String, ToString};
 use std::vec::Vec;
 use core::cmp::Ordering;
 use core::hash::{Hash, Hasher};
+use candid::CandidType;
 use serde::de::Error as DeserializerError;
 use serde::ser::SerializeSeq;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::nostr::tag::tagerror::TagError;
+use crate::nostr::tag::tagstandard::TagStandard;
+use crate::nostr::tag::coordinate::Coordinate;
+use crate::nostr::event_id::EventId;
+use crate::signing::NostrPubKey;
 
 /// Tag
 #[derive(Debug, Clone)]
@@ -67,6 +72,58 @@ impl TagData {
         ))
     }
 
+    /// Compose custom tag
+    ///
+    /// JSON: `["<kind>", "<value-1>", "<value-2>", ...]`
+    pub fn custom<K, I, S>(kind: K, values: I) -> Self
+    where
+        K: Into<String>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut buf: Vec<String> = vec![kind.into()];
+        buf.extend(values.into_iter().map(|v| v.into()));
+        Self::new(buf)
+    }
+
+    /// Compose `e` tag
+    ///
+    /// JSON: `["e", "<event-id>"]`
+    #[inline]
+    pub fn event(event_id: &EventId) -> Self {
+        Self::custom("e", [event_id.to_hex()])
+    }
+
+    /// Compose `p` tag
+    ///
+    /// JSON: `["p", "<public-key>"]`
+    #[inline]
+    pub fn public_key(public_key: &NostrPubKey) -> Self {
+        Self::custom("p", [String::from(public_key)])
+    }
+
+    /// Compose `a` tag
+    ///
+    /// JSON: `["a", "<kind>:<public-key>:<d-tag>"]`
+    #[inline]
+    pub fn coordinate(coordinate: &Coordinate) -> Self {
+        Self::custom("a", [coordinate.to_string()])
+    }
+
+    /// Standardize tag
+    ///
+    /// Tags with an unknown layout become [`TagStandard::Uncategorized`],
+    /// tags with invalid values become [`TagStandard::Malformed`].
+    pub fn as_standardized(&self) -> TagStandard {
+        match TagStandard::parse(&self.buf) {
+            Ok(standard) => standard,
+            Err(TagError::UnknownStardardizedTag) => {
+                TagStandard::Uncategorized(self.buf.clone())
+            }
+            Err(_) => TagStandard::Malformed,
+        }
+    }
+
     /// Get tag kind
     #[inline]
     pub fn kind_str(&self) -> Option<&str> {
@@ -106,6 +163,20 @@ impl Serialize for TagData {
     }
 }
 
+/// Candid `vec text`, as the JSON array
+impl CandidType for TagData {
+    fn _ty() -> candid::types::Type {
+        Vec::<String>::ty()
+    }
+
+    fn idl_serialize<S>(&self, serializer: S) -> Result<(), S::Error>
+    where
+        S: candid::types::Serializer,
+    {
+        self.buf.idl_serialize(serializer)
+    }
+}
+
 impl<'de> Deserialize<'de> for TagData {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -21,6 +21,9 @@ pub enum TagError {
     #[error("Invalid event info: {0}")]
     InvalidEvent(#[from] EventDataError),
     
+    #[error("Invalid delegation: {0}")]
+    Delegation(#[from] crate::nostr::tag::delegation::ConditionError),
+
     #[error("Invalid Image: {0}")]
     Image(#[from] crate::util::nostrimage::Error),
 
@@ -1,6 +1,5 @@
 use core::str::{FromStr};
 use url::Url;
-use k256::sha2::{Digest, Sha256};
 use hex_conservative::{FromHex, DisplayHex};
 
 use crate::nostr::event_id::EventId;
@@ -14,12 +13,18 @@ use crate::util::basecore::{DataBytes};
 
 use crate::nostr::tag::marker::Marker;
 use crate::nostr::tag::report::Report;
+use crate::nostr::tag::identity::Identity;
+use crate::nostr::tag::status::{DataVendingMachineStatus, LiveEventStatus};
 use crate::nostr::tag::relaymetadata::RelayMetadata;
 use crate::nostr::tag::coordinate::Coordinate;
 use crate::nostr::tag::delegation::{Conditions,DelegationTag};
 use crate::nostr::tag::nostrhttpmethod::NostrHttpMethod;
 use crate::nostr::tag::tagerror::TagError;
 use crate::nostr::tag::tagkind::TagKind;
+use crate::nostr::tag::tagdata::TagData;
+use crate::nostr::tag::single_letter_tag::{Alphabet, SingleLetterTag};
+use crate::util::basecore::ParseError;
+use crate::util::jsonutil::JsonUtil;
 
 
 /// Standardized tag
@@ -28,8 +33,8 @@ pub enum TagStandard {
     /// Not processed yey
     NotProcessed,
     
-    /// A valid tag that is not implemented yet
-    Uncategorized(String),
+    /// A valid tag that is not implemented yet, with its kind and values
+    Uncategorized(Vec<String>),
     
     /// A tag with parsicng errors
     Malformed,
@@ -39,6 +44,7 @@ pub enum TagStandard {
     /// <https://github.com/nostr-protocol/nips/blob/master/01.md> and <https://github.com/nostr-protocol/nips/blob/master/10.md>
     Event {
         event_id: EventId,
+        /// An empty relay slot is kept as an empty url
         relay_url: Option<UncheckedUrl>,
         marker: Option<Marker>,
         /// Should be the public key of the author of the referenced event
@@ -54,6 +60,7 @@ pub enum TagStandard {
     /// <https://github.com/nostr-protocol/nips/blob/master/01.md>
     PublicKey {
         public_key: NostrPubKey,
+        /// An empty relay slot is kept as an empty url
         relay_url: Option<UncheckedUrl>,
         alias: Option<String>,
         /// Whether the p tag is an uppercase P or not
@@ -63,12 +70,17 @@ pub enum TagStandard {
     ///
     /// <https://github.com/nostr-protocol/nips/blob/master/56.md>
     PublicKeyReport(NostrPubKey, Report),
+    /// External identity
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/39.md>
+    ExternalIdentity(Identity),
     Reference(String),
     /// Relay Metadata
     ///
     /// <https://github.com/nostr-protocol/nips/blob/master/65.md>
     RelayMetadata {
-        relay_url: Url,
+        /// Checked to be a url, kept as written
+        relay_url: UncheckedUrl,
         metadata: Option<RelayMetadata>,
     },
     Hashtag(String),
@@ -106,9 +118,16 @@ pub enum TagStandard {
     Bolt11(String),
     Preimage(String),
     Relays(Vec<UncheckedUrl>),
+    /// Amount in millisats (NIP57)
+    Amount {
+        millisats: u64,
+        bolt11: Option<String>,
+    },
+    Lnurl(String),
     Name(String),
     PublishedAt(Timestamp),
-    Url(Url),
+    /// Checked to be a url, kept as written
+    Url(UncheckedUrl),
     MimeType(String),
     Aes256Gcm {
         key: String,
@@ -119,12 +138,34 @@ pub enum TagStandard {
     Dim(ImageDimensions),
     Magnet(String),
     Blurhash(String),
+    /// Live event streaming url (NIP53)
+    Streaming(UncheckedUrl),
+    /// Live event recording url (NIP53)
+    Recording(UncheckedUrl),
+    Starts(Timestamp),
+    Ends(Timestamp),
+    /// Job feedback status (NIP90)
+    DataVendingMachineStatus {
+        status: DataVendingMachineStatus,
+        extra_info: Option<String>,
+    },
+    /// Live event status (NIP53)
+    LiveEventStatus(LiveEventStatus),
+    CurrentParticipants(u64),
+    TotalParticipants(u64),
     Method(NostrHttpMethod),
     AbsoluteURL(UncheckedUrl),    
     Payload(DataBytes),
     Anon {
         msg: Option<String>,
-    },    
+    },
+    /// Proxy
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/48.md>
+    Proxy {
+        id: String,
+        protocol: String,
+    },
     Emoji {
         /// Name given for the emoji, which MUST be comprised of only alphanumeric characters and underscores
         shortcode: String,
@@ -154,6 +195,11 @@ impl Default for TagStandard
 
 impl TagStandard
 {
+    /// Parse a standardized tag from its array representation
+    ///
+    /// Return [`TagError::UnknownStardardizedTag`] if the tag layout is not known.
+    /// A tag that would not serialize back to the same values, e.g. `["k", "01"]`, is kept as
+    /// [`TagStandard::Uncategorized`], so converting it back never changes the id of its event.
     pub fn parse<S>(params:&[S]) -> Result<Self, TagError>
     where S: AsRef<str>
     {
@@ -162,14 +208,18 @@ impl TagStandard
             None => return Err(TagError::KindNotFound),
         };
 
-        Self::internal_parse(tag_kind, params)
+        let standard: Self = Self::internal_parse(tag_kind, params)?;
+        let tag: Vec<String> = params.iter().map(|param| param.as_ref().to_string()).collect();
+        if Vec::<String>::from(standard.clone()) != tag {
+            return Ok(Self::Uncategorized(tag));
+        }
+        Ok(standard)
     }
 
-    fn internal_parse<S>(tag_kind: TagKind, params: &[S]) -> Result<Self, TagError>
+    fn internal_parse<S>(tag_kind: TagKind, tag: &[S]) -> Result<Self, TagError>
     where
         S: AsRef<str>,
     {
-        /*
         match tag_kind {
             TagKind::SingleLetter(single_letter) => match single_letter {
                 // Parse `a` tag
@@ -239,7 +289,7 @@ impl TagStandard
                 }) => {
                     if tag_1.starts_with("ws://") || tag_1.starts_with("wss://") {
                         Ok(Self::RelayMetadata {
-                            relay_url: Url::parse(tag_1)?,
+                            relay_url: parse_url(tag_1)?,
                             metadata: None,
                         })
                     } else {
@@ -261,7 +311,7 @@ impl TagStandard
                 TagKind::SingleLetter(SingleLetterTag {
                     character: Alphabet::K,
                     uppercase: false,
-                }) => Ok(Self::Kind(Kind::from_str(tag_1)?)),
+                }) => Ok(Self::Kind(Kind::from_str(tag_1).map_err(ParseError::from)?)),
                 TagKind::SingleLetter(SingleLetterTag {
                     character: Alphabet::M,
                     uppercase: false,
@@ -269,55 +319,56 @@ impl TagStandard
                 TagKind::SingleLetter(SingleLetterTag {
                     character: Alphabet::X,
                     uppercase: false,
-                }) => Ok(Self::Sha256(Sha256Hash::from_str(tag_1)?)),
+                }) => Ok(Self::Sha256(parse_hex_bytes(tag_1)?)),
                 TagKind::SingleLetter(SingleLetterTag {
                     character: Alphabet::U,
                     uppercase: false,
                 }) => Ok(Self::AbsoluteURL(UncheckedUrl::from(tag_1))),
+                TagKind::SingleLetter(SingleLetterTag {
+                    character: Alphabet::L,
+                    uppercase: true,
+                }) => Ok(Self::LabelNamespace(tag_1.to_string())),
                 TagKind::Relay => Ok(Self::Relay(UncheckedUrl::from(tag_1))),
-                TagKind::Expiration => Ok(Self::Expiration(Timestamp::from_str(tag_1)?)),
+                TagKind::Expiration => Ok(Self::Expiration(parse_timestamp(tag_1)?)),
                 TagKind::Subject => Ok(Self::Subject(tag_1.to_string())),
                 TagKind::Challenge => Ok(Self::Challenge(tag_1.to_string())),
                 TagKind::Title => Ok(Self::Title(tag_1.to_string())),
                 TagKind::Image => Ok(Self::Image(UncheckedUrl::from(tag_1), None)),
                 TagKind::Thumb => Ok(Self::Thumb(UncheckedUrl::from(tag_1), None)),
                 TagKind::Summary => Ok(Self::Summary(tag_1.to_string())),
-                TagKind::PublishedAt => Ok(Self::PublishedAt(Timestamp::from_str(tag_1)?)),
+                TagKind::PublishedAt => Ok(Self::PublishedAt(parse_timestamp(tag_1)?)),
                 TagKind::Description => Ok(Self::Description(tag_1.to_string())),
                 TagKind::Bolt11 => Ok(Self::Bolt11(tag_1.to_string())),
                 TagKind::Preimage => Ok(Self::Preimage(tag_1.to_string())),
                 TagKind::Amount => Ok(Self::Amount {
-                    millisats: tag_1.parse()?,
+                    millisats: tag_1.parse().map_err(ParseError::from)?,
                     bolt11: None,
                 }),
                 TagKind::Lnurl => Ok(Self::Lnurl(tag_1.to_string())),
                 TagKind::Name => Ok(Self::Name(tag_1.to_string())),
-                TagKind::Url => Ok(Self::Url(Url::parse(tag_1)?)),
+                TagKind::Url => Ok(Self::Url(parse_url(tag_1)?)),
+                TagKind::Size => Ok(Self::Size(tag_1.parse().map_err(ParseError::from)?)),
+                TagKind::Dim => Ok(Self::Dim(ImageDimensions::from_str(tag_1)?)),
                 TagKind::Magnet => Ok(Self::Magnet(tag_1.to_string())),
                 TagKind::Blurhash => Ok(Self::Blurhash(tag_1.to_string())),
                 TagKind::Streaming => Ok(Self::Streaming(UncheckedUrl::from(tag_1))),
                 TagKind::Recording => Ok(Self::Recording(UncheckedUrl::from(tag_1))),
-                TagKind::Starts => Ok(Self::Starts(Timestamp::from_str(tag_1)?)),
-                TagKind::Ends => Ok(Self::Ends(Timestamp::from_str(tag_1)?)),
+                TagKind::Starts => Ok(Self::Starts(parse_timestamp(tag_1)?)),
+                TagKind::Ends => Ok(Self::Ends(parse_timestamp(tag_1)?)),
                 TagKind::Status => match DataVendingMachineStatus::from_str(tag_1) {
                     Ok(status) => Ok(Self::DataVendingMachineStatus {
                         status,
                         extra_info: None,
                     }),
-                    Err(_) => Ok(Self::LiveEventStatus(LiveEventStatus::from(tag_1))), /* TODO: check if unknown status error? */
+                    Err(_) => Ok(Self::LiveEventStatus(LiveEventStatus::from(tag_1))),
                 },
-                TagKind::CurrentParticipants => Ok(Self::CurrentParticipants(tag_1.parse()?)),
-                TagKind::TotalParticipants => Ok(Self::TotalParticipants(tag_1.parse()?)),
-                TagKind::Method => Ok(Self::Method(HttpMethod::from_str(tag_1)?)),
-                TagKind::Payload => Ok(Self::Payload(Sha256Hash::from_str(tag_1)?)),
-                TagKind::Request => Ok(Self::Request(Event::from_json(tag_1)?)),
+                TagKind::CurrentParticipants => Ok(Self::CurrentParticipants(tag_1.parse().map_err(ParseError::from)?)),
+                TagKind::TotalParticipants => Ok(Self::TotalParticipants(tag_1.parse().map_err(ParseError::from)?)),
+                TagKind::Method => Ok(Self::Method(NostrHttpMethod::from_str(tag_1)?)),
+                TagKind::Payload => Ok(Self::Payload(parse_hex_bytes(tag_1)?)),
+                TagKind::Request => Ok(Self::Request(EventData::from_json(tag_1)?)),
                 TagKind::Word => Ok(Self::Word(tag_1.to_string())),
-                TagKind::SingleLetter(SingleLetterTag {
-                    character: Alphabet::L,
-                    uppercase: true,
-                }) => Ok(Self::LabelNamespace(tag_1.to_string())),
-                TagKind::Dim => Ok(Self::Dim(ImageDimensions::from_str(tag_1)?)),
-                _ => Err(Error::UnknownStardardizedTag),
+                _ => Err(TagError::UnknownStardardizedTag),
             };
         }
 
@@ -331,8 +382,8 @@ impl TagStandard
                     uppercase: false,
                 }) => Ok(Self::ExternalIdentity(Identity::new(tag_1, tag_2)?)),
                 TagKind::Nonce => Ok(Self::POW {
-                    nonce: tag_1.parse()?,
-                    difficulty: tag_2.parse()?,
+                    nonce: tag_1.parse().map_err(ParseError::from)?,
+                    difficulty: tag_2.parse().map_err(ParseError::from)?,
                 }),
                 TagKind::Image => Ok(Self::Image(
                     UncheckedUrl::from(tag_1),
@@ -346,6 +397,10 @@ impl TagStandard
                     key: tag_1.to_string(),
                     iv: tag_2.to_string(),
                 }),
+                TagKind::Amount => Ok(Self::Amount {
+                    millisats: tag_1.parse().map_err(ParseError::from)?,
+                    bolt11: Some(tag_2.to_string()),
+                }),
                 TagKind::SingleLetter(SingleLetterTag {
                     character: Alphabet::R,
                     uppercase: false,
@@ -354,16 +409,18 @@ impl TagStandard
                         && !tag_2.is_empty()
                     {
                         Ok(Self::RelayMetadata {
-                            relay_url: Url::parse(tag_1)?,
-                            metadata: Some(RelayMetadata::from_str(tag_2)?),
+                            relay_url: parse_url(tag_1)?,
+                            metadata: Some(RelayMetadata::from_str(tag_2).map_err(
+                                |_| TagError::InvalidRelayMetadata(tag_2.to_string())
+                            )?),
                         })
                     } else {
-                        Err(Error::UnknownStardardizedTag)
+                        Err(TagError::UnknownStardardizedTag)
                     }
                 }
                 TagKind::Proxy => Ok(Self::Proxy {
                     id: tag_1.to_string(),
-                    protocol: Protocol::from(tag_2),
+                    protocol: tag_2.to_string(),
                 }),
                 TagKind::Emoji => Ok(Self::Emoji {
                     shortcode: tag_1.to_string(),
@@ -374,17 +431,13 @@ impl TagStandard
                         status,
                         extra_info: Some(tag_2.to_string()),
                     }),
-                    Err(_) => Err(Error::UnknownStardardizedTag),
+                    Err(_) => Err(TagError::UnknownStardardizedTag),
                 },
-                _ => Err(Error::UnknownStardardizedTag),
+                _ => Err(TagError::UnknownStardardizedTag),
             };
         }
 
-        Err(Error::UnknownStardardizedTag)
-
-         */
-
-        todo!()
+        Err(TagError::UnknownStardardizedTag)
     }
 
     /// Compose `TagStandard::Event` without `relay_url` and `marker`
@@ -398,6 +451,276 @@ impl TagStandard
             public_key: None,
         }
     }
+
+    /// Compose `TagStandard::PublicKey` without `relay_url` and `alias`
+    ///
+    /// JSON: `["p", "<public-key>"]`
+    pub fn public_key(public_key: NostrPubKey) -> Self {
+        Self::PublicKey {
+            public_key,
+            relay_url: None,
+            alias: None,
+            uppercase: false,
+        }
+    }
+
+    /// Get the tag kind name of this tag, as it appears in index `0`
+    pub fn kind_str(&self) -> String {
+        let single_letter = |character: Alphabet| SingleLetterTag::lowercase(character).to_string();
+        match self {
+            Self::NotProcessed | Self::Malformed => String::new(),
+            Self::Uncategorized(tag) => tag.first().cloned().unwrap_or_default(),
+            Self::Event { .. } | Self::EventReport(..) => single_letter(Alphabet::E),
+            Self::PublicKey { uppercase, .. } => {
+                if *uppercase {
+                    SingleLetterTag::uppercase(Alphabet::P).to_string()
+                } else {
+                    single_letter(Alphabet::P)
+                }
+            }
+            Self::PublicKeyReport(..) => single_letter(Alphabet::P),
+            Self::ExternalIdentity(..) => single_letter(Alphabet::I),
+            Self::Reference(..) | Self::RelayMetadata { .. } => single_letter(Alphabet::R),
+            Self::Hashtag(..) => single_letter(Alphabet::T),
+            Self::Geohash(..) => single_letter(Alphabet::G),
+            Self::Identifier(..) => single_letter(Alphabet::D),
+            Self::Coordinate { .. } => single_letter(Alphabet::A),
+            Self::Kind(..) => single_letter(Alphabet::K),
+            Self::MimeType(..) => single_letter(Alphabet::M),
+            Self::Sha256(..) => single_letter(Alphabet::X),
+            Self::AbsoluteURL(..) => single_letter(Alphabet::U),
+            Self::Label(..) => single_letter(Alphabet::L),
+            Self::LabelNamespace(..) => SingleLetterTag::uppercase(Alphabet::L).to_string(),
+            Self::Relay(..) => TagKind::Relay.to_string(),
+            Self::POW { .. } => TagKind::Nonce.to_string(),
+            Self::Delegation { .. } => TagKind::Delegation.to_string(),
+            Self::ContentWarning { .. } => TagKind::ContentWarning.to_string(),
+            Self::Expiration(..) => TagKind::Expiration.to_string(),
+            Self::Subject(..) => TagKind::Subject.to_string(),
+            Self::Challenge(..) => TagKind::Challenge.to_string(),
+            Self::Title(..) => TagKind::Title.to_string(),
+            Self::Image(..) => TagKind::Image.to_string(),
+            Self::Thumb(..) => TagKind::Thumb.to_string(),
+            Self::Summary(..) => TagKind::Summary.to_string(),
+            Self::Description(..) => TagKind::Description.to_string(),
+            Self::Bolt11(..) => TagKind::Bolt11.to_string(),
+            Self::Preimage(..) => TagKind::Preimage.to_string(),
+            Self::Relays(..) => TagKind::Relays.to_string(),
+            Self::Amount { .. } => TagKind::Amount.to_string(),
+            Self::Lnurl(..) => TagKind::Lnurl.to_string(),
+            Self::Name(..) => TagKind::Name.to_string(),
+            Self::PublishedAt(..) => TagKind::PublishedAt.to_string(),
+            Self::Url(..) => TagKind::Url.to_string(),
+            Self::Aes256Gcm { .. } => TagKind::Aes256Gcm.to_string(),
+            Self::Size(..) => TagKind::Size.to_string(),
+            Self::Dim(..) => TagKind::Dim.to_string(),
+            Self::Magnet(..) => TagKind::Magnet.to_string(),
+            Self::Blurhash(..) => TagKind::Blurhash.to_string(),
+            Self::Streaming(..) => TagKind::Streaming.to_string(),
+            Self::Recording(..) => TagKind::Recording.to_string(),
+            Self::Starts(..) => TagKind::Starts.to_string(),
+            Self::Ends(..) => TagKind::Ends.to_string(),
+            Self::DataVendingMachineStatus { .. } | Self::LiveEventStatus(..) => TagKind::Status.to_string(),
+            Self::CurrentParticipants(..) => TagKind::CurrentParticipants.to_string(),
+            Self::TotalParticipants(..) => TagKind::TotalParticipants.to_string(),
+            Self::Method(..) => TagKind::Method.to_string(),
+            Self::Payload(..) => TagKind::Payload.to_string(),
+            Self::Anon { .. } => TagKind::Anon.to_string(),
+            Self::Proxy { .. } => TagKind::Proxy.to_string(),
+            Self::Emoji { .. } => TagKind::Emoji.to_string(),
+            Self::Encrypted => TagKind::Encrypted.to_string(),
+            Self::Request(..) => TagKind::Request.to_string(),
+            Self::Word(..) => TagKind::Word.to_string(),
+        }
+    }
+}
+
+impl From<TagStandard> for Vec<String> {
+    fn from(standard: TagStandard) -> Self {
+        let mut tag: Vec<String> = Vec::new();
+        let kind_str: String = standard.kind_str();
+        if !kind_str.is_empty() {
+            tag.push(kind_str);
+        }
+
+        match standard {
+            TagStandard::NotProcessed | TagStandard::Malformed => (),
+            TagStandard::Uncategorized(values) => tag.extend(values.into_iter().skip(1)),
+            TagStandard::Event { event_id, relay_url, marker, public_key } => {
+                tag.push(event_id.to_hex());
+                if relay_url.is_some() || marker.is_some() || public_key.is_some() {
+                    tag.push(relay_url.map(|r| r.to_string()).unwrap_or_default());
+                }
+                if marker.is_some() || public_key.is_some() {
+                    tag.push(marker.map(|m| m.to_string()).unwrap_or_default());
+                }
+                if let Some(public_key) = public_key {
+                    tag.push(public_key.to_string());
+                }
+            }
+            TagStandard::EventReport(event_id, report) => {
+                tag.extend([event_id.to_hex(), report.to_string()]);
+            }
+            TagStandard::PublicKey { public_key, relay_url, alias, .. } => {
+                tag.push(public_key.to_string());
+                match (relay_url, alias) {
+                    (Some(relay_url), Some(alias)) => tag.extend([relay_url.to_string(), alias]),
+                    (Some(relay_url), None) => tag.push(relay_url.to_string()),
+                    (None, Some(alias)) => tag.extend([String::new(), alias]),
+                    (None, None) => (),
+                }
+            }
+            TagStandard::PublicKeyReport(public_key, report) => {
+                tag.extend([public_key.to_string(), report.to_string()]);
+            }
+            TagStandard::ExternalIdentity(identity) => {
+                tag.extend([identity.platform_ident(), identity.proof]);
+            }
+            TagStandard::RelayMetadata { relay_url, metadata } => {
+                tag.push(relay_url.to_string());
+                if let Some(metadata) = metadata {
+                    tag.push(metadata.to_string());
+                }
+            }
+            TagStandard::Coordinate { coordinate, relay_url } => {
+                tag.push(coordinate.to_string());
+                if let Some(relay_url) = relay_url {
+                    tag.push(relay_url.to_string());
+                }
+            }
+            TagStandard::POW { nonce, difficulty } => {
+                tag.extend([nonce.to_string(), difficulty.to_string()]);
+            }
+            TagStandard::Delegation { delegator, conditions, sig } => {
+                tag.extend([delegator.to_string(), conditions.to_string(), sig.to_string()]);
+            }
+            TagStandard::ContentWarning { reason } => tag.extend(reason),
+            TagStandard::Anon { msg } => tag.extend(msg),
+            TagStandard::Image(url, dimensions) | TagStandard::Thumb(url, dimensions) => {
+                tag.push(url.to_string());
+                if let Some(dimensions) = dimensions {
+                    tag.push(dimensions.to_string());
+                }
+            }
+            TagStandard::Relays(relays) => tag.extend(relays.into_iter().map(|r| r.to_string())),
+            TagStandard::Amount { millisats, bolt11 } => {
+                tag.push(millisats.to_string());
+                tag.extend(bolt11);
+            }
+            TagStandard::Aes256Gcm { key, iv } => tag.extend([key, iv]),
+            TagStandard::DataVendingMachineStatus { status, extra_info } => {
+                tag.push(status.to_string());
+                tag.extend(extra_info);
+            }
+            TagStandard::LiveEventStatus(status) => tag.push(status.to_string()),
+            TagStandard::Proxy { id, protocol } => tag.extend([id, protocol]),
+            TagStandard::Emoji { shortcode, url } => tag.extend([shortcode, url.to_string()]),
+            TagStandard::Label(labels) => tag.extend(labels),
+            TagStandard::Encrypted => (),
+            TagStandard::Request(event) => tag.push(event.as_json()),
+            TagStandard::Sha256(bytes) | TagStandard::Payload(bytes) => {
+                tag.push(bytes.as_ref().to_lower_hex_string());
+            }
+            TagStandard::Kind(kind) => tag.push(kind.to_string()),
+            TagStandard::Expiration(timestamp)
+            | TagStandard::PublishedAt(timestamp)
+            | TagStandard::Starts(timestamp)
+            | TagStandard::Ends(timestamp) => tag.push(timestamp.to_string()),
+            TagStandard::Relay(url)
+            | TagStandard::AbsoluteURL(url)
+            | TagStandard::Streaming(url)
+            | TagStandard::Recording(url)
+            | TagStandard::Url(url) => tag.push(url.to_string()),
+            TagStandard::Size(size) => tag.push(size.to_string()),
+            TagStandard::Dim(dimensions) => tag.push(dimensions.to_string()),
+            TagStandard::Method(method) => tag.push(method.to_string()),
+            TagStandard::CurrentParticipants(num) | TagStandard::TotalParticipants(num) => {
+                tag.push(num.to_string());
+            }
+            TagStandard::Reference(value)
+            | TagStandard::Hashtag(value)
+            | TagStandard::Geohash(value)
+            | TagStandard::Identifier(value)
+            | TagStandard::Subject(value)
+            | TagStandard::Challenge(value)
+            | TagStandard::Title(value)
+            | TagStandard::Summary(value)
+            | TagStandard::Description(value)
+            | TagStandard::Bolt11(value)
+            | TagStandard::Preimage(value)
+            | TagStandard::Lnurl(value)
+            | TagStandard::Name(value)
+            | TagStandard::MimeType(value)
+            | TagStandard::Magnet(value)
+            | TagStandard::Blurhash(value)
+            | TagStandard::Word(value)
+            | TagStandard::LabelNamespace(value) => tag.push(value),
+        }
+
+        tag
+    }
+}
+
+impl TryFrom<TagStandard> for TagData {
+    type Error = TagError;
+
+    /// Return [`TagError::EmptyTag`] for the `NotProcessed` and `Malformed` placeholders
+    fn try_from(standard: TagStandard) -> Result<Self, Self::Error> {
+        let tag: Vec<String> = standard.into();
+        TagData::parse(&tag)
+    }
+}
+
+impl TryFrom<&TagData> for TagStandard {
+    type Error = TagError;
+
+    fn try_from(tag: &TagData) -> Result<Self, Self::Error> {
+        Self::parse(tag.as_vec())
+    }
+}
+
+/// Get the value at `index`, only if not empty
+fn extract_optional_string<S>(tag: &[S], index: usize) -> Option<&str>
+where
+    S: AsRef<str>,
+{
+    match tag.get(index) {
+        Some(t) => {
+            let t: &str = t.as_ref();
+            (!t.is_empty()).then_some(t)
+        }
+        None => None,
+    }
+}
+
+fn parse_hex_bytes(hexstr: &str) -> Result<DataBytes, TagError> {
+    let buffer = <Vec<u8> as FromHex>::from_hex(hexstr).map_err(ParseError::from)?;
+    Ok(buffer.into_boxed_slice())
+}
+
+/// Check that `url` parses, keeping it as written as parsing normalizes it, e.g. with a trailing `/`
+fn parse_url(url: &str) -> Result<UncheckedUrl, TagError> {
+    Url::parse(url).map_err(ParseError::from)?;
+    Ok(UncheckedUrl::from(url))
+}
+
+fn parse_timestamp(tsstr: &str) -> Result<Timestamp, TagError> {
+    Ok(Timestamp::from_str(tsstr).map_err(ParseError::from)?)
+}
+
+fn parse_a_tag<S>(tag: &[S]) -> Result<TagStandard, TagError>
+where
+    S: AsRef<str>,
+{
+    if tag.len() >= 2 {
+        let coordinate: Coordinate = Coordinate::from_str(tag[1].as_ref())?;
+        Ok(TagStandard::Coordinate {
+            coordinate,
+            relay_url: tag.get(2).map(|u| UncheckedUrl::from(u.as_ref())),
+        })
+    } else {
+        Err(TagError::UnknownStardardizedTag)
+    }
 }
 
 fn parse_e_tag<S>(tag: &[S]) -> Result<TagStandard, TagError>
@@ -417,10 +740,10 @@ where
                 Ok(report) => Ok(TagStandard::EventReport(event_id, report)),
                 Err(_) => Ok(TagStandard::Event {
                     event_id,
-                    relay_url: (!tag_2.is_empty()).then_some(UncheckedUrl::from(tag_2)),
+                    relay_url: Some(UncheckedUrl::from(tag_2)),
                     marker: tag_3.and_then(|t| (!t.is_empty()).then_some(Marker::from(t))),
                     public_key: match tag_4 {
-                        Some(public_key) => Some(NostrPubKey::from_str(public_key).map_err(|e| TagError::Keys(e)) ?),
+                        Some(public_key) => Some(NostrPubKey::from_str(public_key).map_err(TagError::Keys) ?),
                         None => None,
                     },
                 }),
@@ -431,4 +754,243 @@ where
     } else {
         Err(TagError::UnknownStardardizedTag)
     }
-}
\ No newline at end of file
+}
+
+fn parse_p_tag<S>(tag: &[S], uppercase: bool) -> Result<TagStandard, TagError>
+where
+    S: AsRef<str>,
+{
+    if tag.len() >= 2 {
+        let public_key: NostrPubKey = NostrPubKey::from_str(tag[1].as_ref()).map_err(TagError::Keys)?;
+
+        let tag_2: Option<&str> = tag.get(2).map(|r| r.as_ref());
+        let tag_3: Option<&str> = tag.get(3).map(|r| r.as_ref());
+
+        // Check if it's a report
+        if let Some(tag_2) = tag_2 {
+            return match Report::from_str(tag_2) {
+                Ok(report) if !uppercase => Ok(TagStandard::PublicKeyReport(public_key, report)),
+                _ => Ok(TagStandard::PublicKey {
+                    public_key,
+                    relay_url: Some(UncheckedUrl::from(tag_2)),
+                    alias: tag_3.and_then(|t| (!t.is_empty()).then_some(t.to_string())),
+                    uppercase,
+                }),
+            };
+        }
+
+        Ok(TagStandard::PublicKey {
+            public_key,
+            relay_url: None,
+            alias: None,
+            uppercase,
+        })
+    } else {
+        Err(TagError::UnknownStardardizedTag)
+    }
+}
+
+fn parse_delegation_tag<S>(tag: &[S]) -> Result<TagStandard, TagError>
+where
+    S: AsRef<str>,
+{
+    if tag.len() == 4 {
+        Ok(TagStandard::Delegation {
+            delegator: NostrPubKey::from_str(tag[1].as_ref()).map_err(TagError::Keys)?,
+            conditions: Conditions::from_str(tag[2].as_ref())?,
+            sig: NostrSignature::from_str(tag[3].as_ref()).map_err(TagError::Keys)?,
+        })
+    } else {
+        Err(TagError::UnknownStardardizedTag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::rng::CryptoHashRng;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::signing::{AsymmetricKeyOps, AsymmetricKeyImpl};
+    use crate::nostr::tag::identity::ExternalIdentity;
+
+    const EVENT_ID: &str = "378f145897eea948952674269945e88612420db35791784abf0616b4fed56ef7";
+    const PUBLIC_KEY: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const SIGNATURE: &str = "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0";
+
+    fn assert_roundtrip(tag: &[&str]) -> TagStandard {
+        let standard = TagStandard::parse(tag).unwrap();
+        let serialized: Vec<String> = standard.clone().into();
+        assert_eq!(serialized, tag, "{standard:?}");
+
+        let tagdata = TagData::try_from(standard.clone()).unwrap();
+        assert_eq!(tagdata.as_vec(), tag);
+        assert_eq!(tagdata.as_standardized(), standard);
+        standard
+    }
+
+    #[test]
+    fn test_event_tags_roundtrip() {
+        assert_eq!(assert_roundtrip(&["e", EVENT_ID]), TagStandard::event(EventId::from_hex(EVENT_ID).unwrap()));
+        assert_roundtrip(&["e", EVENT_ID, "wss://relay.damus.io"]);
+        assert_roundtrip(&["e", EVENT_ID, "", "root"]);
+        assert_roundtrip(&["e", EVENT_ID, ""]);
+        assert_roundtrip(&["e", EVENT_ID, "", "", PUBLIC_KEY]);
+        assert_roundtrip(&["e", EVENT_ID, "wss://relay.damus.io", "reply", PUBLIC_KEY]);
+        assert!(matches!(
+            assert_roundtrip(&["e", EVENT_ID, "spam"]),
+            TagStandard::EventReport(_, Report::Spam)
+        ));
+    }
+
+    #[test]
+    fn test_public_key_tags_roundtrip() {
+        assert_eq!(
+            assert_roundtrip(&["p", PUBLIC_KEY]),
+            TagStandard::public_key(NostrPubKey::from_str(PUBLIC_KEY).unwrap())
+        );
+        assert_roundtrip(&["p", PUBLIC_KEY, "wss://relay.damus.io"]);
+        assert_roundtrip(&["p", PUBLIC_KEY, "", "alias"]);
+        assert!(matches!(
+            assert_roundtrip(&["p", PUBLIC_KEY, ""]),
+            TagStandard::PublicKey { relay_url: Some(..), alias: None, .. }
+        ));
+        assert_roundtrip(&["p", PUBLIC_KEY, "wss://relay.damus.io", "alias"]);
+        assert!(matches!(
+            assert_roundtrip(&["P", PUBLIC_KEY]),
+            TagStandard::PublicKey { uppercase: true, .. }
+        ));
+        assert!(matches!(
+            assert_roundtrip(&["p", PUBLIC_KEY, "impersonation"]),
+            TagStandard::PublicKeyReport(_, Report::Impersonation)
+        ));
+    }
+
+    #[test]
+    fn test_single_letter_tags_roundtrip() {
+        let coordinate = format!("30023:{PUBLIC_KEY}:article");
+        assert_roundtrip(&["a", coordinate.as_str()]);
+        assert_roundtrip(&["a", coordinate.as_str(), "wss://relay.damus.io"]);
+        assert!(matches!(assert_roundtrip(&["r", "https://example.com"]), TagStandard::Reference(..)));
+        assert!(matches!(assert_roundtrip(&["r", "wss://relay.damus.io/"]), TagStandard::RelayMetadata { .. }));
+        assert_roundtrip(&["r", "wss://relay.damus.io/", "read"]);
+        assert!(matches!(assert_roundtrip(&["r", "wss://relay.damus.io"]), TagStandard::RelayMetadata { .. }));
+        assert_roundtrip(&["r", "wss://relay.damus.io", "write"]);
+        assert_roundtrip(&["t", "nostr"]);
+        assert_roundtrip(&["g", "u4pruydqqvj"]);
+        assert_roundtrip(&["d", "article"]);
+        assert_roundtrip(&["k", "1"]);
+        assert_roundtrip(&["m", "image/png"]);
+        assert_roundtrip(&["x", EVENT_ID]);
+        assert_roundtrip(&["u", "https://example.com/api"]);
+        assert_roundtrip(&["L", "ISO-639-1"]);
+        assert!(matches!(
+            assert_roundtrip(&["i", "github:semisol", "9721ce4ee4fceb91c9711ca2a6c9a5ab"]),
+            TagStandard::ExternalIdentity(Identity { platform: ExternalIdentity::GitHub, .. })
+        ));
+        assert_roundtrip(&["l", "en", "ISO-639-1"]);
+    }
+
+    #[test]
+    fn test_named_tags_roundtrip() {
+        let conditions = "kind=1&created_at>1674834236&created_at<1677426236";
+        assert_roundtrip(&["relay", "wss://relay.damus.io"]);
+        assert_roundtrip(&["nonce", "776797", "20"]);
+        assert_roundtrip(&["delegation", PUBLIC_KEY, conditions, SIGNATURE]);
+        assert_roundtrip(&["content-warning"]);
+        assert_roundtrip(&["content-warning", "reason"]);
+        assert_roundtrip(&["expiration", "1600000000"]);
+        assert_roundtrip(&["subject", "hello"]);
+        assert_roundtrip(&["challenge", "challenge-string"]);
+        assert_roundtrip(&["title", "title"]);
+        assert_roundtrip(&["image", "https://example.com/image.png"]);
+        assert_roundtrip(&["image", "https://example.com/image.png", "640x480"]);
+        assert_roundtrip(&["thumb", "https://example.com/thumb.png", "64x48"]);
+        assert_roundtrip(&["summary", "summary"]);
+        assert_roundtrip(&["published_at", "1600000000"]);
+        assert_roundtrip(&["description", "description"]);
+        assert_roundtrip(&["bolt11", "lnbc10u1p3unwfusp5t9r3yymhpfqculx78u027lxspgxcr2n2987mx2j55nnfs95nxnzqpp5jmrh92pfld78spqs78v9euf2385t83uvpwk9ldrlvf6ch7tpascqhp5zvkrmemgth3tufcvflmzjzfvjt023nazlhljz2n9hattj4f8jq8qxqyjw5qcqpjrzjqtc4fc44feggv7065fqe5m4ytjarg3repr5j9el35xhmtfexc42yczarjuqqfzqqqqqqqqlgqqqqqqgq9q9qxpqysgq079nkq507a5tw7xgttmj4u990j7wfggtrasah5gd4ywfr2pjcn29383tphp4t48gquelz9z78p4cq7ml3nrrphw5w6eckhjwmhezhnqpy6gyf0"]);
+        assert_roundtrip(&["preimage", "5d006d2cf1e73c7148e7519a4c68adc81642ce0e25a432b2434c99f97344c15f"]);
+        assert_roundtrip(&["relays", "wss://relay.damus.io", "wss://nos.lol"]);
+        assert_roundtrip(&["amount", "10000"]);
+        assert_roundtrip(&["amount", "10000", "lnbc1"]);
+        assert_roundtrip(&["lnurl", "lnurl1dp68gurn8ghj7um5v93kketj9ehx2amn9uh8wetvdskkkmn0wahz7mrww4excup0dajx2mrv92x9xp"]);
+        assert_roundtrip(&["name", "name"]);
+        assert_roundtrip(&["url", "https://example.com/"]);
+        assert!(matches!(assert_roundtrip(&["url", "https://example.com"]), TagStandard::Url(..)));
+        assert_roundtrip(&["aes-256-gcm", "key", "iv"]);
+        assert_roundtrip(&["size", "1024"]);
+        assert_roundtrip(&["dim", "640x480"]);
+        assert_roundtrip(&["magnet", "magnet:?xt=urn:btih:9a4f"]);
+        assert_roundtrip(&["blurhash", "eVF$^OI:${M{o#*0-nNFxakD-?xVM}WEWB%iNKxvR-oetmo#R-aen$"]);
+        assert_roundtrip(&["streaming", "https://example.com/live.m3u8"]);
+        assert_roundtrip(&["recording", "https://example.com/recording.m3u8"]);
+        assert_roundtrip(&["starts", "1600000000"]);
+        assert_roundtrip(&["ends", "1600003600"]);
+        assert_eq!(assert_roundtrip(&["status", "live"]), TagStandard::LiveEventStatus(LiveEventStatus::Live));
+        assert_roundtrip(&["status", "live-ish"]);
+        assert_eq!(
+            assert_roundtrip(&["status", "payment-required", "extra"]),
+            TagStandard::DataVendingMachineStatus {
+                status: DataVendingMachineStatus::PaymentRequired,
+                extra_info: Some(String::from("extra")),
+            }
+        );
+        assert_roundtrip(&["status", "success"]);
+        assert_roundtrip(&["current_participants", "10"]);
+        assert_roundtrip(&["total_participants", "100"]);
+        assert_roundtrip(&["method", "POST"]);
+        assert_roundtrip(&["payload", EVENT_ID]);
+        assert_roundtrip(&["anon"]);
+        assert_roundtrip(&["anon", "message"]);
+        assert_roundtrip(&["proxy", "https://example.com/post/1", "activitypub"]);
+        assert_roundtrip(&["emoji", "soapbox", "https://example.com/soapbox.png"]);
+        assert_roundtrip(&["encrypted"]);
+        assert_roundtrip(&["word", "nostr"]);
+    }
+
+    #[test]
+    fn test_request_tag_roundtrip() {
+        let ecda = AsymmetricKeyImpl();
+        let mut rng = CryptoHashRng::from_seed([3u8; 32]);
+        let signer = ecda.generate_keypair(&mut rng).unwrap();
+        let event = EventBuilder::text_note("job input", []).sign(&signer, &mut rng).unwrap();
+        let json = event.as_json();
+
+        assert_eq!(assert_roundtrip(&["request", json.as_str()]), TagStandard::Request(event));
+    }
+
+    #[test]
+    fn test_unknown_and_malformed_tags() {
+        assert!(matches!(TagStandard::parse::<&str>(&[]), Err(TagError::KindNotFound)));
+        assert!(matches!(
+            TagStandard::parse(&["custom", "value"]),
+            Err(TagError::UnknownStardardizedTag)
+        ));
+
+        let custom = TagData::custom("custom", ["value"]);
+        let standard = custom.as_standardized();
+        assert_eq!(standard, TagStandard::Uncategorized(vec![String::from("custom"), String::from("value")]));
+        assert_eq!(TagData::try_from(standard).unwrap(), custom);
+
+        let malformed = TagData::custom("e", ["not-an-event-id"]);
+        assert_eq!(malformed.as_standardized(), TagStandard::Malformed);
+        assert_eq!(TagData::custom("i", ["github", "proof"]).as_standardized(), TagStandard::Malformed);
+        assert!(matches!(
+            TagData::custom("i", ["isbn:9780765382030", "https://example.com"]).as_standardized(),
+            TagStandard::Uncategorized(..)
+        ));
+        assert!(matches!(
+            TagData::custom("status", ["live", "extra"]).as_standardized(),
+            TagStandard::Uncategorized(..)
+        ));
+
+        assert!(matches!(TagData::try_from(TagStandard::NotProcessed), Err(TagError::EmptyTag)));
+
+        // Tags normalized by parsing are kept as they are
+        for tag in [&["k", "01"][..], &["e", EVENT_ID, "", ""], &["content-warning", ""], &["expiration", "+1600000000"]] {
+            assert!(matches!(assert_roundtrip(tag), TagStandard::Uncategorized(..)), "{tag:?}");
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::event_id::EventId;
+use crate::nostr::event_error::EventDataError;
+use crate::nostr::event_kind::Kind;
+use crate::nostr::event_data::EventData;
+use crate::nostr::tag::TagData;
+use crate::signing::{
+    NostrPubKey, NostrSignature, NostrSigningKey, AsymmetricKeyOps, AsymmetricKeyImpl, CryptoRngCore
+};
+use crate::util::time::Timestamp;
+use crate::util::basecore::ParseError;
+use crate::util::jsonutil::JsonUtil;
+
+
+/// Event with a computed [`EventId`] but without signature
+///
+/// Produced by [`EventBuilder`](crate::nostr::event_builder::EventBuilder).
+/// Also used as the `rumor` of NIP59.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UnsignedEvent {
+    /// Id
+    pub id: EventId,
+    /// Author
+    pub pubkey: NostrPubKey,
+    /// Timestamp (seconds)
+    pub created_at: Timestamp,
+    /// Kind
+    pub kind: Kind,
+    /// Vector of [`TagData`]
+    pub tags: Vec<TagData>,
+    /// Content
+    pub content: String,
+}
+
+impl UnsignedEvent {
+    /// Compose an unsigned event, computing its [`EventId`]
+    pub fn new<I, S>(
+        public_key: NostrPubKey,
+        created_at: Timestamp,
+        kind: Kind,
+        tags: I,
+        content: S,
+    ) -> Self
+    where
+        I: IntoIterator<Item = TagData>,
+        S: Into<String>,
+    {
+        let tags: Vec<TagData> = tags.into_iter().collect();
+        let content: String = content.into();
+        let id = EventId::new(&public_key, &created_at, &kind, tags.as_slice(), content.as_str());
+        Self { id, pubkey: public_key, created_at, kind, tags, content }
+    }
+
+    /// Recompute the [`EventId`] after the fields have been edited
+    pub fn compute_id(&mut self) {
+        self.id = EventId::new(
+            &self.pubkey,
+            &self.created_at,
+            &self.kind,
+            self.tags.as_slice(),
+            self.content.as_str()
+        );
+    }
+
+    /// Check that [`UnsignedEvent::id`] matches the fields
+    pub fn verify_id(&self) -> Result<(), EventDataError>
+    {
+        let evid = EventId::new(
+            &self.pubkey,
+            &self.created_at,
+            &self.kind,
+            self.tags.as_slice(),
+            self.content.as_str()
+        );
+
+        if evid != self.id { Err(EventDataError::InvalidEventHash) } else { Ok(()) }
+    }
+
+    /// Sign the event id with `signer`
+    ///
+    /// `signer` must be the keypair of [`UnsignedEvent::pubkey`].
+    pub fn sign<RG>(self, signer: &NostrSigningKey, rngcore: &mut RG) -> Result<EventData, EventDataError>
+    where RG: CryptoRngCore
+    {
+        let ecda = AsymmetricKeyImpl();
+        if NostrPubKey(ecda.pubkey_from_pair(signer)) != self.pubkey {
+            return Err(EventDataError::Keys(crate::signing::AsymmetricKeyError::InvalidPublicKey));
+        }
+
+        let sig = ecda.generate_signature_from_bytes(self.id.as_bytes(), signer, rngcore)?;
+        self.add_signature(NostrSignature(sig))
+    }
+
+    /// Attach a signature produced elsewhere and verify the resulting [`EventData`]
+    pub fn add_signature(self, sig: NostrSignature) -> Result<EventData, EventDataError>
+    {
+        let event = EventData::new(
+            self.id, self.pubkey, self.created_at, self.kind, self.tags, self.content, sig
+        );
+        event.verify()?;
+        Ok(event)
+    }
+}
+
+impl JsonUtil for UnsignedEvent {
+    type Err = ParseError;
+}
+
+impl From<EventData> for UnsignedEvent {
+    fn from(event: EventData) -> Self {
+        Self {
+            id: event.id,
+            pubkey: event.pubkey,
+            created_at: event.created_at,
+            kind: event.kind,
+            tags: event.tags,
+            content: event.content,
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! NIP01 message processing
+//!
+//! Raw client messages are answered with the relay messages a websocket relay would send.
+//! The canister keeps no connection, so a `REQ` is answered with the stored events and `EOSE`,
+//! keeping the subscription alive is left to the gateway holding the websocket.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/01.md>
+
+use serde_json::Value;
+
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_id::EventId;
+use crate::nostr::filter::Filter;
+use crate::nostr::message::{ClientMessage, MachineReadablePrefix, RelayMessage};
+use crate::relay::PolicyError;
+use crate::store::{EventCount, PublishStatus, StoreError};
+use crate::util::jsonutil::JsonUtil;
+
+/// Rejection of a published event
+#[derive(thiserror::Error, Debug)]
+pub enum RelayError {
+
+    #[error(transparent)]
+    Policy(#[from] PolicyError),
+
+    #[error(transparent)]
+    Store(#[from] StoreError),
+
+    #[error("restricted: events are only accepted by update calls")]
+    ReadOnly,
+}
+
+impl RelayError {
+    /// `OK` message rejecting `event_id`
+    ///
+    /// A duplicate is accepted, as NIP01 asks. Policy errors already carry their prefix.
+    pub fn to_message(&self, event_id: EventId) -> RelayMessage {
+        match self {
+            Self::Store(StoreError::Duplicate(_)) => {
+                RelayMessage::accepted(event_id, MachineReadablePrefix::Duplicate.message("already have this event"))
+            }
+            Self::Store(StoreError::InvalidEvent(e)) => RelayMessage::rejected(event_id, MachineReadablePrefix::Invalid, e.to_string()),
+            Self::Store(e @ StoreError::Superseded(_)) => RelayMessage::rejected(event_id, MachineReadablePrefix::Duplicate, e.to_string()),
+            Self::Store(e @ StoreError::Deleted(_)) => RelayMessage::rejected(event_id, MachineReadablePrefix::Blocked, e.to_string()),
+            Self::Store(e @ StoreError::Expired(_)) => RelayMessage::rejected(event_id, MachineReadablePrefix::Invalid, e.to_string()),
+            Self::Policy(_) | Self::ReadOnly => RelayMessage::Ok { event_id, status: false, message: self.to_string() },
+        }
+    }
+}
+
+/// Canister state used by [`handle_message`]
+pub trait RelayContext {
+    /// Check `event` against the admission policy and store it
+    fn publish(&mut self, event: EventData) -> Result<PublishStatus, RelayError>;
+
+    /// Stored events matching any of `filters`, newest first
+    fn query(&self, filters: &[Filter]) -> Vec<EventData>;
+
+    /// Number of stored events matching any of `filters`
+    fn count(&self, filters: &[Filter]) -> EventCount;
+}
+
+/// Answer a raw client message
+///
+/// - `EVENT`: `OK`, the signature is verified before the admission policy
+/// - `REQ`: an `EVENT` per stored event, then `EOSE`
+/// - `COUNT`: `COUNT` of the stored events, from the indexes when possible
+/// - `CLOSE`: nothing
+/// - `AUTH`: `OK` rejecting it, NIP42 is not supported
+///
+/// A message that cannot be parsed gets a `NOTICE`, or an `OK` when it is an `EVENT` with a readable id.
+pub fn handle_message<C>(text: &str, context: &mut C) -> Vec<RelayMessage>
+where
+    C: RelayContext,
+{
+    let message = match ClientMessage::from_json(text) {
+        Ok(message) => message,
+        Err(e) => {
+            let reply = match malformed_event_id(text) {
+                Some(event_id) => RelayMessage::rejected(event_id, MachineReadablePrefix::Invalid, e.to_string()),
+                None => RelayMessage::Notice(MachineReadablePrefix::Invalid.message(e.to_string())),
+            };
+            return vec![reply];
+        }
+    };
+    match message {
+        ClientMessage::Event(event) => vec![publish(*event, context)],
+        ClientMessage::Req { subscription_id, filters } => {
+            let mut messages: Vec<RelayMessage> = context
+                .query(filters.as_slice())
+                .into_iter()
+                .map(|event| RelayMessage::event(subscription_id.clone(), event))
+                .collect();
+            messages.push(RelayMessage::EndOfStoredEvents(subscription_id));
+            messages
+        }
+        ClientMessage::Count { subscription_id, filters } => {
+            let EventCount { count, approximate } = context.count(filters.as_slice());
+            vec![RelayMessage::Count { subscription_id, count, approximate }]
+        }
+        ClientMessage::Close(_) => Vec::new(),
+        ClientMessage::Auth(event) => {
+            vec![RelayMessage::rejected(event.id, MachineReadablePrefix::Restricted, "authentication is not supported")]
+        }
+    }
+}
+
+fn publish<C>(event: EventData, context: &mut C) -> RelayMessage
+where
+    C: RelayContext,
+{
+    let event_id: EventId = event.id;
+    if let Err(e) = event.verify() {
+        return RelayMessage::rejected(event_id, MachineReadablePrefix::Invalid, e.to_string());
+    }
+    match context.publish(event) {
+        Ok(_) => RelayMessage::accepted(event_id, ""),
+        Err(e) => e.to_message(event_id),
+    }
+}
+
+/// Id of an `EVENT` whose event cannot be parsed
+fn malformed_event_id(text: &str) -> Option<EventId> {
+    let value: Value = serde_json::from_str(text).ok()?;
+    match value.as_array()?.as_slice() {
+        [Value::String(message_type), event] if message_type == "EVENT" => EventId::from_hex(event.get("id")?.as_str()?).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::memory_manager::{MemoryManager, VirtualMemory};
+    use ic_stable_structures::DefaultMemoryImpl;
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::nostr::message::SubscriptionId;
+    use crate::nostr::tag::coordinate::EventIdOrCoordinate;
+    use crate::nostr::tag::TagData;
+    use crate::relay::AdmissionPolicy;
+    use crate::rng::CryptoHashRng;
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, NostrSigningKey};
+    use crate::store::EventStore;
+    use crate::util::time::{MockTimeSupplier, Timestamp};
+
+    struct TestRelay {
+        store: EventStore<VirtualMemory<DefaultMemoryImpl>, MockTimeSupplier>,
+        policy: AdmissionPolicy,
+        read_only: bool,
+    }
+
+    impl TestRelay {
+        fn new() -> Self {
+            let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+            Self {
+                store: EventStore::init(|id| memory_manager.get(id), MockTimeSupplier::new(Timestamp::from(10_000))),
+                policy: AdmissionPolicy::default(),
+                read_only: false,
+            }
+        }
+
+        /// Relay messages answering `text`, as JSON
+        fn send(&mut self, text: &str) -> Vec<String> {
+            handle_message(text, self).iter().map(|message| message.as_json()).collect()
+        }
+
+        /// Single `OK` answering `text`
+        fn ok(&mut self, text: &str) -> RelayMessage {
+            let mut messages = handle_message(text, self);
+            assert_eq!(messages.len(), 1);
+            let message = messages.remove(0);
+            assert_eq!(message.message_type(), "OK");
+            message
+        }
+    }
+
+    impl RelayContext for TestRelay {
+        fn publish(&mut self, event: EventData) -> Result<PublishStatus, RelayError> {
+            if self.read_only {
+                return Err(RelayError::ReadOnly);
+            }
+            self.policy.check(&event)?;
+            Ok(self.store.publish(event)?)
+        }
+
+        fn query(&self, filters: &[Filter]) -> Vec<EventData> {
+            self.store.query(filters)
+        }
+
+        fn count(&self, filters: &[Filter]) -> EventCount {
+            self.store.count(filters)
+        }
+    }
+
+    fn signer() -> NostrSigningKey {
+        let ecda = AsymmetricKeyImpl();
+        ecda.generate_keypair(&mut CryptoHashRng::from_seed([1u8; 32])).unwrap()
+    }
+
+    fn event(builder: EventBuilder, created_at: u64) -> EventData {
+        builder
+            .custom_created_at(Timestamp::from(created_at))
+            .sign(&signer(), &mut CryptoHashRng::from_seed([2u8; 32]))
+            .unwrap()
+    }
+
+    fn event_message(event: &EventData) -> String {
+        format!(r#"["EVENT",{}]"#, event.as_json())
+    }
+
+    fn assert_rejected(message: RelayMessage, prefix: MachineReadablePrefix) {
+        assert!(matches!(message, RelayMessage::Ok { status: false, .. }), "{:?}", message);
+        assert_eq!(message.prefix(), Some(prefix), "{:?}", message);
+    }
+
+    #[test]
+    fn test_conversation() {
+        let mut relay = TestRelay::new();
+        let first = event(EventBuilder::text_note("first", []), 1_000);
+        let second = event(EventBuilder::text_note("second", []), 2_000);
+        let feed = Filter::new().author(first.pubkey.clone());
+        let stored = |event: &EventData| RelayMessage::event(SubscriptionId::new("feed").unwrap(), event.clone()).as_json();
+
+        let script: Vec<(String, Vec<String>)> = vec![
+            (
+                format!(r#"["REQ","feed",{}]"#, feed.as_json()),
+                vec![String::from(r#"["EOSE","feed"]"#)],
+            ),
+            (event_message(&first), vec![format!(r#"["OK","{}",true,""]"#, first.id)]),
+            (event_message(&second), vec![format!(r#"["OK","{}",true,""]"#, second.id)]),
+            (
+                event_message(&first),
+                vec![format!(r#"["OK","{}",true,"duplicate: already have this event"]"#, first.id)],
+            ),
+            (
+                format!(r#"["REQ","feed",{}]"#, feed.as_json()),
+                vec![stored(&second), stored(&first), String::from(r#"["EOSE","feed"]"#)],
+            ),
+            (
+                format!(r#"["REQ","feed",{}]"#, feed.clone().limit(1).as_json()),
+                vec![stored(&second), String::from(r#"["EOSE","feed"]"#)],
+            ),
+            (
+                format!(r#"["COUNT","feed",{}]"#, feed.as_json()),
+                vec![String::from(r#"["COUNT","feed",{"count":2}]"#)],
+            ),
+            (String::from(r#"["CLOSE","feed"]"#), vec![]),
+        ];
+        for (request, expected) in script {
+            assert_eq!(relay.send(request.as_str()), expected, "{}", request);
+        }
+    }
+
+    #[test]
+    fn test_rejections() {
+        let mut relay = TestRelay::new();
+
+        let mut forged = event(EventBuilder::text_note("original", []), 1_000);
+        forged.content = String::from("forged");
+        assert_rejected(relay.ok(event_message(&forged).as_str()), MachineReadablePrefix::Invalid);
+
+        let expiring = event(EventBuilder::text_note("late", [TagData::custom("expiration", ["5000"])]), 1_000);
+        assert_rejected(relay.ok(event_message(&expiring).as_str()), MachineReadablePrefix::Invalid);
+
+        let newer = event(EventBuilder::contact_list([]), 2_000);
+        let older = event(EventBuilder::contact_list([]), 1_000);
+        assert_eq!(relay.ok(event_message(&newer).as_str()), RelayMessage::accepted(newer.id, ""));
+        assert_rejected(relay.ok(event_message(&older).as_str()), MachineReadablePrefix::Duplicate);
+
+        let deletion = event(EventBuilder::delete([EventIdOrCoordinate::from(newer.id)], None::<String>), 3_000);
+        assert_eq!(relay.ok(event_message(&deletion).as_str()), RelayMessage::accepted(deletion.id, ""));
+        assert_rejected(relay.ok(event_message(&newer).as_str()), MachineReadablePrefix::Blocked);
+
+        relay.policy.min_pow = 8;
+        let note = event(EventBuilder::text_note("no work", []), 1_000);
+        assert_rejected(relay.ok(event_message(&note).as_str()), MachineReadablePrefix::Pow);
+
+        relay.policy.min_pow = 0;
+        relay.read_only = true;
+        assert_rejected(relay.ok(event_message(&note).as_str()), MachineReadablePrefix::Restricted);
+        assert_rejected(relay.ok(format!(r#"["AUTH",{}]"#, note.as_json()).as_str()), MachineReadablePrefix::Restricted);
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        let mut relay = TestRelay::new();
+        for text in ["hello", "[]", r#"["PING"]"#, r#"["REQ","feed"]"#, r#"["EVENT",{"content":"no id"}]"#] {
+            let messages = handle_message(text, &mut relay);
+            assert!(matches!(messages.as_slice(), [RelayMessage::Notice(notice)] if notice.starts_with("invalid: ")), "{}", text);
+        }
+
+        let id = event(EventBuilder::text_note("note", []), 1_000).id;
+        let truncated = format!(r#"["EVENT",{{"id":"{}","content":"note"}}]"#, id);
+        assert_rejected(relay.ok(truncated.as_str()), MachineReadablePrefix::Invalid);
+        assert!(matches!(relay.ok(truncated.as_str()), RelayMessage::Ok { event_id, .. } if event_id == id));
+    }
+}
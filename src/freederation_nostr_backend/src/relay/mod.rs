@@ -0,0 +1,7 @@
+//! Relay rules applied on top of the event store
+
+pub mod handler;
+pub mod policy;
+
+pub use self::handler::{handle_message, RelayContext, RelayError};
+pub use self::policy::{AdmissionPolicy, PolicyError};
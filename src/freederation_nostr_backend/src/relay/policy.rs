@@ -0,0 +1,128 @@
+//! Admission policy
+//!
+//! Rules checked before an event reaches the [`EventStore`](crate::store::EventStore).
+//! Errors are prefixed as the machine-readable part of a NIP01 `OK` message.
+
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::nostr::event_data::EventData;
+
+/// Configurable admission rules of the relay
+#[derive(CandidType, Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdmissionPolicy {
+    /// Minimum NIP13 difficulty, `0` accepts events without proof of work
+    pub min_pow: u8,
+    /// Maximum size of the content in bytes
+    #[serde(default)]
+    pub max_content_length: Option<u32>,
+}
+
+/// Rejection of an event by the [`AdmissionPolicy`]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+
+    #[error("pow: missing nonce tag, difficulty {0} is required")]
+    MissingPowCommitment(u8),
+
+    #[error("pow: committed difficulty {committed} is lower than {required}")]
+    PowCommitmentTooLow { committed: u8, required: u8 },
+
+    #[error("pow: difficulty {actual} is lower than the committed {committed}")]
+    PowNotMet { actual: u8, committed: u8 },
+
+    #[error("invalid: content is longer than {0} bytes")]
+    ContentTooLong(u32),
+}
+
+impl AdmissionPolicy {
+    /// Check `event` against every rule
+    pub fn check(&self, event: &EventData) -> Result<(), PolicyError> {
+        self.check_content_length(event)?;
+        self.check_pow(event)
+    }
+
+    /// Size of the content
+    pub fn check_content_length(&self, event: &EventData) -> Result<(), PolicyError> {
+        match self.max_content_length {
+            Some(max) if event.content.len() > max as usize => Err(PolicyError::ContentTooLong(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// NIP13 proof of work
+    ///
+    /// The committed target must reach the minimum and the id must reach the committed target,
+    /// so a spammer targeting a lower difficulty is rejected even when the id is lucky.
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/13.md>
+    pub fn check_pow(&self, event: &EventData) -> Result<(), PolicyError> {
+        if self.min_pow == 0 {
+            return Ok(());
+        }
+
+        let committed: u8 = event.pow_commitment().ok_or(PolicyError::MissingPowCommitment(self.min_pow))?;
+        if committed < self.min_pow {
+            return Err(PolicyError::PowCommitmentTooLow { committed, required: self.min_pow });
+        }
+
+        let actual: u8 = event.pow_difficulty();
+        if actual < committed {
+            return Err(PolicyError::PowNotMet { actual, committed });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::nostr::tag::TagData;
+    use crate::rng::CryptoHashRng;
+    use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, NostrSigningKey};
+
+    fn signer() -> NostrSigningKey {
+        let ecda = AsymmetricKeyImpl();
+        let skey = ecda.parse_secret_key_from_hex("6b911fd37cdf5c81d4c0adb1ab7fa822ed253ab0ad9aa18d77257c88b29b718e").unwrap();
+        ecda.new_keypair(skey).unwrap()
+    }
+
+    fn event(builder: EventBuilder) -> EventData {
+        builder.sign(&signer(), &mut CryptoHashRng::from_seed([5u8; 32])).unwrap()
+    }
+
+    #[test]
+    fn test_min_pow() {
+        let policy = AdmissionPolicy { min_pow: 8, ..Default::default() };
+        let plain = event(EventBuilder::text_note("plain", []));
+        let mined = event(EventBuilder::text_note("mined", []).pow(8));
+        let weak = event(EventBuilder::text_note("weak", []).pow(4));
+        let unmined = event(EventBuilder::text_note("unmined", [TagData::custom("nonce", ["0", "24"])]));
+
+        assert!(AdmissionPolicy::default().check(&plain).is_ok());
+        assert!(policy.check(&mined).is_ok());
+        assert_eq!(policy.check(&plain), Err(PolicyError::MissingPowCommitment(8)));
+        assert_eq!(policy.check(&weak), Err(PolicyError::PowCommitmentTooLow { committed: 4, required: 8 }));
+        assert_eq!(
+            policy.check(&unmined),
+            Err(PolicyError::PowNotMet { actual: unmined.pow_difficulty(), committed: 24 })
+        );
+        assert!(policy.check(&unmined).unwrap_err().to_string().starts_with("pow: "));
+    }
+
+    #[test]
+    fn test_max_content_length() {
+        let policy = AdmissionPolicy { max_content_length: Some(5), ..Default::default() };
+        assert!(policy.check(&event(EventBuilder::text_note("short", []))).is_ok());
+        assert_eq!(
+            policy.check(&event(EventBuilder::text_note("longer", []))),
+            Err(PolicyError::ContentTooLong(5))
+        );
+
+        let stored: AdmissionPolicy = serde_json::from_str(r#"{"min_pow":4}"#).unwrap();
+        assert_eq!(stored, AdmissionPolicy { min_pow: 4, max_content_length: None });
+    }
+}
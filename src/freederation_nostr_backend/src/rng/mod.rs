@@ -2,7 +2,143 @@ use rand_chacha::ChaCha20Rng;
 use rand_core::SeedableRng;
 use rand_core::{CryptoRng, RngCore};
 
+use crate::encryption::{Sha256Hash, Sha2Digest};
+
 pub type CryptoHashRng = ChaCha20Rng;
 pub type CryptoHashSeed = <ChaCha20Rng as SeedableRng>::Seed;
 
-trait_set::trait_set!{pub trait CryptoRngCore =  CryptoRng + RngCore}
\ No newline at end of file
+trait_set::trait_set!{pub trait CryptoRngCore =  CryptoRng + RngCore}
+
+/// Serialized size of a [`RngState`]
+pub const RNG_STATE_SIZE: usize = 1 + 32 + 8 + 16;
+
+/// [`RngState`] error
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum RngError {
+
+    #[error("RNG not seeded yet")]
+    NotSeeded,
+
+    #[error("Invalid RNG state")]
+    InvalidState,
+}
+
+/// Persistent state of a [`CryptoHashRng`]
+///
+/// The generator is restored, advanced and saved back on every use, so consecutive uses
+/// never replay the same output. Seed entropy is mixed in with [`RngState::reseed`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RngState {
+    seeded: bool,
+    seed: CryptoHashSeed,
+    stream: u64,
+    word_pos: u128,
+}
+
+impl RngState {
+    /// Check if entropy has been mixed in
+    #[inline]
+    pub fn is_seeded(&self) -> bool {
+        self.seeded
+    }
+
+    /// Mix `entropy` into the seed, `seed = SHA256(seed || entropy)`, and restart the generator
+    pub fn reseed(&mut self, entropy: &[u8]) {
+        let mut hasher = Sha256Hash::new();
+        hasher.update(self.seed);
+        hasher.update(entropy);
+        self.seed.copy_from_slice(hasher.finalize().as_ref());
+        self.stream = 0;
+        self.word_pos = 0;
+        self.seeded = true;
+    }
+
+    /// Run `f` with the generator, persisting its advanced position
+    pub fn with_rng<F, R>(&mut self, f: F) -> Result<R, RngError>
+    where
+        F: FnOnce(&mut CryptoHashRng) -> R,
+    {
+        if !self.seeded {
+            return Err(RngError::NotSeeded);
+        }
+        let mut rngcore = CryptoHashRng::from_seed(self.seed);
+        rngcore.set_stream(self.stream);
+        rngcore.set_word_pos(self.word_pos);
+        let result = f(&mut rngcore);
+        self.word_pos = rngcore.get_word_pos();
+        Ok(result)
+    }
+
+    /// Serialize the state
+    pub fn to_bytes(&self) -> [u8; RNG_STATE_SIZE] {
+        let mut bytes = [0u8; RNG_STATE_SIZE];
+        bytes[0] = self.seeded as u8;
+        bytes[1..33].copy_from_slice(&self.seed);
+        bytes[33..41].copy_from_slice(&self.stream.to_be_bytes());
+        bytes[41..].copy_from_slice(&self.word_pos.to_be_bytes());
+        bytes
+    }
+
+    /// Deserialize a state produced by [`RngState::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RngError> {
+        if bytes.len() != RNG_STATE_SIZE {
+            return Err(RngError::InvalidState);
+        }
+        let mut state = Self { seeded: bytes[0] != 0, ..Self::default() };
+        state.seed.copy_from_slice(&bytes[1..33]);
+        state.stream = u64::from_be_bytes(bytes[33..41].try_into().map_err(|_| RngError::InvalidState)?);
+        state.word_pos = u128::from_be_bytes(bytes[41..].try_into().map_err(|_| RngError::InvalidState)?);
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stand-in for the management canister `raw_rand`
+    const STUB_ENTROPY: [u8; 32] = [42u8; 32];
+
+    fn next(state: &mut RngState) -> u64 {
+        state.with_rng(|rngcore| rngcore.next_u64()).unwrap()
+    }
+
+    #[test]
+    fn test_unseeded_state_is_refused() {
+        let mut state = RngState::default();
+        assert_eq!(state.with_rng(|rngcore| rngcore.next_u32()), Err(RngError::NotSeeded));
+    }
+
+    #[test]
+    fn test_state_advances_and_persists() {
+        let mut state = RngState::default();
+        state.reseed(&STUB_ENTROPY);
+
+        let mut reference = state.clone();
+        let expected: Vec<u64> = reference.with_rng(|rngcore| (0..3).map(|_| rngcore.next_u64()).collect()).unwrap();
+
+        let first = next(&mut state);
+        let mut restored = RngState::from_bytes(&state.to_bytes()).unwrap();
+        let second = next(&mut restored);
+        let third = next(&mut restored);
+
+        assert_eq!(vec![first, second, third], expected);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_reseed_mixes_previous_seed() {
+        let mut state = RngState::default();
+        state.reseed(&STUB_ENTROPY);
+        let mut other = RngState::default();
+        other.reseed(&[7u8; 32]);
+        let before = next(&mut state.clone());
+
+        state.reseed(&STUB_ENTROPY);
+        assert_ne!(next(&mut state.clone()), before);
+
+        other.reseed(&STUB_ENTROPY);
+        assert_ne!(next(&mut other), next(&mut state));
+        assert!(RngState::from_bytes(&[0u8; 3]).is_err());
+    }
+}
@@ -88,6 +88,10 @@ impl AsymmetricKeyOps for AssymetricSecp256k1
     // Instance keypair
     fn new_public_key_from_bytes(&self, key_bytes: &[u8]) -> Result<Self::PublicKey, AsymmetricKeyError>
     {
+        // `from_bytes` panics on a slice that is not 32 bytes long
+        if key_bytes.len() != 32 {
+            return Err(AsymmetricKeyError::InvalidPublicKey);
+        }
         let rk = Self::PublicKey::from_bytes(key_bytes);
         match rk {
             Ok(val) => Ok(val),
@@ -1,5 +1,7 @@
 use core::{str::FromStr};
 use core::fmt;
+use candid::types::{Serializer as CandidSerializer, Type as CandidTypeDef};
+use candid::CandidType;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::hash::{DefaultHasher, Hash, Hasher};
 use core::convert::TryFrom;
@@ -130,6 +132,9 @@ pub struct NostrSecretKey(pub <AsymmetricKeyImpl as AsymmetricKeyOps>::SecretKey
 #[derive(Debug, Clone)]
 pub struct NostrSignature(pub <AsymmetricKeyImpl as AsymmetricKeyOps>::Signature);
 
+/// Keypair used to sign events
+pub type NostrSigningKey = <AsymmetricKeyImpl as AsymmetricKeyOps>::SigningKey;
+
 
 macro_rules! nostr_key_ser {
     ($type:ident, $parse_fn:ident, $hexstr_fn:ident, $frombytes_fn:ident, $tobytes_fn:ident) => {
@@ -154,7 +159,7 @@ macro_rules! nostr_key_ser {
         
         impl fmt::Display for $type {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                write!(f, "{}", self.to_string())
+                write!(f, "{}", String::from(self))
             }
         }
 
@@ -252,6 +257,29 @@ nostr_key_ser!(NostrSecretKey,parse_secret_key, secret_key_to_hex, new_secret_ke
 
 nostr_key_ser!(NostrSignature,parse_signature, signature_to_hex, new_signature_from_bytes, signature_to_bytes);
 
+/// Candid `text` holding the lowercase hex, as in the JSON encoding
+macro_rules! nostr_key_candid {
+    ($type:ident) => {
+
+        impl CandidType for $type {
+            fn _ty() -> CandidTypeDef {
+                String::ty()
+            }
+
+            fn idl_serialize<S>(&self, serializer: S) -> Result<(), S::Error>
+            where
+                S: CandidSerializer,
+            {
+                serializer.serialize_text(String::from(self).as_str())
+            }
+        }
+    };
+}
+
+nostr_key_candid!(NostrPubKey);
+
+nostr_key_candid!(NostrSignature);
+
 
 use crate::util::nostrbech32_params::{FromBech32, ToBech32};
 use bech32::{Bech32};
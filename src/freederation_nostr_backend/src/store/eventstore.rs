@@ -0,0 +1,1245 @@
+//! Event store
+//!
+//! Events keyed by [`EventId`] with secondary indexes on author, kind, `created_at`
+//! and single-letter tags, all kept in stable memory.
+//!
+//! Replaceable and parameterized replaceable events keep only the latest event of their
+//! [`Coordinate`], ephemeral events are never stored.
+//!
+//! Deletion requests remove the referenced events of the same author and leave a [`Tombstone`]
+//! that rejects their re-publication.
+//!
+//! Expired events are rejected, hidden from queries and removed by [`EventStore::purge_expired`].
+//! The current time comes from the [`TimeSupplier`] of the store.
+//!
+//! [`EventStore::count`] answers from the index keys when they cover the filter, see [`EventCount`].
+//!
+//! Filters with NIP50 search terms are answered from the [`SearchIndex`], best match first.
+//! While the index is rebuilt after a version change they are answered by a scan, newest first.
+
+use core::cmp::Ordering;
+use core::str::FromStr;
+use std::collections::BTreeSet;
+use std::ops::{Bound, RangeInclusive};
+
+use candid::{CandidType, Deserialize};
+
+use hex_conservative::FromHex;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{Memory, StableBTreeMap};
+
+use crate::http::nip05::Nip05Identifier;
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_id::{EventId, EVENT_ID_SIZE};
+use crate::nostr::filter::{is_hex_prefix, Filter};
+use crate::nostr::event_kind::Kind;
+use crate::nostr::metadata::Metadata;
+use crate::nostr::search::SearchQuery;
+use crate::nostr::tag::coordinate::{Coordinate, EventIdOrCoordinate};
+use crate::nostr::tag::single_letter_tag::SingleLetterTag;
+use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, DataField, NostrPubKey};
+use crate::store::memory::{
+    AUTHOR_INDEX_MEMORY_ID, COORDINATE_INDEX_MEMORY_ID, CREATED_AT_INDEX_MEMORY_ID, DELETED_COORDINATES_MEMORY_ID,
+    DELETED_EVENTS_MEMORY_ID, EVENTS_MEMORY_ID, EXPIRATION_INDEX_MEMORY_ID, KIND_INDEX_MEMORY_ID, SEARCH_POSTINGS_MEMORY_ID,
+    SEARCH_TERMS_MEMORY_ID, SEARCH_VERSION_MEMORY_ID, TAG_INDEX_MEMORY_ID, TAG_KIND_INDEX_MEMORY_ID,
+};
+use crate::store::search_index::{SearchHits, SearchIndex};
+use crate::store::storable::{IndexEntry, IndexKey, StoredEvent, Tombstone};
+use crate::store::store_error::StoreError;
+use crate::util::jsonutil::JsonUtil;
+use crate::util::time::{TimeSupplier, Timestamp};
+
+/// Upper bound of events returned for a single [`Filter`]
+pub const MAX_QUERY_LIMIT: usize = 500;
+
+/// Events loaded by [`EventStore::count`] before the rest is extrapolated
+pub const MAX_COUNT_LOADS: usize = 1_000;
+
+/// Index keys walked by [`EventStore::count`] before it stops
+pub const MAX_COUNT_KEYS: usize = 100_000;
+
+/// Offset of the kind in the keys of the tag and kind index, after the letter and the value hash
+const TAG_KIND_OFFSET: usize = 1 + 32;
+
+type Index<M> = StableBTreeMap<IndexKey, IndexEntry, M>;
+
+/// Outcome of [`EventStore::publish`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishStatus {
+    /// Stored
+    Stored,
+    /// Stored, superseding the event with this id
+    Replaced(EventId),
+    /// Accepted but not stored
+    Ephemeral,
+}
+
+/// Options of [`EventStore::query_with_options`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryOptions {
+    /// Return deletion requests even when the filter does not ask for their kind
+    pub include_deletions: bool,
+}
+
+/// Outcome of [`EventStore::count`]
+///
+/// The count is exact when the filters are covered by an index: `kinds` only, or a single
+/// tag with optional `kinds`, besides `since` and `until`. Other filters load the events,
+/// past [`MAX_COUNT_LOADS`] the remaining keys are scaled by the share of matching events.
+/// Past [`MAX_COUNT_KEYS`] the count stops. Both cases are `approximate`.
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventCount {
+    pub count: u64,
+    pub approximate: bool,
+}
+
+/// Running count of [`EventStore::count`]
+#[derive(Default)]
+struct Tally {
+    /// Matching ids, so an event matching several filters is counted once
+    ids: BTreeSet<EventId>,
+    /// Estimate of the matching events not walked
+    extrapolated: u64,
+    walked: usize,
+    approximate: bool,
+}
+
+impl Tally {
+    /// Count one more key, `false` once [`MAX_COUNT_KEYS`] is reached
+    fn walk(&mut self) -> bool {
+        if self.walked >= MAX_COUNT_KEYS {
+            self.approximate = true;
+            return false;
+        }
+        self.walked += 1;
+        true
+    }
+}
+
+/// Persistent event store
+pub struct EventStore<M: Memory, T: TimeSupplier> {
+    events: StableBTreeMap<IndexKey, StoredEvent, M>,
+    by_author: Index<M>,
+    by_kind: Index<M>,
+    by_created_at: Index<M>,
+    by_tag: Index<M>,
+    /// Tag then kind, for the counts
+    by_tag_kind: Index<M>,
+    /// Coordinate to the id of its latest event
+    by_coordinate: StableBTreeMap<IndexKey, IndexKey, M>,
+    deleted_events: StableBTreeMap<IndexKey, Tombstone, M>,
+    deleted_coordinates: StableBTreeMap<IndexKey, Tombstone, M>,
+    /// Expiration then id
+    by_expiration: Index<M>,
+    search: SearchIndex<M>,
+    /// Ids removed since the last [`EventStore::take_removed`], kept on the heap
+    removed: Vec<EventId>,
+    time_supplier: T,
+}
+
+impl<M: Memory, T: TimeSupplier> EventStore<M, T> {
+    /// Open the store, `memory` provides the virtual memory of each [`MemoryId`]
+    ///
+    /// Existing content of the memories is kept, so the same call restores the store after an upgrade.
+    /// A search index predating its current version is cleared, it is filled again by
+    /// [`EventStore::reindex_search_after`].
+    pub fn init<F>(mut memory: F, time_supplier: T) -> Self
+    where
+        F: FnMut(MemoryId) -> M,
+    {
+        let mut store = Self {
+            events: StableBTreeMap::init(memory(EVENTS_MEMORY_ID)),
+            by_author: StableBTreeMap::init(memory(AUTHOR_INDEX_MEMORY_ID)),
+            by_kind: StableBTreeMap::init(memory(KIND_INDEX_MEMORY_ID)),
+            by_created_at: StableBTreeMap::init(memory(CREATED_AT_INDEX_MEMORY_ID)),
+            by_tag: StableBTreeMap::init(memory(TAG_INDEX_MEMORY_ID)),
+            by_tag_kind: StableBTreeMap::init(memory(TAG_KIND_INDEX_MEMORY_ID)),
+            by_coordinate: StableBTreeMap::init(memory(COORDINATE_INDEX_MEMORY_ID)),
+            deleted_events: StableBTreeMap::init(memory(DELETED_EVENTS_MEMORY_ID)),
+            deleted_coordinates: StableBTreeMap::init(memory(DELETED_COORDINATES_MEMORY_ID)),
+            by_expiration: StableBTreeMap::init(memory(EXPIRATION_INDEX_MEMORY_ID)),
+            search: SearchIndex::init(
+                memory(SEARCH_POSTINGS_MEMORY_ID),
+                memory(SEARCH_TERMS_MEMORY_ID),
+                memory(SEARCH_VERSION_MEMORY_ID),
+            ),
+            removed: Vec::new(),
+            time_supplier,
+        };
+        if store.search.is_outdated() {
+            store.search.clear();
+            if store.events.is_empty() {
+                store.search.set_current();
+            }
+        }
+        store
+    }
+
+    /// Check if the search index is behind its current version, searches are then answered by a scan
+    #[inline]
+    pub fn is_search_outdated(&self) -> bool {
+        self.search.is_outdated()
+    }
+
+    /// Index the search terms of up to `max` events following `after`, every event from the first when `None`
+    ///
+    /// Returns the last indexed id while events remain, the index is current once `None` is returned.
+    /// Events published in between are indexed as they come, indexing them twice is harmless.
+    pub fn reindex_search_after(&mut self, after: Option<&EventId>, max: usize) -> Option<EventId> {
+        let events: Vec<EventData> = self.iter_after(after).take(max).collect();
+        for event in events.iter() {
+            self.search.insert(event);
+        }
+        match events.last() {
+            Some(last) if events.len() == max => Some(last.id),
+            _ => {
+                self.search.set_current();
+                None
+            }
+        }
+    }
+
+    /// Current time of the store [`TimeSupplier`]
+    #[inline]
+    pub fn now(&self) -> Timestamp {
+        Timestamp::now_with_supplier(&self.time_supplier)
+    }
+
+    /// Number of stored events
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.events.len()
+    }
+
+    /// Check if the store is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Check if an event is stored
+    #[inline]
+    pub fn contains(&self, id: &EventId) -> bool {
+        self.events.contains_key(&IndexKey::from_id(id))
+    }
+
+    /// Get an event by id
+    #[inline]
+    pub fn get(&self, id: &EventId) -> Option<EventData> {
+        self.events.get(&IndexKey::from_id(id)).map(|stored| stored.0)
+    }
+
+    /// Stored events following `after`, every event when `None`, by id
+    pub fn iter_after(&self, after: Option<&EventId>) -> impl Iterator<Item = EventData> + '_ {
+        let start: Bound<IndexKey> = after.map_or(Bound::Unbounded, |id| Bound::Excluded(IndexKey::from_id(id)));
+        self.events.range((start, Bound::Unbounded)).map(|(_, stored)| stored.0)
+    }
+
+    /// Ids of the events removed since the last call
+    ///
+    /// Replacements, deletion requests and purges remove events as a side effect,
+    /// state derived from the stored events follows them with this journal.
+    pub fn take_removed(&mut self) -> Vec<EventId> {
+        core::mem::take(&mut self.removed)
+    }
+
+    /// Latest event of a replaceable or parameterized replaceable [`Coordinate`]
+    pub fn get_by_coordinate(&self, coordinate: &Coordinate) -> Option<EventData> {
+        self.by_coordinate
+            .get(&coordinate_key(coordinate))
+            .and_then(|key| key.event_id())
+            .and_then(|id| self.get(&id))
+    }
+
+    /// Check if a deletion request of `author` targeted the event id
+    #[inline]
+    pub fn is_deleted(&self, id: &EventId, author: &NostrPubKey) -> bool {
+        self.tombstone(id, author).is_some()
+    }
+
+    /// Tombstone left on the event id by a deletion request of `author`
+    ///
+    /// Tombstones are keyed by id and author, so the requests of others cannot replace it.
+    fn tombstone(&self, id: &EventId, author: &NostrPubKey) -> Option<Tombstone> {
+        let ecda = AsymmetricKeyImpl();
+        let author: DataField = ecda.public_key_to_bytes(&author.0);
+        self.deleted_events.get(&tombstone_key(id, &author))
+    }
+
+    /// Verify and store an event
+    ///
+    /// A replaceable event only replaces an older event of the same [`Coordinate`],
+    /// on equal `created_at` the lowest id is retained.
+    /// Deletion requests are stored after removing and tombstoning their targets.
+    pub fn publish(&mut self, event: EventData) -> Result<PublishStatus, StoreError> {
+        event.verify()?;
+        if let Some(expiration) = event.expiration().filter(|expiration| *expiration <= self.now()) {
+            return Err(StoreError::Expired(expiration));
+        }
+        if event.kind.is_ephemeral() {
+            return Ok(PublishStatus::Ephemeral);
+        }
+        if self.contains(&event.id) {
+            return Err(StoreError::Duplicate(event.id));
+        }
+        self.check_tombstones(&event)?;
+
+        let current: Option<EventData> = event.coordinate().and_then(|coordinate| self.get_by_coordinate(&coordinate));
+        let status = match current {
+            Some(current) if !supersedes(&event, &current) => return Err(StoreError::Superseded(current.id)),
+            Some(current) => {
+                self.remove(&current.id);
+                PublishStatus::Replaced(current.id)
+            }
+            None => PublishStatus::Stored,
+        };
+        if event.kind == Kind::EventDeletion {
+            self.apply_deletion(&event);
+        }
+        self.insert(event);
+        Ok(status)
+    }
+
+    fn check_tombstones(&self, event: &EventData) -> Result<(), StoreError> {
+        if let Some(tombstone) = self.tombstone(&event.id, &event.pubkey) {
+            return Err(StoreError::Deleted(tombstone.deletion));
+        }
+        if let Some(coordinate) = event.coordinate() {
+            if let Some(tombstone) = self.deleted_coordinates.get(&coordinate_key(&coordinate)) {
+                if event.created_at <= tombstone.deleted_at {
+                    return Err(StoreError::Deleted(tombstone.deletion));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the `e` and `a` targets of a deletion request and tombstone them
+    ///
+    /// Only targets authored by the requester are affected. Coordinates are deleted up to the
+    /// `created_at` of the request, deletion requests themselves cannot be deleted.
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/09.md>
+    fn apply_deletion(&mut self, deletion: &EventData) {
+        let ecda = AsymmetricKeyImpl();
+        let tombstone = Tombstone {
+            author: ecda.public_key_to_bytes(&deletion.pubkey.0),
+            deletion: deletion.id,
+            deleted_at: deletion.created_at,
+        };
+
+        for target in deletion_targets(deletion) {
+            match target {
+                EventIdOrCoordinate::Id(id) => {
+                    if let Some(event) = self.get(&id) {
+                        if event.pubkey != deletion.pubkey || event.kind == Kind::EventDeletion {
+                            continue;
+                        }
+                        self.remove(&id);
+                    }
+                    let key = tombstone_key(&id, &tombstone.author);
+                    if !self.deleted_events.contains_key(&key) {
+                        self.deleted_events.insert(key, tombstone.clone());
+                    }
+                }
+                EventIdOrCoordinate::Coordinate(coordinate) => {
+                    if coordinate.public_key != deletion.pubkey {
+                        continue;
+                    }
+                    if let Some(event) = self.get_by_coordinate(&coordinate) {
+                        if event.created_at <= deletion.created_at {
+                            self.remove(&event.id);
+                        }
+                    }
+                    let key = coordinate_key(&coordinate);
+                    let newer = self.deleted_coordinates.get(&key).is_some_and(|current| current.deleted_at >= deletion.created_at);
+                    if !newer {
+                        self.deleted_coordinates.insert(key, tombstone.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove an event and its index entries
+    pub fn remove(&mut self, id: &EventId) -> Option<EventData> {
+        let event = self.events.remove(&IndexKey::from_id(id))?.0;
+        self.by_author.remove(&author_key(&event));
+        self.by_kind.remove(&kind_key(&event));
+        self.by_created_at.remove(&created_at_key(&event));
+        for key in tag_keys(&event) {
+            self.by_tag.remove(&key);
+        }
+        for key in tag_kind_keys(&event) {
+            self.by_tag_kind.remove(&key);
+        }
+        self.search.remove(&event);
+        if let Some(coordinate) = event.coordinate() {
+            let key = coordinate_key(&coordinate);
+            if self.by_coordinate.get(&key).and_then(|latest| latest.event_id()) == Some(event.id) {
+                self.by_coordinate.remove(&key);
+            }
+        }
+        if let Some(key) = expiration_key(&event) {
+            self.by_expiration.remove(&key);
+        }
+        self.removed.push(event.id);
+        Some(event)
+    }
+
+    fn insert(&mut self, event: EventData) {
+        self.by_author.insert(author_key(&event), IndexEntry);
+        self.by_kind.insert(kind_key(&event), IndexEntry);
+        self.by_created_at.insert(created_at_key(&event), IndexEntry);
+        for key in tag_keys(&event) {
+            self.by_tag.insert(key, IndexEntry);
+        }
+        for key in tag_kind_keys(&event) {
+            self.by_tag_kind.insert(key, IndexEntry);
+        }
+        self.search.insert(&event);
+        if let Some(coordinate) = event.coordinate() {
+            self.by_coordinate.insert(coordinate_key(&coordinate), IndexKey::from_id(&event.id));
+        }
+        if let Some(key) = expiration_key(&event) {
+            self.by_expiration.insert(key, IndexEntry);
+        }
+        self.events.insert(IndexKey::from_id(&event.id), StoredEvent(event));
+    }
+
+    /// Remove up to `max` expired events, returning how many were removed
+    pub fn purge_expired(&mut self, max: usize) -> u64 {
+        let end = IndexKey::new().with_u64(self.now().as_u64()).with_bytes(&[u8::MAX; EVENT_ID_SIZE]);
+        let expired: Vec<EventId> = self
+            .by_expiration
+            .range(..=end)
+            .take(max)
+            .filter_map(|(key, _)| key.event_id())
+            .collect();
+        for id in expired.iter() {
+            self.remove(id);
+        }
+        expired.len() as u64
+    }
+
+    /// Events matching any of `filters` with default [`QueryOptions`], see [`EventStore::query_with_options`]
+    ///
+    /// The `limit` of each filter is capped at [`MAX_QUERY_LIMIT`].
+    #[inline]
+    pub fn query(&self, filters: &[Filter]) -> Vec<EventData> {
+        self.query_with_options(filters, QueryOptions::default())
+    }
+
+    /// Events matching any of `filters`, newest first
+    ///
+    /// Events of filters with search terms come first, best match first.
+    /// Deletion requests are only returned when a filter lists [`Kind::EventDeletion`]
+    /// or [`QueryOptions::include_deletions`] is set. Expired events are never returned.
+    pub fn query_with_options(&self, filters: &[Filter], options: QueryOptions) -> Vec<EventData> {
+        let (searches, others): (Vec<&Filter>, Vec<&Filter>) = filters.iter().partition(|filter| is_search(filter));
+        let mut events: Vec<EventData> = others
+            .into_iter()
+            .flat_map(|filter| self.query_filter(filter, options))
+            .collect();
+        events.sort_by(newest_first);
+        events.dedup_by(|a, b| a.id == b.id);
+        if searches.is_empty() {
+            return events;
+        }
+
+        let mut ranked: Vec<EventData> = searches
+            .into_iter()
+            .flat_map(|filter| self.query_filter(filter, options))
+            .collect();
+        ranked.extend(events);
+        let mut seen: BTreeSet<EventId> = BTreeSet::new();
+        ranked.retain(|event| seen.insert(event.id));
+        ranked
+    }
+
+    fn query_filter(&self, filter: &Filter, options: QueryOptions) -> Vec<EventData> {
+        let limit = filter.limit.unwrap_or(MAX_QUERY_LIMIT).min(MAX_QUERY_LIMIT);
+        let mut events: Vec<EventData> = Vec::new();
+        if limit == 0 {
+            return events;
+        }
+
+        let now: Timestamp = self.now();
+        let accept = self.acceptor(filter, options, now);
+
+        if let Some(SearchHits { hits, .. }) = self.search_hits(filter) {
+            return hits
+                .iter()
+                .filter_map(|hit| self.get(&hit.id))
+                .filter(|event| accept(event))
+                .take(limit)
+                .collect();
+        }
+        if let Some(ids) = &filter.ids {
+            for prefix in ids {
+                self.scan_ids(prefix, &accept, limit, &mut events);
+            }
+        } else {
+            let (index, prefixes) = self.index_prefixes(filter);
+            for prefix in prefixes {
+                self.scan_index(index, prefix, filter, &accept, limit, &mut events);
+            }
+        }
+
+        events.sort_by(newest_first);
+        events.dedup_by(|a, b| a.id == b.id);
+        events.truncate(limit);
+        events
+    }
+
+    /// Hits of the search terms of `filter`, `None` without terms or while the index is outdated
+    fn search_hits(&self, filter: &Filter) -> Option<SearchHits> {
+        if self.search.is_outdated() {
+            return None;
+        }
+        let query: SearchQuery = filter.search_query().filter(SearchQuery::has_terms)?;
+        Some(self.search.search(&query.terms, filter.since, filter.until, self.len()))
+    }
+
+    /// Index walked for a filter without ids, with the key prefixes to walk
+    fn index_prefixes(&self, filter: &Filter) -> (&Index<M>, Vec<IndexKey>) {
+        if let Some(authors) = full_authors(filter) {
+            let prefixes = authors.iter().map(|author| IndexKey::new().with_bytes(author)).collect();
+            (&self.by_author, prefixes)
+        } else if let Some((tag, values)) = filter.generic_tags.iter().next() {
+            let prefixes = values.iter().map(|value| tag_prefix(tag, value)).collect();
+            (&self.by_tag, prefixes)
+        } else if let Some(kinds) = &filter.kinds {
+            let prefixes = kinds.iter().map(|kind| IndexKey::new().with_u16(kind.as_u16())).collect();
+            (&self.by_kind, prefixes)
+        } else {
+            (&self.by_created_at, vec![IndexKey::new()])
+        }
+    }
+
+    /// Number of stored events matching any of `filters`, see [`EventCount`]
+    ///
+    /// Deletion requests and expired events are left out as in [`EventStore::query`], `limit` is ignored.
+    pub fn count(&self, filters: &[Filter]) -> EventCount {
+        let now: Timestamp = self.now();
+        let end = IndexKey::new().with_u64(now.as_u64()).with_bytes(&[u8::MAX; EVENT_ID_SIZE]);
+        let expired: BTreeSet<EventId> = self.by_expiration.range(..=end).filter_map(|(key, _)| key.event_id()).collect();
+
+        let mut tally = Tally::default();
+        for filter in filters {
+            if !self.count_keys(filter, &expired, &mut tally) {
+                self.count_events(filter, now, &mut tally);
+            }
+        }
+        EventCount {
+            count: tally.ids.len() as u64 + tally.extrapolated,
+            approximate: tally.approximate,
+        }
+    }
+
+    /// Count from the index keys alone, `false` when no index covers `filter`
+    fn count_keys(&self, filter: &Filter, expired: &BTreeSet<EventId>, tally: &mut Tally) -> bool {
+        if filter.ids.is_some() || filter.authors.is_some() || filter.search.is_some() {
+            return false;
+        }
+        let mut ranges: Vec<(&Index<M>, RangeInclusive<IndexKey>)> = Vec::new();
+        match (filter.generic_tags.iter().next(), &filter.kinds) {
+            (None, Some(kinds)) => {
+                for kind in kinds {
+                    let prefix = IndexKey::new().with_u16(kind.as_u16());
+                    ranges.push((&self.by_kind, IndexKey::time_range(prefix, filter.since, filter.until)));
+                }
+            }
+            (Some((tag, values)), kinds) if filter.generic_tags.len() == 1 => {
+                for value in values {
+                    match kinds {
+                        Some(kinds) => {
+                            for kind in kinds {
+                                let prefix = tag_prefix(tag, value).with_u16(kind.as_u16());
+                                ranges.push((&self.by_tag_kind, IndexKey::time_range(prefix, filter.since, filter.until)));
+                            }
+                        }
+                        None => ranges.push((&self.by_tag_kind, IndexKey::prefix_range(tag_prefix(tag, value)))),
+                    }
+                }
+            }
+            _ => return false,
+        }
+
+        // Without kinds the range spans every kind and time, both are read from the key
+        let check_key = |key: &IndexKey| {
+            filter.kinds.is_some()
+                || (key.read_u16(TAG_KIND_OFFSET) != Some(Kind::EventDeletion.as_u16())
+                    && key.read_created_at(TAG_KIND_OFFSET + 2).is_some_and(|created_at| {
+                        filter.since.is_none_or(|since| created_at >= since)
+                            && filter.until.is_none_or(|until| created_at <= until)
+                    }))
+        };
+        for (index, range) in ranges {
+            for (key, _) in index.range(range) {
+                if !tally.walk() {
+                    return true;
+                }
+                if let Some(id) = key.event_id().filter(|id| check_key(&key) && !expired.contains(id)) {
+                    tally.ids.insert(id);
+                }
+            }
+        }
+        true
+    }
+
+    /// Count by loading the events of the ids or of an index, extrapolating past [`MAX_COUNT_LOADS`]
+    fn count_events(&self, filter: &Filter, now: Timestamp, tally: &mut Tally) {
+        let accept = self.acceptor(filter, QueryOptions::default(), now);
+        if let Some(SearchHits { hits, complete }) = self.search_hits(filter) {
+            let loaded: usize = hits.len().min(MAX_COUNT_LOADS);
+            let mut matched: u64 = 0;
+            for event in hits[..loaded].iter().filter_map(|hit| self.get(&hit.id)).filter(|event| accept(event)) {
+                tally.ids.insert(event.id);
+                matched += 1;
+            }
+            if loaded < hits.len() {
+                tally.extrapolated += (hits.len() - loaded) as u64 * matched / loaded as u64;
+            }
+            tally.approximate |= loaded < hits.len() || !complete;
+            return;
+        }
+
+        let keys: Box<dyn Iterator<Item = IndexKey> + '_> = match &filter.ids {
+            Some(ids) => Box::new(ids.iter().flat_map(|prefix| self.id_keys(prefix))),
+            None => {
+                let (index, prefixes) = self.index_prefixes(filter);
+                Box::new(prefixes.into_iter().flat_map(|prefix| index.keys_range(IndexKey::time_range(prefix, filter.since, filter.until))))
+            }
+        };
+        let (mut loaded, mut matched, mut remaining): (u64, u64, u64) = (0, 0, 0);
+        for key in keys {
+            if !tally.walk() {
+                break;
+            }
+            if loaded >= MAX_COUNT_LOADS as u64 {
+                remaining += 1;
+                continue;
+            }
+            loaded += 1;
+            if let Some(event) = key.event_id().and_then(|id| self.get(&id)).filter(|event| accept(event)) {
+                tally.ids.insert(event.id);
+                matched += 1;
+            }
+        }
+        if remaining > 0 {
+            tally.extrapolated += remaining * matched / loaded;
+            tally.approximate = true;
+        }
+    }
+
+    /// Check if an event passes `filter`, deletion requests and expired events are hidden
+    fn acceptor<'a>(&'a self, filter: &'a Filter, options: QueryOptions, now: Timestamp) -> impl Fn(&EventData) -> bool + 'a {
+        let show_deletions: bool = options.include_deletions
+            || filter.kinds.as_ref().is_some_and(|kinds| kinds.contains(&Kind::EventDeletion));
+        let domain: Option<String> = filter.search_query().and_then(|query| query.domain().map(String::from));
+        move |event: &EventData| {
+            (show_deletions || event.kind != Kind::EventDeletion)
+                && !event.is_expired_at(&now)
+                && filter.match_event(event)
+                && domain.as_deref().is_none_or(|domain| self.has_domain(&event.pubkey, domain))
+        }
+    }
+
+    /// Check if the latest stored metadata of `author` has a NIP05 identifier on `domain`
+    ///
+    /// The identifier is taken as is, a canister query cannot fetch `nostr.json` to verify it.
+    fn has_domain(&self, author: &NostrPubKey, domain: &str) -> bool {
+        self.get_by_coordinate(&Coordinate::new(Kind::Metadata, author.clone()))
+            .and_then(|metadata| Metadata::from_json(metadata.content).ok())
+            .and_then(|metadata| metadata.nip05)
+            .and_then(|nip05| Nip05Identifier::from_str(nip05.as_str()).ok())
+            .is_some_and(|identifier| identifier.domain.eq_ignore_ascii_case(domain))
+    }
+
+    /// Walk an index newest first, collecting at most `limit` accepted events
+    fn scan_index<F>(&self, index: &Index<M>, prefix: IndexKey, filter: &Filter, accept: &F, limit: usize, events: &mut Vec<EventData>)
+    where
+        F: Fn(&EventData) -> bool,
+    {
+        let mut found: usize = 0;
+        for (key, _) in index.range(IndexKey::time_range(prefix, filter.since, filter.until)) {
+            let event = match key.event_id().and_then(|id| self.get(&id)) {
+                Some(event) => event,
+                None => continue,
+            };
+            if accept(&event) {
+                events.push(event);
+                found += 1;
+                if found >= limit {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Walk the events whose id starts with the hex `prefix`, collecting at most `limit` accepted events
+    ///
+    /// The walk goes in id order and stops at `limit`. Prefixes rejected by [`is_hex_prefix`] match nothing.
+    fn scan_ids<F>(&self, prefix: &str, accept: &F, limit: usize, events: &mut Vec<EventData>)
+    where
+        F: Fn(&EventData) -> bool,
+    {
+        let Some(bytes) = id_prefix_bytes(prefix) else {
+            return;
+        };
+
+        let mut found: usize = 0;
+        for (key, stored) in self.events.range(IndexKey::new().with_bytes(&bytes)..) {
+            if found >= limit || !key.starts_with(&bytes) {
+                break;
+            }
+            if accept(&stored.0) {
+                events.push(stored.0);
+                found += 1;
+            }
+        }
+    }
+
+    /// Keys of the events whose id starts with the whole bytes of the hex `prefix`, in id order
+    fn id_keys(&self, prefix: &str) -> impl Iterator<Item = IndexKey> + '_ {
+        id_prefix_bytes(prefix).into_iter().flat_map(|bytes| {
+            let start = IndexKey::new().with_bytes(&bytes);
+            self.events.keys_range(start..).take_while(move |key| key.starts_with(&bytes))
+        })
+    }
+}
+
+/// Whole bytes of a hex id prefix, an odd last digit is left to the filter
+///
+/// `None` when [`is_hex_prefix`] rejects the prefix.
+fn id_prefix_bytes(prefix: &str) -> Option<Vec<u8>> {
+    if !is_hex_prefix(prefix) {
+        return None;
+    }
+    Vec::from_hex(&prefix[..prefix.len() - prefix.len() % 2]).ok()
+}
+
+/// Check if `candidate` replaces `current`
+///
+/// The newest event wins, on equal `created_at` the lowest one by [`EventData`] `Ord`, i.e. the lowest id.
+fn supersedes(candidate: &EventData, current: &EventData) -> bool {
+    match candidate.created_at.cmp(&current.created_at) {
+        Ordering::Greater => true,
+        Ordering::Less => false,
+        Ordering::Equal => candidate < current,
+    }
+}
+
+/// Check if the filter has search terms, its events are ranked
+fn is_search(filter: &Filter) -> bool {
+    filter.search_query().is_some_and(|query| query.has_terms())
+}
+
+/// Newest first, ties broken by lowest id
+fn newest_first(a: &EventData, b: &EventData) -> Ordering {
+    b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id))
+}
+
+/// `e` and `a` targets of a deletion request, malformed values are skipped
+fn deletion_targets(deletion: &EventData) -> Vec<EventIdOrCoordinate> {
+    deletion.tags.iter().filter_map(|tag| {
+        let value = tag.content()?;
+        match tag.kind_str()? {
+            "e" => EventId::from_hex(value).ok().map(EventIdOrCoordinate::from),
+            "a" => Coordinate::parse(value).ok().map(EventIdOrCoordinate::from),
+            _ => None,
+        }
+    }).collect()
+}
+
+/// Authors of the filter as raw public keys, if all of them are complete
+fn full_authors(filter: &Filter) -> Option<Vec<[u8; 32]>> {
+    let ecda = AsymmetricKeyImpl();
+    filter.authors.as_ref()?.iter().map(|author| {
+        if author.len() != 64 {
+            return None;
+        }
+        NostrPubKey::from_str(author).ok().map(|pubkey| ecda.public_key_to_bytes(&pubkey.0))
+    }).collect()
+}
+
+fn author_key(event: &EventData) -> IndexKey {
+    let ecda = AsymmetricKeyImpl();
+    IndexKey::new()
+        .with_bytes(&ecda.public_key_to_bytes(&event.pubkey.0))
+        .with_created_at(event.created_at)
+        .with_id(&event.id)
+}
+
+/// Kind and author, plus the hashed identifier for parameterized replaceable events
+fn coordinate_key(coordinate: &Coordinate) -> IndexKey {
+    let ecda = AsymmetricKeyImpl();
+    let key = IndexKey::new()
+        .with_u16(coordinate.kind.as_u16())
+        .with_bytes(&ecda.public_key_to_bytes(&coordinate.public_key.0));
+    if coordinate.kind.is_parameterized_replaceable() {
+        key.with_hash(coordinate.identifier.as_str())
+    } else {
+        key
+    }
+}
+
+/// Event id then the raw public key of the author of the deletion request
+fn tombstone_key(id: &EventId, author: &DataField) -> IndexKey {
+    IndexKey::from_id(id).with_bytes(author)
+}
+
+fn expiration_key(event: &EventData) -> Option<IndexKey> {
+    event.expiration().map(|expiration| IndexKey::new().with_u64(expiration.as_u64()).with_id(&event.id))
+}
+
+fn kind_key(event: &EventData) -> IndexKey {
+    IndexKey::new().with_u16(event.kind.as_u16()).with_created_at(event.created_at).with_id(&event.id)
+}
+
+fn created_at_key(event: &EventData) -> IndexKey {
+    IndexKey::new().with_created_at(event.created_at).with_id(&event.id)
+}
+
+/// Tag letter and hashed value, the prefix of the tag index keys
+fn tag_prefix(tag: &SingleLetterTag, value: &str) -> IndexKey {
+    IndexKey::new().with_bytes(&[tag.as_char() as u8]).with_hash(value)
+}
+
+/// Index keys of the single-letter tags, on the first tag value
+fn tag_keys(event: &EventData) -> BTreeSet<IndexKey> {
+    event.tags.iter().filter_map(|tag| {
+        let letter = SingleLetterTag::from_str(tag.kind_str()?).ok()?;
+        let value = tag.content()?;
+        Some(tag_prefix(&letter, value).with_created_at(event.created_at).with_id(&event.id))
+    }).collect()
+}
+
+/// Keys of the tag and kind index, [`tag_keys`] with the kind before `created_at`
+fn tag_kind_keys(event: &EventData) -> BTreeSet<IndexKey> {
+    event.tags.iter().filter_map(|tag| {
+        let letter = SingleLetterTag::from_str(tag.kind_str()?).ok()?;
+        let value = tag.content()?;
+        Some(
+            tag_prefix(&letter, value)
+                .with_u16(event.kind.as_u16())
+                .with_created_at(event.created_at)
+                .with_id(&event.id),
+        )
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::memory_manager::MemoryManager;
+    use ic_stable_structures::{DefaultMemoryImpl, StableCell};
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::rng::CryptoHashRng;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::nostr::event_kind::Kind;
+    use crate::nostr::tag::TagData;
+    use crate::signing::NostrSigningKey;
+    use crate::util::time::MockTimeSupplier;
+
+    fn signer(seed: u8) -> NostrSigningKey {
+        let ecda = AsymmetricKeyImpl();
+        ecda.generate_keypair(&mut CryptoHashRng::from_seed([seed; 32])).unwrap()
+    }
+
+    fn event(signer: &NostrSigningKey, builder: EventBuilder, created_at: u64) -> EventData {
+        builder
+            .custom_created_at(Timestamp::from(created_at))
+            .sign(signer, &mut CryptoHashRng::from_seed([1u8; 32]))
+            .unwrap()
+    }
+
+    fn open_with_clock(memory: &DefaultMemoryImpl, clock: MockTimeSupplier) -> EventStore<impl Memory, MockTimeSupplier> {
+        let memory_manager = MemoryManager::init(memory.clone());
+        EventStore::init(|id| memory_manager.get(id), clock)
+    }
+
+    fn open(memory: &DefaultMemoryImpl) -> EventStore<impl Memory, MockTimeSupplier> {
+        open_with_clock(memory, MockTimeSupplier::default())
+    }
+
+    #[test]
+    fn test_publish_and_get() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let note = event(&signer(1), EventBuilder::text_note("hello", []), 1_000);
+
+        store.publish(note.clone()).unwrap();
+        assert_eq!(store.get(&note.id), Some(note.clone()));
+        assert!(matches!(store.publish(note.clone()), Err(StoreError::Duplicate(_))));
+
+        let mut tampered = event(&signer(1), EventBuilder::text_note("other", []), 1_001);
+        tampered.content = String::from("tampered");
+        assert!(matches!(store.publish(tampered), Err(StoreError::InvalidEvent(_))));
+        assert_eq!(store.len(), 1);
+
+        assert_eq!(store.remove(&note.id), Some(note.clone()));
+        assert!(store.is_empty());
+        assert!(store.query(&[Filter::new().kind(Kind::TextNote)]).is_empty());
+    }
+
+    #[test]
+    fn test_query_indexes() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let alice = signer(1);
+        let bob = signer(2);
+
+        let first = event(&alice, EventBuilder::text_note("first", [TagData::custom("t", ["nostr"])]), 1_000);
+        let second = event(&alice, EventBuilder::text_note("second", []), 2_000);
+        let reaction = event(&bob, EventBuilder::reaction(&first, "+"), 3_000);
+        for e in [first.clone(), second.clone(), reaction.clone()] {
+            store.publish(e).unwrap();
+        }
+
+        let ids = |events: Vec<EventData>| events.into_iter().map(|e| e.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(store.query(&[Filter::new()])), vec![reaction.id, second.id, first.id]);
+        assert_eq!(ids(store.query(&[Filter::new().limit(2)])), vec![reaction.id, second.id]);
+        assert_eq!(ids(store.query(&[Filter::new().author(first.pubkey.clone())])), vec![second.id, first.id]);
+        assert_eq!(ids(store.query(&[Filter::new().author_prefix(&reaction.pubkey.to_string()[..10])])), vec![reaction.id]);
+        assert_eq!(ids(store.query(&[Filter::new().kind(Kind::Reaction)])), vec![reaction.id]);
+        assert_eq!(ids(store.query(&[Filter::new().hashtag("nostr")])), vec![first.id]);
+        assert_eq!(ids(store.query(&[Filter::new().event(first.id)])), vec![reaction.id]);
+        assert_eq!(ids(store.query(&[Filter::new().id_prefix(&first.id.to_hex()[..7])])), vec![first.id]);
+        assert!(store.query(&[Filter::new().id_prefix("aé"), Filter::new().id_prefix("a")]).is_empty());
+        assert_eq!(
+            ids(store.query(&[Filter::new().since(Timestamp::from(1_500)).until(Timestamp::from(2_500))])),
+            vec![second.id]
+        );
+        assert_eq!(
+            ids(store.query(&[Filter::new().kind(Kind::Reaction), Filter::new().id(first.id), Filter::new().id(first.id)])),
+            vec![reaction.id, first.id]
+        );
+    }
+
+    #[test]
+    fn test_replaceable_events() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let alice = signer(1);
+
+        let old = event(&alice, EventBuilder::new(Kind::Metadata, "{}", []), 1_000);
+        let new = event(&alice, EventBuilder::new(Kind::Metadata, r#"{"name":"alice"}"#, []), 2_000);
+        assert_eq!(store.publish(old.clone()).unwrap(), PublishStatus::Stored);
+        assert_eq!(store.publish(new.clone()).unwrap(), PublishStatus::Replaced(old.id));
+        assert!(matches!(store.publish(old.clone()), Err(StoreError::Superseded(id)) if id == new.id));
+        assert_eq!(store.get_by_coordinate(&new.coordinate().unwrap()), Some(new.clone()));
+        assert_eq!(store.query(&[Filter::new().kind(Kind::Metadata)]), vec![new.clone()]);
+
+        // Same created_at, the lowest id is retained whatever the arrival order
+        let tie = event(&alice, EventBuilder::new(Kind::Metadata, r#"{"name":"tie"}"#, []), 2_000);
+        let (lowest, highest) = if tie < new { (tie, new) } else { (new, tie) };
+        let mut other = open(&DefaultMemoryImpl::default());
+        other.publish(highest.clone()).unwrap();
+        assert_eq!(other.publish(lowest.clone()).unwrap(), PublishStatus::Replaced(highest.id));
+        assert!(matches!(other.publish(highest.clone()), Err(StoreError::Superseded(_))));
+        assert_eq!(other.len(), 1);
+    }
+
+    #[test]
+    fn test_parameterized_replaceable_and_ephemeral_events() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let alice = signer(1);
+        let article = |d: &str, content: &str, created_at: u64| {
+            event(&alice, EventBuilder::new(Kind::LongFormTextNote, content, [TagData::custom("d", [d])]), created_at)
+        };
+
+        let first = article("first", "v1", 1_000);
+        let second = article("second", "v1", 1_000);
+        let first_v2 = article("first", "v2", 2_000);
+        store.publish(first.clone()).unwrap();
+        store.publish(second.clone()).unwrap();
+        assert_eq!(store.publish(first_v2.clone()).unwrap(), PublishStatus::Replaced(first.id));
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&first.id).is_none());
+
+        let coordinate = Coordinate::new(Kind::LongFormTextNote, first.pubkey.clone()).identifier("first");
+        assert_eq!(store.get_by_coordinate(&coordinate), Some(first_v2));
+
+        let ephemeral = event(&alice, EventBuilder::new(Kind::from(20_001), "typing", []), 3_000);
+        assert_eq!(store.publish(ephemeral.clone()).unwrap(), PublishStatus::Ephemeral);
+        assert!(store.get(&ephemeral.id).is_none());
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_deletion_requests() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let alice = signer(1);
+        let bob = signer(2);
+
+        let note = event(&alice, EventBuilder::text_note("oops", []), 1_000);
+        let bob_note = event(&bob, EventBuilder::text_note("bob", []), 1_000);
+        let article = event(&alice, EventBuilder::new(Kind::LongFormTextNote, "v1", [TagData::custom("d", ["a"])]), 1_000);
+        for e in [note.clone(), bob_note.clone(), article.clone()] {
+            store.publish(e).unwrap();
+        }
+
+        let deletion = event(
+            &alice,
+            EventBuilder::delete(
+                [
+                    EventIdOrCoordinate::from(note.id),
+                    EventIdOrCoordinate::from(bob_note.id),
+                    EventIdOrCoordinate::from(article.coordinate().unwrap()),
+                ],
+                Some("cleanup"),
+            ),
+            2_000,
+        );
+        store.publish(deletion.clone()).unwrap();
+
+        assert!(store.get(&note.id).is_none());
+        assert!(store.get(&article.id).is_none());
+        assert_eq!(store.get(&bob_note.id), Some(bob_note.clone()));
+        assert!(store.is_deleted(&note.id, &note.pubkey));
+        assert!(!store.is_deleted(&bob_note.id, &bob_note.pubkey));
+        assert_eq!(store.take_removed(), vec![note.id, article.id]);
+        assert!(store.take_removed().is_empty());
+        assert_eq!(store.iter_after(None).map(|e| e.id).collect::<BTreeSet<_>>(), BTreeSet::from([bob_note.id, deletion.id]));
+
+        assert!(matches!(store.publish(note.clone()), Err(StoreError::Deleted(id)) if id == deletion.id));
+        assert!(matches!(store.publish(article.clone()), Err(StoreError::Deleted(_))));
+        let article_v2 = event(&alice, EventBuilder::new(Kind::LongFormTextNote, "v2", [TagData::custom("d", ["a"])]), 3_000);
+        assert_eq!(store.publish(article_v2).unwrap(), PublishStatus::Stored);
+
+        // A deletion request cannot be deleted
+        let undo = event(&alice, EventBuilder::delete([EventIdOrCoordinate::from(deletion.id)], None::<String>), 4_000);
+        store.publish(undo.clone()).unwrap();
+        assert!(store.get(&deletion.id).is_some());
+
+        let author = Filter::new().author(note.pubkey.clone());
+        assert!(store.query(std::slice::from_ref(&author)).iter().all(|e| e.kind != Kind::EventDeletion));
+        assert_eq!(store.query(&[author.clone().kind(Kind::EventDeletion)]).len(), 2);
+        let options = QueryOptions { include_deletions: true };
+        assert_eq!(store.query_with_options(&[author], options).len(), 3);
+    }
+
+    #[test]
+    fn test_deletion_before_target() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let alice = signer(1);
+        let note = event(&alice, EventBuilder::text_note("late", []), 1_000);
+        let deletion = event(&alice, EventBuilder::delete([EventIdOrCoordinate::from(note.id)], None::<String>), 2_000);
+
+        store.publish(deletion).unwrap();
+        assert!(matches!(store.publish(note), Err(StoreError::Deleted(_))));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_foreign_deletion_keeps_tombstone() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let (alice, mallory) = (signer(1), signer(3));
+        let note = event(&alice, EventBuilder::text_note("deleted", []), 1_000);
+        let later = event(&alice, EventBuilder::text_note("deleted later", []), 1_000);
+        store.publish(note.clone()).unwrap();
+
+        let deletion = event(&alice, EventBuilder::delete([EventIdOrCoordinate::from(note.id)], None::<String>), 2_000);
+        store.publish(deletion.clone()).unwrap();
+        // Deletion requests of others for the same ids, after and before the author's
+        let targets = [EventIdOrCoordinate::from(note.id), EventIdOrCoordinate::from(later.id)];
+        store.publish(event(&mallory, EventBuilder::delete(targets, None::<String>), 3_000)).unwrap();
+        let late_deletion = event(&alice, EventBuilder::delete([EventIdOrCoordinate::from(later.id)], None::<String>), 4_000);
+        store.publish(late_deletion.clone()).unwrap();
+
+        assert!(matches!(store.publish(note.clone()), Err(StoreError::Deleted(id)) if id == deletion.id));
+        assert!(matches!(store.publish(later.clone()), Err(StoreError::Deleted(id)) if id == late_deletion.id));
+        assert!(store.is_deleted(&note.id, &note.pubkey));
+        assert!(store.is_deleted(&later.id, &later.pubkey));
+    }
+
+    #[test]
+    fn test_expiration() {
+        let clock = MockTimeSupplier::new(Timestamp::from(1_000));
+        let mut store = open_with_clock(&DefaultMemoryImpl::default(), clock.clone());
+        let alice = signer(1);
+        let expiring = |content: &str, expiration: u64| {
+            event(&alice, EventBuilder::text_note(content, [TagData::custom("expiration", [expiration.to_string()])]), 900)
+        };
+
+        let expired = expiring("too late", 1_000);
+        assert!(matches!(store.publish(expired), Err(StoreError::Expired(at)) if at == Timestamp::from(1_000)));
+
+        let soon = expiring("soon", 2_000);
+        let later = expiring("later", 3_000);
+        let forever = event(&alice, EventBuilder::text_note("forever", []), 900);
+        for e in [soon.clone(), later.clone(), forever.clone()] {
+            store.publish(e).unwrap();
+        }
+        assert_eq!(store.purge_expired(10), 0);
+
+        clock.set(Timestamp::from(2_500));
+        assert_eq!(store.query(&[Filter::new()]).len(), 2);
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.purge_expired(10), 1);
+        assert!(store.get(&soon.id).is_none());
+        assert_eq!(store.take_removed(), vec![soon.id]);
+
+        clock.set(Timestamp::from(10_000));
+        assert_eq!(store.purge_expired(10), 1);
+        assert_eq!(store.query(&[Filter::new()]), vec![forever]);
+        assert_eq!(store.purge_expired(10), 0);
+    }
+
+    #[test]
+    fn test_survives_reinit() {
+        let memory = DefaultMemoryImpl::default();
+        let note = event(&signer(1), EventBuilder::text_note("persisted", [TagData::custom("t", ["ic"])]), 1_000);
+        {
+            let mut store = open(&memory);
+            store.publish(note.clone()).unwrap();
+        }
+
+        let store = open(&memory);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.query(&[Filter::new().hashtag("ic")]), vec![note]);
+    }
+
+    #[test]
+    fn test_count() {
+        let memory = DefaultMemoryImpl::default();
+        let clock = MockTimeSupplier::new(Timestamp::from(1_500));
+        let mut store = open_with_clock(&memory, clock.clone());
+        let (alice, bob) = (signer(1), signer(2));
+        let note = event(&alice, EventBuilder::text_note("note", []), 1_000);
+        let expiring = event(&bob, EventBuilder::text_note("soon", [TagData::custom("expiration", ["2000"])]), 1_000);
+        store.publish(note.clone()).unwrap();
+        store.publish(expiring).unwrap();
+        store.publish(event(&alice, EventBuilder::reaction(&note, "+"), 1_001)).unwrap();
+        store.publish(event(&bob, EventBuilder::reaction(&note, "+"), 1_002)).unwrap();
+        store.publish(event(&bob, EventBuilder::delete([EventIdOrCoordinate::from(note.id)], None::<String>), 1_003)).unwrap();
+        assert_eq!(store.len(), 5);
+
+        let exact = |count: u64| EventCount { count, approximate: false };
+        let replies = Filter::new().event(note.id);
+        assert_eq!(store.count(&[replies.clone().kind(Kind::Reaction)]), exact(2));
+        assert_eq!(store.count(std::slice::from_ref(&replies)), exact(2));
+        assert_eq!(store.count(&[replies.clone().kind(Kind::EventDeletion)]), exact(1));
+        assert_eq!(store.count(&[replies.clone().kind(Kind::Reaction).since(Timestamp::from(1_002))]), exact(1));
+        assert_eq!(store.count(&[replies.clone().until(Timestamp::from(1_001))]), exact(1));
+        assert_eq!(store.count(&[Filter::new().kind(Kind::Reaction), replies.clone()]), exact(2));
+        assert_eq!(store.count(&[Filter::new().kind(Kind::TextNote)]), exact(2));
+        assert_eq!(store.count(&[Filter::new().author(note.pubkey.clone())]), exact(2));
+        assert_eq!(store.count(&[Filter::new()]), exact(4));
+        assert_eq!(store.count(&[Filter::new().kind(Kind::TextNote).limit(1)]), exact(2));
+
+        clock.set(Timestamp::from(2_000));
+        assert_eq!(store.count(&[Filter::new().kind(Kind::TextNote)]), exact(1));
+        assert_eq!(store.count(&[Filter::new()]), exact(3));
+
+        // The tag and kind index is kept on reopen
+        let store = open_with_clock(&memory, clock);
+        assert_eq!(store.by_tag_kind.len(), store.by_tag.len());
+        assert_eq!(store.count(&[replies.kind(Kind::Reaction)]), exact(2));
+    }
+
+    #[test]
+    fn test_count_ids() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let alice = signer(1);
+        let notes: Vec<EventData> = (0..600).map(|i| event(&alice, EventBuilder::text_note(i.to_string(), []), 1_000 + i)).collect();
+        for note in notes.iter() {
+            store.publish(note.clone()).unwrap();
+        }
+        let exact = |count: u64| EventCount { count, approximate: false };
+        let all = Filter::new().ids(notes.iter().map(|note| note.id));
+        assert_eq!(store.count(core::slice::from_ref(&all)), exact(600));
+        assert_eq!(store.count(&[all.clone().since(Timestamp::from(1_100))]), exact(500));
+        assert_eq!(store.count(&[all.kind(Kind::Reaction)]), exact(0));
+
+        let prefix: String = String::from(&notes[0].id)[..4].to_string();
+        let expected = notes.iter().filter(|note| String::from(&note.id).starts_with(&prefix)).count() as u64;
+        assert_eq!(store.count(&[Filter::new().id_prefix(prefix)]), exact(expected));
+    }
+
+    fn ids<M: Memory>(store: &EventStore<M, MockTimeSupplier>, filters: &[Filter]) -> Vec<EventId> {
+        store.query(filters).iter().map(|event| event.id).collect()
+    }
+
+    #[test]
+    fn test_search() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let (alice, bob) = (signer(1), signer(2));
+        let profile = event(&alice, EventBuilder::metadata(&Metadata::new().name("alice").nip05("alice@Example.com")), 900);
+        let note = event(&alice, EventBuilder::text_note("Rust canisters on the Internet Computer", []), 1_000);
+        let borrow = event(&alice, EventBuilder::text_note("rust rust rust rust, the borrow checker", []), 1_001);
+        let tags = [TagData::custom("d", ["rust"]), TagData::custom("title", ["Rust in canisters"])];
+        let article = event(&alice, EventBuilder::new(Kind::LongFormTextNote, "Writing canisters", tags), 1_002);
+        let other = event(&bob, EventBuilder::text_note("Canisters in Go", []), 1_003);
+        for event in [&profile, &note, &borrow, &article, &other] {
+            store.publish(event.clone()).unwrap();
+        }
+
+        assert_eq!(ids(&store, &[Filter::new().search("rust CANISTERS")]), [article.id, note.id]);
+        assert_eq!(ids(&store, &[Filter::new().search("canisters").limit(1)]), [article.id]);
+        assert_eq!(ids(&store, &[Filter::new().search("rust canisters").kind(Kind::TextNote)]), [note.id]);
+        assert_eq!(ids(&store, &[Filter::new().search(r#""internet computer""#)]), [note.id]);
+        assert!(ids(&store, &[Filter::new().search(r#""computer internet""#)]).is_empty());
+        assert!(ids(&store, &[Filter::new().search("rust python")]).is_empty());
+        assert_eq!(ids(&store, &[Filter::new().search("canisters domain:example.com")]), [article.id, note.id]);
+        assert_eq!(ids(&store, &[Filter::new().search("domain:example.com").kind(Kind::TextNote)]), [borrow.id, note.id]);
+        assert_eq!(ids(&store, &[Filter::new().kind(Kind::Metadata), Filter::new().search("go")]), [other.id, profile.id]);
+        assert_eq!(store.count(&[Filter::new().search("canisters")]), EventCount { count: 3, approximate: false });
+        assert_eq!(store.count(&[Filter::new().search("canisters").until(Timestamp::from(1_001))]).count, 1);
+
+        store.remove(&note.id);
+        assert!(ids(&store, &[Filter::new().search("internet")]).is_empty());
+        assert_eq!(ids(&store, &[Filter::new().search("rust")]), [borrow.id, article.id]);
+    }
+
+    #[test]
+    fn test_search_past_rejected_hits() {
+        let mut store = open(&DefaultMemoryImpl::default());
+        let alice = signer(1);
+        let article = event(&alice, EventBuilder::new(Kind::LongFormTextNote, "rust", [TagData::custom("d", ["rust"])]), 900);
+        store.publish(article.clone()).unwrap();
+        for i in 0..1_100 {
+            store.publish(event(&alice, EventBuilder::text_note(format!("rust {i}"), []), 1_000 + i)).unwrap();
+        }
+        assert_eq!(ids(&store, &[Filter::new().search("rust").kind(Kind::LongFormTextNote).limit(1)]), [article.id]);
+    }
+
+    #[test]
+    fn test_search_index_version() {
+        let memory = DefaultMemoryImpl::default();
+        let mut store = open(&memory);
+        let alice = signer(1);
+        let note = event(&alice, EventBuilder::text_note("Rust canisters", []), 1_000);
+        let other = event(&alice, EventBuilder::text_note("Rust in Go", []), 1_001);
+        store.publish(note.clone()).unwrap();
+        store.publish(other.clone()).unwrap();
+        assert!(!store.is_search_outdated());
+
+        // A current index is kept on reopen, even one indexing nothing
+        store.search.clear();
+        let store = open(&memory);
+        assert!(ids(&store, &[Filter::new().search("rust")]).is_empty());
+
+        // An index older than the current version is cleared, searches scan until it is filled again
+        let memory_manager = MemoryManager::init(memory.clone());
+        StableCell::<u64, _>::init(memory_manager.get(SEARCH_VERSION_MEMORY_ID), 0).unwrap().set(0).unwrap();
+        let mut store = open(&memory);
+        assert!(store.is_search_outdated());
+        assert_eq!(ids(&store, &[Filter::new().search("rust")]), [other.id, note.id]);
+        assert_eq!(store.count(&[Filter::new().search("canisters")]), EventCount { count: 1, approximate: false });
+
+        let first = store.reindex_search_after(None, 1);
+        assert!(first.is_some());
+        let later = event(&alice, EventBuilder::text_note("Rust everywhere", []), 1_002);
+        store.publish(later.clone()).unwrap();
+        let mut after = first;
+        while let Some(last) = after {
+            assert!(store.is_search_outdated());
+            after = store.reindex_search_after(Some(&last), 1);
+        }
+        assert!(!store.is_search_outdated());
+        assert_eq!(ids(&store, &[Filter::new().search("rust canisters")]), [note.id]);
+        assert_eq!(store.count(&[Filter::new().search("rust")]), EventCount { count: 3, approximate: false });
+    }
+}
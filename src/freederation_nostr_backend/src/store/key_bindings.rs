@@ -0,0 +1,303 @@
+//! Principal to Nostr key bindings
+//!
+//! A principal proves control of a key by signing an event whose content contains a challenge
+//! issued by the canister for that principal. Challenges are single use and expire after
+//! [`CHALLENGE_TTL`]. A key is bound to at most one principal.
+
+use std::borrow::Cow;
+
+use candid::Principal;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::storable::{Bound, Storable};
+use ic_stable_structures::{Memory, StableBTreeMap};
+use hex_conservative::DisplayHex;
+
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_error::EventDataError;
+use crate::signing::{AsymmetricKeyImpl, AsymmetricKeyOps, DataField, NostrPubKey};
+use crate::store::memory::{BIND_CHALLENGES_MEMORY_ID, BOUND_KEYS_MEMORY_ID, KEY_BINDINGS_MEMORY_ID};
+use crate::util::time::{TimeSupplier, Timestamp};
+
+/// Validity of a challenge in seconds
+pub const CHALLENGE_TTL: u64 = 10 * 60;
+
+/// Most keys bound to a principal
+pub const MAX_KEYS_PER_PRINCIPAL: usize = 10;
+
+/// [`KeyBindings`] error
+#[derive(thiserror::Error, Debug)]
+pub enum BindingError {
+
+    #[error("Anonymous principals cannot bind keys")]
+    Anonymous,
+
+    #[error("No pending challenge, request one first")]
+    NoChallenge,
+
+    #[error("Challenge expired at {0}")]
+    ChallengeExpired(Timestamp),
+
+    #[error("Event content does not contain the challenge")]
+    ChallengeMismatch,
+
+    #[error(transparent)]
+    InvalidEvent(#[from] EventDataError),
+
+    #[error("Key is bound to another principal")]
+    BoundElsewhere,
+
+    #[error("Key is not bound to the caller")]
+    NotBound,
+
+    #[error("Too many keys, at most {MAX_KEYS_PER_PRINCIPAL}")]
+    TooManyKeys,
+}
+
+/// Pending challenge of a principal
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Challenge {
+    nonce: [u8; 32],
+    expires_at: Timestamp,
+}
+
+/// Serialized size of a [`Challenge`]
+const CHALLENGE_SIZE: usize = 32 + 8;
+
+impl Storable for Challenge {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(CHALLENGE_SIZE);
+        bytes.extend_from_slice(&self.nonce);
+        bytes.extend_from_slice(&self.expires_at.as_u64().to_be_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut nonce = [0u8; 32];
+        nonce.copy_from_slice(&bytes[..32]);
+        let mut expires_at = [0u8; 8];
+        expires_at.copy_from_slice(&bytes[32..CHALLENGE_SIZE]);
+        Self {
+            nonce,
+            expires_at: Timestamp::from(u64::from_be_bytes(expires_at)),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: CHALLENGE_SIZE as u32,
+        is_fixed_size: true,
+    };
+}
+
+/// Persistent key bindings
+pub struct KeyBindings<M: Memory, T: TimeSupplier> {
+    /// Principal and key to the binding time
+    by_principal: StableBTreeMap<(Principal, DataField), u64, M>,
+    /// Key to its principal
+    by_key: StableBTreeMap<DataField, Principal, M>,
+    challenges: StableBTreeMap<Principal, Challenge, M>,
+    time_supplier: T,
+}
+
+impl<M: Memory, T: TimeSupplier> KeyBindings<M, T> {
+    /// Open the bindings, `memory` provides the virtual memory of each [`MemoryId`]
+    pub fn init<F>(mut memory: F, time_supplier: T) -> Self
+    where
+        F: FnMut(MemoryId) -> M,
+    {
+        Self {
+            by_principal: StableBTreeMap::init(memory(KEY_BINDINGS_MEMORY_ID)),
+            by_key: StableBTreeMap::init(memory(BOUND_KEYS_MEMORY_ID)),
+            challenges: StableBTreeMap::init(memory(BIND_CHALLENGES_MEMORY_ID)),
+            time_supplier,
+        }
+    }
+
+    /// Current time of the [`TimeSupplier`]
+    #[inline]
+    pub fn now(&self) -> Timestamp {
+        Timestamp::now_with_supplier(&self.time_supplier)
+    }
+
+    /// Issue a challenge for `principal` from random `nonce`, replacing the pending one
+    ///
+    /// Returns the hex challenge to put in the content of the proof.
+    pub fn issue_challenge(&mut self, principal: Principal, nonce: [u8; 32]) -> Result<String, BindingError> {
+        check_principal(&principal)?;
+        let expires_at = Timestamp::from(self.now().as_u64() + CHALLENGE_TTL);
+        self.challenges.insert(principal, Challenge { nonce, expires_at });
+        Ok(nonce.to_lower_hex_string())
+    }
+
+    /// Bind the author of `proof` to `principal`, consuming the challenge
+    ///
+    /// `proof` must be validly signed and its content must contain the pending challenge.
+    /// Binding a key again is a no-op.
+    pub fn bind(&mut self, principal: Principal, proof: &EventData) -> Result<NostrPubKey, BindingError> {
+        check_principal(&principal)?;
+        let challenge: Challenge = self.challenges.get(&principal).ok_or(BindingError::NoChallenge)?;
+        if challenge.expires_at <= self.now() {
+            self.challenges.remove(&principal);
+            return Err(BindingError::ChallengeExpired(challenge.expires_at));
+        }
+        proof.verify()?;
+        if !proof.content.contains(challenge.nonce.to_lower_hex_string().as_str()) {
+            return Err(BindingError::ChallengeMismatch);
+        }
+
+        let key: DataField = key_bytes(&proof.pubkey);
+        match self.by_key.get(&key) {
+            Some(owner) if owner != principal => return Err(BindingError::BoundElsewhere),
+            Some(_) => {}
+            None => {
+                if self.keys_of(&principal).len() >= MAX_KEYS_PER_PRINCIPAL {
+                    return Err(BindingError::TooManyKeys);
+                }
+                self.by_principal.insert((principal, key), self.now().as_u64());
+                self.by_key.insert(key, principal);
+            }
+        }
+        self.challenges.remove(&principal);
+        Ok(proof.pubkey.clone())
+    }
+
+    /// Remove the binding of `public_key` to `principal`, returning whether it existed
+    pub fn unbind(&mut self, principal: &Principal, public_key: &NostrPubKey) -> bool {
+        let key: DataField = key_bytes(public_key);
+        if self.by_key.get(&key).as_ref() != Some(principal) {
+            return false;
+        }
+        self.by_key.remove(&key);
+        self.by_principal.remove(&(*principal, key));
+        true
+    }
+
+    /// Keys bound to `principal`
+    pub fn keys_of(&self, principal: &Principal) -> Vec<NostrPubKey> {
+        self.by_principal
+            .range((*principal, [0u8; 32])..=(*principal, [u8::MAX; 32]))
+            .filter_map(|((_, key), _)| NostrPubKey::try_from(key.as_slice()).ok())
+            .collect()
+    }
+
+    /// Check that `public_key` is bound to `principal`
+    pub fn check_bound(&self, principal: &Principal, public_key: &NostrPubKey) -> Result<(), BindingError> {
+        match self.principal_of(public_key).as_ref() == Some(principal) {
+            true => Ok(()),
+            false => Err(BindingError::NotBound),
+        }
+    }
+
+    /// Principal bound to `public_key`
+    #[inline]
+    pub fn principal_of(&self, public_key: &NostrPubKey) -> Option<Principal> {
+        self.by_key.get(&key_bytes(public_key))
+    }
+}
+
+fn check_principal(principal: &Principal) -> Result<(), BindingError> {
+    match *principal == Principal::anonymous() {
+        true => Err(BindingError::Anonymous),
+        false => Ok(()),
+    }
+}
+
+fn key_bytes(public_key: &NostrPubKey) -> DataField {
+    AsymmetricKeyImpl().public_key_to_bytes(&public_key.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::memory_manager::MemoryManager;
+    use ic_stable_structures::DefaultMemoryImpl;
+    use rand_core::SeedableRng;
+
+    use super::*;
+    use crate::nostr::event_builder::EventBuilder;
+    use crate::rng::CryptoHashRng;
+    use crate::signing::NostrSigningKey;
+    use crate::util::time::MockTimeSupplier;
+
+    fn open(memory: &DefaultMemoryImpl, clock: MockTimeSupplier) -> KeyBindings<impl Memory, MockTimeSupplier> {
+        let memory_manager = MemoryManager::init(memory.clone());
+        KeyBindings::init(|id| memory_manager.get(id), clock)
+    }
+
+    fn signer(seed: u8) -> NostrSigningKey {
+        AsymmetricKeyImpl().generate_keypair(&mut CryptoHashRng::from_seed([seed; 32])).unwrap()
+    }
+
+    fn proof(signer: &NostrSigningKey, content: &str) -> EventData {
+        EventBuilder::text_note(content, []).sign(signer, &mut CryptoHashRng::from_seed([1u8; 32])).unwrap()
+    }
+
+    fn public_key(signer: &NostrSigningKey) -> NostrPubKey {
+        NostrPubKey(AsymmetricKeyImpl().pubkey_from_pair(signer))
+    }
+
+    #[test]
+    fn test_bind() {
+        let memory = DefaultMemoryImpl::default();
+        let mut bindings = open(&memory, MockTimeSupplier::new(Timestamp::from(1_000)));
+        let alice = Principal::from_slice(&[1]);
+        let bob = Principal::from_slice(&[2]);
+        let key = signer(1);
+
+        assert!(matches!(bindings.bind(alice, &proof(&key, "no challenge")), Err(BindingError::NoChallenge)));
+        let challenge = bindings.issue_challenge(alice, [7u8; 32]).unwrap();
+        assert_eq!(challenge, "07".repeat(32));
+
+        assert!(matches!(bindings.bind(alice, &proof(&key, "wrong")), Err(BindingError::ChallengeMismatch)));
+        let mut tampered = proof(&key, &challenge);
+        tampered.content = format!("bind {challenge}");
+        assert!(matches!(bindings.bind(alice, &tampered), Err(BindingError::InvalidEvent(_))));
+
+        let bound = bindings.bind(alice, &proof(&key, &format!("bind {challenge}"))).unwrap();
+        assert_eq!(bound, public_key(&key));
+        assert!(matches!(bindings.bind(alice, &proof(&key, &challenge)), Err(BindingError::NoChallenge)));
+        assert_eq!(bindings.keys_of(&alice), vec![bound.clone()]);
+        assert_eq!(bindings.principal_of(&bound), Some(alice));
+        assert!(bindings.check_bound(&alice, &bound).is_ok());
+        assert!(matches!(bindings.check_bound(&bob, &bound), Err(BindingError::NotBound)));
+        assert!(matches!(bindings.check_bound(&alice, &public_key(&signer(2))), Err(BindingError::NotBound)));
+
+        let challenge = bindings.issue_challenge(bob, [8u8; 32]).unwrap();
+        assert!(matches!(bindings.bind(bob, &proof(&key, &challenge)), Err(BindingError::BoundElsewhere)));
+        assert!(bindings.keys_of(&bob).is_empty());
+
+        let reopened = open(&memory, MockTimeSupplier::new(Timestamp::from(1_000)));
+        assert_eq!(reopened.principal_of(&bound), Some(alice));
+
+        assert!(!bindings.unbind(&bob, &bound));
+        assert!(bindings.unbind(&alice, &bound));
+        assert!(bindings.keys_of(&alice).is_empty());
+        assert_eq!(bindings.principal_of(&bound), None);
+        assert!(matches!(bindings.check_bound(&alice, &bound), Err(BindingError::NotBound)));
+        assert!(bindings.bind(bob, &proof(&key, &challenge)).is_ok());
+    }
+
+    #[test]
+    fn test_challenge_rules() {
+        let clock = MockTimeSupplier::new(Timestamp::from(1_000));
+        let mut bindings = open(&DefaultMemoryImpl::default(), clock.clone());
+        let alice = Principal::from_slice(&[1]);
+
+        assert!(matches!(bindings.issue_challenge(Principal::anonymous(), [0u8; 32]), Err(BindingError::Anonymous)));
+        assert!(matches!(bindings.bind(Principal::anonymous(), &proof(&signer(1), "")), Err(BindingError::Anonymous)));
+
+        let challenge = bindings.issue_challenge(alice, [1u8; 32]).unwrap();
+        clock.set(Timestamp::from(1_000 + CHALLENGE_TTL));
+        assert!(matches!(bindings.bind(alice, &proof(&signer(1), &challenge)), Err(BindingError::ChallengeExpired(_))));
+        assert!(matches!(bindings.bind(alice, &proof(&signer(1), &challenge)), Err(BindingError::NoChallenge)));
+
+        for seed in 0..=MAX_KEYS_PER_PRINCIPAL as u8 {
+            let challenge = bindings.issue_challenge(alice, [seed; 32]).unwrap();
+            let result = bindings.bind(alice, &proof(&signer(seed + 1), &challenge));
+            match (seed as usize) < MAX_KEYS_PER_PRINCIPAL {
+                true => assert!(result.is_ok()),
+                false => assert!(matches!(result, Err(BindingError::TooManyKeys))),
+            }
+        }
+        assert_eq!(bindings.keys_of(&alice).len(), MAX_KEYS_PER_PRINCIPAL);
+        assert!(bindings.keys_of(&Principal::from_slice(&[1, 0])).is_empty());
+    }
+}
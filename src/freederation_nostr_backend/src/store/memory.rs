@@ -0,0 +1,60 @@
+//! Stable memory layout
+//!
+//! Every stable structure of the canister gets its own virtual memory from a single
+//! [`MemoryManager`]. Ids must never be reused or reordered, otherwise an upgrade would
+//! read the data of one structure as another.
+
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::DefaultMemoryImpl;
+
+/// Virtual memory handed to stable structures
+pub type StoreMemory = VirtualMemory<DefaultMemoryImpl>;
+
+/// Memory manager over the canister stable memory
+pub type StoreMemoryManager = MemoryManager<DefaultMemoryImpl>;
+
+/// Events by id
+pub const EVENTS_MEMORY_ID: MemoryId = MemoryId::new(0);
+/// Author index
+pub const AUTHOR_INDEX_MEMORY_ID: MemoryId = MemoryId::new(1);
+/// Kind index
+pub const KIND_INDEX_MEMORY_ID: MemoryId = MemoryId::new(2);
+/// `created_at` index
+pub const CREATED_AT_INDEX_MEMORY_ID: MemoryId = MemoryId::new(3);
+/// Single-letter tag index
+pub const TAG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(4);
+/// Latest replaceable event by coordinate
+pub const COORDINATE_INDEX_MEMORY_ID: MemoryId = MemoryId::new(5);
+/// Tombstones of deleted event ids
+pub const DELETED_EVENTS_MEMORY_ID: MemoryId = MemoryId::new(6);
+/// Tombstones of deleted coordinates
+pub const DELETED_COORDINATES_MEMORY_ID: MemoryId = MemoryId::new(7);
+/// Expiration index
+pub const EXPIRATION_INDEX_MEMORY_ID: MemoryId = MemoryId::new(8);
+/// Persistent RNG state
+pub const RNG_STATE_MEMORY_ID: MemoryId = MemoryId::new(9);
+/// Threshold signer configuration
+pub const SIGNER_CONFIG_MEMORY_ID: MemoryId = MemoryId::new(10);
+/// Relay admission policy
+pub const RELAY_POLICY_MEMORY_ID: MemoryId = MemoryId::new(11);
+/// NIP11 relay information
+pub const RELAY_INFO_MEMORY_ID: MemoryId = MemoryId::new(12);
+/// NIP05 names
+pub const NIP05_NAMES_MEMORY_ID: MemoryId = MemoryId::new(13);
+/// NIP05 name by owner principal
+pub const NIP05_OWNERS_MEMORY_ID: MemoryId = MemoryId::new(14);
+/// Principal and Nostr key bindings
+pub const KEY_BINDINGS_MEMORY_ID: MemoryId = MemoryId::new(15);
+/// Principal by bound Nostr key
+pub const BOUND_KEYS_MEMORY_ID: MemoryId = MemoryId::new(16);
+/// Pending key binding challenges
+pub const BIND_CHALLENGES_MEMORY_ID: MemoryId = MemoryId::new(17);
+/// Single-letter tag then kind index
+pub const TAG_KIND_INDEX_MEMORY_ID: MemoryId = MemoryId::new(18);
+
+/// Full-text search postings
+pub const SEARCH_POSTINGS_MEMORY_ID: MemoryId = MemoryId::new(19);
+/// Number of events of each search term
+pub const SEARCH_TERMS_MEMORY_ID: MemoryId = MemoryId::new(20);
+/// Version of the search index
+pub const SEARCH_VERSION_MEMORY_ID: MemoryId = MemoryId::new(21);
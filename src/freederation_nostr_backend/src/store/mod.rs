@@ -0,0 +1,14 @@
+//! Persistent storage in canister stable memory
+
+pub mod memory;
+pub mod store_error;
+pub mod storable;
+pub mod eventstore;
+pub mod search_index;
+pub mod key_bindings;
+pub mod name_registry;
+
+pub use self::eventstore::{EventCount, EventStore, PublishStatus, QueryOptions};
+pub use self::key_bindings::{BindingError, KeyBindings};
+pub use self::name_registry::{NameError, NameRegistry};
+pub use self::store_error::StoreError;
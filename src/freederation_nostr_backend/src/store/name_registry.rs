@@ -0,0 +1,243 @@
+//! NIP05 name registry
+//!
+//! Every principal can hold one name, mapped to a [`NostrPubKey`] and its preferred relays.
+//! Names are lowercase local parts of a NIP05 identifier, the domain is the one of the canister.
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/05.md>
+
+use std::borrow::Cow;
+use std::ops;
+
+use candid::Principal;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::storable::{Bound, Storable};
+use ic_stable_structures::{Memory, StableBTreeMap};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::signing::NostrPubKey;
+use crate::store::memory::{NIP05_NAMES_MEMORY_ID, NIP05_OWNERS_MEMORY_ID};
+
+/// Longest registrable name
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Most relays listed for a name
+pub const MAX_RELAYS: usize = 10;
+
+/// [`NameRegistry`] error
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum NameError {
+
+    #[error("Invalid name, only a-z 0-9 - _ . are allowed: {0}")]
+    InvalidName(String),
+
+    #[error("Invalid relay url: {0}")]
+    InvalidRelay(String),
+
+    #[error("Too many relays, at most {MAX_RELAYS}")]
+    TooManyRelays,
+
+    #[error("Name already registered: {0}")]
+    NameTaken(String),
+}
+
+/// Registered name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameEntry {
+    /// Principal holding the name
+    pub owner: Principal,
+    pub public_key: NostrPubKey,
+    /// Relay urls
+    pub relays: Vec<String>,
+}
+
+impl Storable for NameEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_json::to_vec(self).expect("name entry serializes"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).expect("stored name entry")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Lowercase `name`, checking the NIP05 local part characters
+pub fn normalize_name(name: &str) -> Result<String, NameError> {
+    let normalized: String = name.trim().to_ascii_lowercase();
+    let valid: bool = !normalized.is_empty()
+        && normalized.len() <= MAX_NAME_LENGTH
+        && normalized.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'));
+    match valid {
+        true => Ok(normalized),
+        false => Err(NameError::InvalidName(String::from(name))),
+    }
+}
+
+/// Check that every relay is a `ws` or `wss` url
+fn check_relays(relays: &[String]) -> Result<(), NameError> {
+    if relays.len() > MAX_RELAYS {
+        return Err(NameError::TooManyRelays);
+    }
+    for relay in relays {
+        match Url::parse(relay) {
+            Ok(url) if matches!(url.scheme(), "ws" | "wss") && url.has_host() => {}
+            _ => return Err(NameError::InvalidRelay(relay.clone())),
+        }
+    }
+    Ok(())
+}
+
+/// Persistent NIP05 names
+pub struct NameRegistry<M: Memory> {
+    names: StableBTreeMap<String, NameEntry, M>,
+    /// Principal text to name
+    owners: StableBTreeMap<String, String, M>,
+}
+
+impl<M: Memory> NameRegistry<M> {
+    /// Open the registry, `memory` provides the virtual memory of each [`MemoryId`]
+    pub fn init<F>(mut memory: F) -> Self
+    where
+        F: FnMut(MemoryId) -> M,
+    {
+        Self {
+            names: StableBTreeMap::init(memory(NIP05_NAMES_MEMORY_ID)),
+            owners: StableBTreeMap::init(memory(NIP05_OWNERS_MEMORY_ID)),
+        }
+    }
+
+    /// Number of registered names
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.names.len()
+    }
+
+    /// Check if no name is registered
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Entry of `name`, case insensitive
+    pub fn get(&self, name: &str) -> Option<NameEntry> {
+        normalize_name(name).ok().and_then(|name| self.names.get(&name))
+    }
+
+    /// Names following `after` and their entry, every name when `None`, by name
+    pub fn iter_after(&self, after: Option<&str>) -> impl Iterator<Item = (String, NameEntry)> + '_ {
+        let start: ops::Bound<String> = after.map_or(ops::Bound::Unbounded, |name| ops::Bound::Excluded(String::from(name)));
+        self.names.range((start, ops::Bound::Unbounded))
+    }
+
+    /// Name held by `owner`
+    #[inline]
+    pub fn name_of(&self, owner: &Principal) -> Option<String> {
+        self.owners.get(&owner.to_text())
+    }
+
+    /// Register `name` for `owner`, releasing the name it held before
+    ///
+    /// Returns the normalized name. Registering the same name again updates its key and relays.
+    pub fn register(
+        &mut self,
+        owner: Principal,
+        name: &str,
+        public_key: NostrPubKey,
+        relays: Vec<String>,
+    ) -> Result<String, NameError> {
+        let name: String = normalize_name(name)?;
+        check_relays(relays.as_slice())?;
+        if let Some(entry) = self.names.get(&name) {
+            if entry.owner != owner {
+                return Err(NameError::NameTaken(name));
+            }
+        }
+
+        self.unregister(&owner);
+        self.owners.insert(owner.to_text(), name.clone());
+        self.names.insert(name.clone(), NameEntry { owner, public_key, relays });
+        Ok(name)
+    }
+
+    /// Release the name of `owner`, returning it
+    pub fn unregister(&mut self, owner: &Principal) -> Option<String> {
+        let name: String = self.owners.remove(&owner.to_text())?;
+        self.names.remove(&name);
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use ic_stable_structures::memory_manager::MemoryManager;
+    use ic_stable_structures::DefaultMemoryImpl;
+
+    use super::*;
+
+    const ALICE_KEY: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const BOB_KEY: &str = "f572561e79d30c334e7ce864a2055e887eafdf5dad71242adc0c43567cc269f8";
+
+    fn open(memory: &DefaultMemoryImpl) -> NameRegistry<impl Memory> {
+        let memory_manager = MemoryManager::init(memory.clone());
+        NameRegistry::init(|id| memory_manager.get(id))
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("Bob").unwrap(), "bob");
+        assert_eq!(normalize_name("_").unwrap(), "_");
+        assert_eq!(normalize_name("a.b-c_9").unwrap(), "a.b-c_9");
+        assert!(normalize_name("").is_err());
+        assert!(normalize_name("bob@example.com").is_err());
+        assert!(normalize_name("bób").is_err());
+        assert!(normalize_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_register() {
+        let memory = DefaultMemoryImpl::default();
+        let mut registry = open(&memory);
+        let alice = Principal::from_slice(&[1]);
+        let bob = Principal::from_slice(&[2]);
+        let alice_key = NostrPubKey::from_str(ALICE_KEY).unwrap();
+        let bob_key = NostrPubKey::from_str(BOB_KEY).unwrap();
+
+        let relays = vec![String::from("wss://relay.example.com")];
+        assert_eq!(registry.register(alice, "Alice", alice_key.clone(), relays.clone()).unwrap(), "alice");
+        assert_eq!(registry.get("ALICE").unwrap().relays, relays);
+        assert_eq!(registry.name_of(&alice).as_deref(), Some("alice"));
+
+        assert_eq!(
+            registry.register(bob, "alice", bob_key.clone(), vec![]),
+            Err(NameError::NameTaken(String::from("alice")))
+        );
+        assert!(matches!(
+            registry.register(bob, "bob", bob_key.clone(), vec![String::from("https://relay.example.com")]),
+            Err(NameError::InvalidRelay(_))
+        ));
+        assert_eq!(
+            registry.register(bob, "bob", bob_key.clone(), vec![String::from("wss://r.example.com"); MAX_RELAYS + 1]),
+            Err(NameError::TooManyRelays)
+        );
+        registry.register(bob, "bob", bob_key.clone(), vec![]).unwrap();
+
+        registry.register(alice, "al", alice_key.clone(), vec![]).unwrap();
+        assert!(registry.get("alice").is_none());
+        assert_eq!(registry.get("al").unwrap().public_key, alice_key);
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.iter_after(None).map(|(name, _)| name).collect::<Vec<_>>(), ["al", "bob"]);
+        assert_eq!(registry.iter_after(Some("al")).map(|(name, _)| name).collect::<Vec<_>>(), ["bob"]);
+
+        let reopened = open(&memory);
+        assert_eq!(reopened.get("bob").unwrap().owner, bob);
+
+        assert_eq!(registry.unregister(&bob).as_deref(), Some("bob"));
+        assert!(registry.get("bob").is_none());
+        assert_eq!(registry.unregister(&bob), None);
+        assert_eq!(registry.len(), 1);
+    }
+}
@@ -0,0 +1,187 @@
+//! Full-text search index
+//!
+//! Inverted index of the terms of the stored events, see [`event_terms`]. Posting keys are the
+//! truncated hash of the term followed by the inverted `created_at` and the [`EventId`], their
+//! value is the weighted frequency of the term. A second map counts the events of each term.
+//!
+//! Hits are ranked by BM25 without length normalization, events are short.
+//! Memory stays bounded by [`MAX_EVENT_TERMS`](crate::nostr::search::MAX_EVENT_TERMS) postings
+//! per event and [`MAX_SEARCH_POSTINGS`] postings walked per search.
+//!
+//! The index records its [`SEARCH_INDEX_VERSION`], an older index is cleared by the store and
+//! filled again in batches, see [`EventStore::reindex_search_after`](crate::store::eventstore::EventStore::reindex_search_after).
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/50.md>
+
+use core::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use ic_stable_structures::{Memory, StableBTreeMap, StableCell};
+
+use crate::encryption::{Sha256Hash, Sha2Digest};
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_id::EventId;
+use crate::nostr::search::event_terms;
+use crate::store::storable::IndexKey;
+use crate::util::time::Timestamp;
+
+/// Version of the terms and postings, bumped when [`event_terms`] or the keys change
+pub const SEARCH_INDEX_VERSION: u64 = 1;
+
+/// Postings of the rarest term walked by [`SearchIndex::search`]
+pub const MAX_SEARCH_POSTINGS: usize = 10_000;
+
+/// Bytes of the term hash, collisions are filtered out by matching the events
+const TERM_HASH_SIZE: usize = 8;
+
+/// BM25 term frequency saturation
+const K1: f64 = 1.2;
+
+/// Event having every term of a search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchHit {
+    pub id: EventId,
+    pub created_at: Timestamp,
+    /// Relevance, higher is better
+    pub score: f64,
+}
+
+/// Outcome of [`SearchIndex::search`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHits {
+    /// Best first, ties newest first
+    pub hits: Vec<SearchHit>,
+    /// `false` when the walk stopped at [`MAX_SEARCH_POSTINGS`]
+    pub complete: bool,
+}
+
+/// Persistent inverted index
+pub struct SearchIndex<M: Memory> {
+    /// Term, inverted `created_at` and id to the weighted term frequency
+    postings: StableBTreeMap<IndexKey, u16, M>,
+    /// Term to its number of events
+    term_events: StableBTreeMap<IndexKey, u64, M>,
+    /// [`SEARCH_INDEX_VERSION`] of the last complete rebuild, 0 before the first one
+    version: StableCell<u64, M>,
+}
+
+impl<M: Memory> SearchIndex<M> {
+    /// Open the index, existing content of the memories is kept
+    pub fn init(postings: M, term_events: M, version: M) -> Self {
+        Self {
+            postings: StableBTreeMap::init(postings),
+            term_events: StableBTreeMap::init(term_events),
+            version: StableCell::init(version, 0).expect("search index version fits its memory"),
+        }
+    }
+
+    /// Check if the index predates [`SEARCH_INDEX_VERSION`] and must be rebuilt
+    #[inline]
+    pub fn is_outdated(&self) -> bool {
+        *self.version.get() < SEARCH_INDEX_VERSION
+    }
+
+    /// Drop every posting, the index stays outdated until [`SearchIndex::set_current`]
+    pub fn clear(&mut self) {
+        self.postings.clear_new();
+        self.term_events.clear_new();
+    }
+
+    /// Record that every stored event is indexed at [`SEARCH_INDEX_VERSION`]
+    pub fn set_current(&mut self) {
+        self.version.set(SEARCH_INDEX_VERSION).expect("search index version fits its memory");
+    }
+
+    /// Index the terms of an event
+    pub fn insert(&mut self, event: &EventData) {
+        for (term, frequency) in event_terms(event) {
+            let prefix = term_prefix(&term);
+            let key = prefix.clone().with_created_at(event.created_at).with_id(&event.id);
+            if self.postings.insert(key, frequency).is_none() {
+                let events: u64 = self.term_events.get(&prefix).unwrap_or_default();
+                self.term_events.insert(prefix, events + 1);
+            }
+        }
+    }
+
+    /// Remove the terms of an event
+    pub fn remove(&mut self, event: &EventData) {
+        for term in event_terms(event).into_keys() {
+            let prefix = term_prefix(&term);
+            let key = prefix.clone().with_created_at(event.created_at).with_id(&event.id);
+            if self.postings.remove(&key).is_none() {
+                continue;
+            }
+            match self.term_events.get(&prefix) {
+                Some(events) if events > 1 => self.term_events.insert(prefix, events - 1),
+                _ => self.term_events.remove(&prefix),
+            };
+        }
+    }
+
+    /// Events having every one of `terms` with `since <= created_at <= until`, out of `total` events
+    ///
+    /// The postings of the rarest term are walked newest first, the other terms are looked up by key.
+    pub fn search(&self, terms: &BTreeSet<String>, since: Option<Timestamp>, until: Option<Timestamp>, total: u64) -> SearchHits {
+        let mut weighted: Vec<(IndexKey, f64)> = Vec::with_capacity(terms.len());
+        for term in terms {
+            let prefix = term_prefix(term);
+            let Some(events) = self.term_events.get(&prefix) else {
+                return SearchHits { hits: Vec::new(), complete: true };
+            };
+            weighted.push((prefix, idf(events, total)));
+        }
+        // The rarest term has the highest idf
+        weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let Some(((lead, lead_idf), others)) = weighted.split_first() else {
+            return SearchHits { hits: Vec::new(), complete: true };
+        };
+
+        let mut result = SearchHits { hits: Vec::new(), complete: true };
+        for (walked, (key, frequency)) in self.postings.range(IndexKey::time_range(lead.clone(), since, until)).enumerate() {
+            if walked >= MAX_SEARCH_POSTINGS {
+                result.complete = false;
+                break;
+            }
+            let (Some(id), Some(created_at)) = (key.event_id(), key.read_created_at(TERM_HASH_SIZE)) else {
+                continue;
+            };
+            let score: Option<f64> = others.iter().try_fold(lead_idf * saturate(frequency), |score, (prefix, idf)| {
+                let frequency = self.postings.get(&prefix.clone().with_created_at(created_at).with_id(&id))?;
+                Some(score + idf * saturate(frequency))
+            });
+            if let Some(score) = score {
+                result.hits.push(SearchHit { id, created_at, score });
+            }
+        }
+        result.hits.sort_by(best_first);
+        result
+    }
+}
+
+/// Highest score first, then newest, then lowest id
+fn best_first(a: &SearchHit, b: &SearchHit) -> Ordering {
+    b.score
+        .total_cmp(&a.score)
+        .then_with(|| b.created_at.cmp(&a.created_at))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+/// BM25 inverse document frequency of a term of `events` out of `total`
+fn idf(events: u64, total: u64) -> f64 {
+    let events = events as f64;
+    let others = (total as f64 - events).max(0.0);
+    (1.0 + (others + 0.5) / (events + 0.5)).ln()
+}
+
+/// BM25 term frequency component
+fn saturate(frequency: u16) -> f64 {
+    let frequency = f64::from(frequency);
+    frequency * (K1 + 1.0) / (frequency + K1)
+}
+
+/// Truncated hash of a term, the prefix of its postings
+fn term_prefix(term: &str) -> IndexKey {
+    let digest = Sha256Hash::digest(term.as_bytes());
+    IndexKey::new().with_bytes(&digest[..TERM_HASH_SIZE])
+}
@@ -0,0 +1,272 @@
+//! [`Storable`] types of the stable maps
+
+use std::borrow::Cow;
+use std::ops::RangeInclusive;
+
+use ic_stable_structures::storable::{Bound, Storable};
+
+use crate::encryption::{Sha256Hash, Sha2Digest};
+use crate::http::RelayInfo;
+use crate::identity::SignerConfig;
+use crate::nostr::event_data::EventData;
+use crate::nostr::event_id::{EventId, EVENT_ID_SIZE};
+use crate::relay::AdmissionPolicy;
+use crate::rng::{RngState, RNG_STATE_SIZE};
+use crate::signing::DataField;
+use crate::util::jsonutil::JsonUtil;
+use crate::util::time::Timestamp;
+
+/// Upper bound of the size of an [`IndexKey`]
+pub const INDEX_KEY_MAX_SIZE: u32 = 128;
+
+/// Byte key of the event map and of the secondary indexes
+///
+/// Secondary index keys are a fixed-length prefix followed by the inverted `created_at`
+/// and the [`EventId`], so ascending key order yields newest events first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IndexKey(Vec<u8>);
+
+impl IndexKey {
+    /// Empty key
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append raw bytes
+    #[inline]
+    pub fn with_bytes(mut self, bytes: &[u8]) -> Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    /// Append the SHA256 of `value`, keeping keys of variable length values fixed
+    #[inline]
+    pub fn with_hash(self, value: &str) -> Self {
+        let digest = Sha256Hash::digest(value.as_bytes());
+        self.with_bytes(&digest[..])
+    }
+
+    /// Append a big endian [`u16`]
+    #[inline]
+    pub fn with_u16(self, value: u16) -> Self {
+        self.with_bytes(&value.to_be_bytes())
+    }
+
+    /// Append a big endian [`u64`]
+    #[inline]
+    pub fn with_u64(self, value: u64) -> Self {
+        self.with_bytes(&value.to_be_bytes())
+    }
+
+    /// Append the inverted `created_at`
+    #[inline]
+    pub fn with_created_at(self, created_at: Timestamp) -> Self {
+        self.with_u64(u64::MAX - created_at.as_u64())
+    }
+
+    /// Append the [`EventId`]
+    #[inline]
+    pub fn with_id(self, id: &EventId) -> Self {
+        self.with_bytes(id.as_bytes())
+    }
+
+    /// Key of an event in the event map
+    #[inline]
+    pub fn from_id(id: &EventId) -> Self {
+        Self::new().with_id(id)
+    }
+
+    /// Range of the keys under `prefix` with `since <= created_at <= until`
+    pub fn time_range(prefix: IndexKey, since: Option<Timestamp>, until: Option<Timestamp>) -> RangeInclusive<IndexKey> {
+        let start = prefix
+            .clone()
+            .with_created_at(until.unwrap_or(Timestamp::from(u64::MAX)))
+            .with_bytes(&[u8::MIN; EVENT_ID_SIZE]);
+        let end = prefix
+            .with_created_at(since.unwrap_or(Timestamp::from(0)))
+            .with_bytes(&[u8::MAX; EVENT_ID_SIZE]);
+        start..=end
+    }
+
+    /// Range of every key starting with `prefix`
+    pub fn prefix_range(prefix: IndexKey) -> RangeInclusive<IndexKey> {
+        let padding = (INDEX_KEY_MAX_SIZE as usize).saturating_sub(prefix.0.len());
+        let end = prefix.clone().with_bytes(&vec![u8::MAX; padding]);
+        prefix..=end
+    }
+
+    /// Big endian [`u16`] at `offset`
+    pub fn read_u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.0.get(offset..offset + 2)?.try_into().ok()?;
+        Some(u16::from_be_bytes(bytes))
+    }
+
+    /// Inverted `created_at` at `offset`, see [`IndexKey::with_created_at`]
+    pub fn read_created_at(&self, offset: usize) -> Option<Timestamp> {
+        let bytes: [u8; 8] = self.0.get(offset..offset + 8)?.try_into().ok()?;
+        Some(Timestamp::from(u64::MAX - u64::from_be_bytes(bytes)))
+    }
+
+    /// The trailing [`EventId`] of the key
+    pub fn event_id(&self) -> Option<EventId> {
+        let start = self.0.len().checked_sub(EVENT_ID_SIZE)?;
+        EventId::from_slice(&self.0[start..]).ok()
+    }
+
+    /// Check if the key starts with `prefix`
+    #[inline]
+    pub fn starts_with(&self, prefix: &[u8]) -> bool {
+        self.0.starts_with(prefix)
+    }
+
+    /// Key bytes
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl Storable for IndexKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_slice())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(bytes.into_owned())
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: INDEX_KEY_MAX_SIZE,
+        is_fixed_size: false,
+    };
+}
+
+/// Value of the secondary indexes, everything lives in the [`IndexKey`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexEntry;
+
+impl Storable for IndexEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&[])
+    }
+
+    fn from_bytes(_bytes: Cow<[u8]>) -> Self {
+        Self
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 0,
+        is_fixed_size: false,
+    };
+}
+
+/// [`EventData`] stored as its JSON serialization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredEvent(pub EventData);
+
+impl Storable for StoredEvent {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.0.as_json().into_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(EventData::from_json(bytes).expect("stored event is valid JSON"))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Trace of a NIP09 deletion request
+///
+/// <https://github.com/nostr-protocol/nips/blob/master/09.md>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tombstone {
+    /// Author of the deletion request
+    pub author: DataField,
+    /// Id of the deletion request
+    pub deletion: EventId,
+    /// `created_at` of the deletion request
+    pub deleted_at: Timestamp,
+}
+
+/// Serialized size of a [`Tombstone`]
+const TOMBSTONE_SIZE: usize = 32 + EVENT_ID_SIZE + 8;
+
+impl Storable for Tombstone {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(TOMBSTONE_SIZE);
+        bytes.extend_from_slice(&self.author);
+        bytes.extend_from_slice(self.deletion.as_bytes());
+        bytes.extend_from_slice(&self.deleted_at.as_u64().to_be_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut author: DataField = [0u8; 32];
+        author.copy_from_slice(&bytes[..32]);
+        let mut deleted_at = [0u8; 8];
+        deleted_at.copy_from_slice(&bytes[32 + EVENT_ID_SIZE..TOMBSTONE_SIZE]);
+        Self {
+            author,
+            deletion: EventId::from_slice(&bytes[32..32 + EVENT_ID_SIZE]).expect("tombstone holds an event id"),
+            deleted_at: Timestamp::from(u64::from_be_bytes(deleted_at)),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: TOMBSTONE_SIZE as u32,
+        is_fixed_size: true,
+    };
+}
+
+impl Storable for RngState {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(RngState::to_bytes(self).to_vec())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        RngState::from_bytes(&bytes).expect("stored RNG state")
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: RNG_STATE_SIZE as u32,
+        is_fixed_size: true,
+    };
+}
+
+impl Storable for SignerConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_json::to_vec(self).expect("signer config serializes"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).expect("stored signer config")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for AdmissionPolicy {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_json::to_vec(self).expect("admission policy serializes"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).expect("stored admission policy")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for RelayInfo {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_json::to_vec(self).expect("relay info serializes"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).expect("stored relay info")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
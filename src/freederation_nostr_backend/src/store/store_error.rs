@@ -0,0 +1,23 @@
+use crate::nostr::event_error::EventDataError;
+use crate::nostr::event_id::EventId;
+use crate::util::time::Timestamp;
+
+/// [`EventStore`](crate::store::EventStore) error
+#[derive(thiserror::Error, Debug)]
+pub enum StoreError {
+
+    #[error("Invalid event: {0}")]
+    InvalidEvent(#[from] EventDataError),
+
+    #[error("Duplicate event {0}")]
+    Duplicate(EventId),
+
+    #[error("Superseded by newer event {0}")]
+    Superseded(EventId),
+
+    #[error("Deleted by event {0}")]
+    Deleted(EventId),
+
+    #[error("Expired at {0}")]
+    Expired(Timestamp),
+}
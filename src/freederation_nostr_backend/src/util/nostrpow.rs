@@ -53,15 +53,14 @@ pub fn get_prefixes_for_difficulty(leading_zero_bits: u8) -> Vec<String> {
 #[cfg(test)]
 pub mod tests {
     
-    use k256::sha2::digest::FixedOutput;
-
-    use crate::encryption::{sha256_from_hex};
+    use hex_conservative::FromHex;
 
     use super::*;
 
+    /// Bytes of a hex encoded hash, not hashed again
     fn sha256_to_bytearray(hexstr:&str) -> Vec<u8>
     {
-        sha256_from_hex(hexstr).unwrap().finalize_fixed().to_vec()
+        Vec::<u8>::from_hex(hexstr).unwrap()
     }
 
     #[test]
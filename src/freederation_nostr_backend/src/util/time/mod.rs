@@ -2,28 +2,30 @@ use core::fmt;
 use core::ops::{Add, Range, Sub};
 use core::str::FromStr;
 use core::time::Duration;
+use candid::CandidType;
 use serde::{Serialize, Deserialize};
 use rand_core::{RngCore};
 
 mod timesupplier;
 
 pub use self::timesupplier::TimeSupplier;
-#[cfg(feature = "std")]
-pub use self::timesupplier::{Instant, SystemTime, UNIX_EPOCH};
+#[cfg(test)]
+pub use self::timesupplier::MockTimeSupplier;
+#[cfg(any(feature = "std", target_arch = "wasm32"))]
+pub use self::timesupplier::{DefaultTimeSupplier, default_time_supplier};
 
 /// Unix timestamp in seconds
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, CandidType)]
 pub struct Timestamp(u64);
 
 impl Timestamp {
-    /// Get UNIX timestamp
-    #[cfg(feature = "std")]
+    /// Get UNIX timestamp from the [`DefaultTimeSupplier`]
+    ///
+    /// Inside a canister this is the IC system clock, `SystemTime` is not available on `wasm32`.
+    #[cfg(any(feature = "std", target_arch = "wasm32"))]
+    #[inline]
     pub fn now() -> Self {
-        let ts: u64 = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        Self(ts)
+        Self::now_with_supplier(&default_time_supplier())
     }
 
     /// Get UNIX timestamp from a specified [`TimeSupplier`]
@@ -218,6 +220,31 @@ impl Sub<u64> for Timestamp {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_time_supplier() {
+        let supplier = MockTimeSupplier::new(Timestamp::from(1_700_000_000));
+        assert_eq!(Timestamp::now_with_supplier(&supplier), Timestamp::from(1_700_000_000));
+
+        let shared = supplier.clone();
+        shared.advance(Duration::from_millis(1_500));
+        assert_eq!(Timestamp::now_with_supplier(&supplier), Timestamp::from(1_700_000_001));
+
+        supplier.set(Timestamp::from(42));
+        assert_eq!(Timestamp::now_with_supplier(&shared), Timestamp::from(42));
+    }
+
+    #[test]
+    fn test_default_time_supplier() {
+        let now = Timestamp::now();
+        assert!(now >= Timestamp::from(1_700_000_000));
+        assert!(Timestamp::now_with_supplier(&default_time_supplier()) >= now);
+    }
+}
+
 /*
 #[cfg(test)]
 mod tests {
@@ -1,5 +1,9 @@
 use core::ops::Sub;
 use core::time::Duration;
+#[cfg(test)]
+use std::cell::Cell;
+#[cfg(test)]
+use std::rc::Rc;
 //#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
@@ -73,3 +77,125 @@ impl TimeSupplier for Instant {
         now.duration_since(since).unwrap_or_default()
     }
 }
+
+/// [`TimeSupplier`] backed by the IC system clock
+///
+/// `ic_cdk::api::time()` is the nanoseconds since the UNIX epoch of the current round,
+/// it only works inside a canister.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IcTimeSupplier;
+
+#[cfg(target_arch = "wasm32")]
+impl TimeSupplier for IcTimeSupplier {
+    type Now = Duration;
+    type StartingPoint = Duration;
+
+    fn now(&self) -> Self::StartingPoint {
+        Duration::from_nanos(ic_cdk::api::time())
+    }
+
+    fn instant_now(&self) -> Self::Now {
+        Duration::from_nanos(ic_cdk::api::time())
+    }
+
+    fn starting_point(&self) -> Self::StartingPoint {
+        Duration::ZERO
+    }
+
+    fn duration_since_starting_point(&self, now: Self::StartingPoint) -> Duration {
+        now
+    }
+
+    fn elapsed_instant_since(&self, now: Self::Now, since: Self::Now) -> Duration {
+        now.saturating_sub(since)
+    }
+
+    fn elapsed_since(&self, now: Self::StartingPoint, since: Self::StartingPoint) -> Duration {
+        now.saturating_sub(since)
+    }
+}
+
+/// Deterministic [`TimeSupplier`] for tests
+///
+/// Clones share the same clock, which only moves with [`MockTimeSupplier::set`] and [`MockTimeSupplier::advance`].
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct MockTimeSupplier {
+    nanos: Rc<Cell<u64>>,
+}
+
+#[cfg(test)]
+impl MockTimeSupplier {
+    /// New clock set at `timestamp`
+    pub fn new(timestamp: Timestamp) -> Self {
+        let supplier = Self::default();
+        supplier.set(timestamp);
+        supplier
+    }
+
+    /// Set the clock at `timestamp`
+    #[inline]
+    pub fn set(&self, timestamp: Timestamp) {
+        self.nanos.set(timestamp.as_u64().saturating_mul(1_000_000_000));
+    }
+
+    /// Move the clock forward
+    #[inline]
+    pub fn advance(&self, duration: Duration) {
+        let nanos: u64 = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.nanos.set(self.nanos.get().saturating_add(nanos));
+    }
+}
+
+#[cfg(test)]
+impl TimeSupplier for MockTimeSupplier {
+    type Now = Duration;
+    type StartingPoint = Duration;
+
+    fn now(&self) -> Self::StartingPoint {
+        Duration::from_nanos(self.nanos.get())
+    }
+
+    fn instant_now(&self) -> Self::Now {
+        Duration::from_nanos(self.nanos.get())
+    }
+
+    fn starting_point(&self) -> Self::StartingPoint {
+        Duration::ZERO
+    }
+
+    fn duration_since_starting_point(&self, now: Self::StartingPoint) -> Duration {
+        now
+    }
+
+    fn elapsed_instant_since(&self, now: Self::Now, since: Self::Now) -> Duration {
+        now.saturating_sub(since)
+    }
+
+    fn elapsed_since(&self, now: Self::StartingPoint, since: Self::StartingPoint) -> Duration {
+        now.saturating_sub(since)
+    }
+}
+
+/// IC system clock of the canister
+#[cfg(target_arch = "wasm32")]
+pub type DefaultTimeSupplier = IcTimeSupplier;
+
+/// Wall clock of the host, for native builds and tests
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub type DefaultTimeSupplier = Instant;
+
+/// Instance of the [`DefaultTimeSupplier`]
+#[cfg(target_arch = "wasm32")]
+#[inline]
+pub fn default_time_supplier() -> DefaultTimeSupplier {
+    IcTimeSupplier
+}
+
+/// Instance of the [`DefaultTimeSupplier`]
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[inline]
+pub fn default_time_supplier() -> DefaultTimeSupplier {
+    Instant::now()
+}
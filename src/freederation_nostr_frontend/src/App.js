@@ -12,7 +12,8 @@ class App {
   #handleSubmit = async (e) => {
     e.preventDefault();
     const name = document.getElementById('name').value;
-    this.greeting = await freederation_nostr_backend.greet(name);
+    const result = await freederation_nostr_backend.greet(name);
+    this.greeting = 'Ok' in result ? result.Ok : Object.entries(result.Err).flat().join(': ');
     this.#render();
   };
 
@@ -0,0 +1,167 @@
+//! Calls to the relay canister
+//!
+//! Both methods take a raw NIP01 client message and return the JSON relay messages,
+//! see `handle_message` and `handle_query_message` in the canister interface.
+
+use std::future::Future;
+
+use crate::GatewayError;
+
+/// Relay canister behind the gateway
+pub trait Canister: Send + Sync + 'static {
+    /// Update call, changes are kept, used for `EVENT`
+    fn handle_message(&self, message: String) -> impl Future<Output = Result<Vec<String>, GatewayError>> + Send;
+
+    /// Query call, faster but changes are dropped, used for `REQ` and `COUNT`
+    fn handle_query_message(&self, message: String) -> impl Future<Output = Result<Vec<String>, GatewayError>> + Send;
+}
+
+#[cfg(feature = "agent")]
+pub use self::agent::AgentCanister;
+
+#[cfg(feature = "agent")]
+mod agent {
+    use candid::{Decode, Encode, Principal};
+    use ic_agent::Agent;
+
+    use super::Canister;
+    use crate::GatewayError;
+
+    /// Canister reached through an `ic-agent` [`Agent`]
+    pub struct AgentCanister {
+        agent: Agent,
+        canister_id: Principal,
+    }
+
+    impl AgentCanister {
+        /// Anonymous agent for the replica at `url`
+        ///
+        /// The root key is fetched unless `url` is the IC mainnet, whose key is built in.
+        pub async fn connect(url: &str, canister_id: &str) -> Result<Self, GatewayError> {
+            let canister_id = Principal::from_text(canister_id).map_err(|e| GatewayError::Config(e.to_string()))?;
+            let agent = Agent::builder()
+                .with_url(url)
+                .build()
+                .map_err(|e| GatewayError::Config(e.to_string()))?;
+            if !is_mainnet(url) {
+                agent.fetch_root_key().await.map_err(canister_error)?;
+            }
+            Ok(Self { agent, canister_id })
+        }
+    }
+
+    impl Canister for AgentCanister {
+        async fn handle_message(&self, message: String) -> Result<Vec<String>, GatewayError> {
+            let response = self
+                .agent
+                .update(&self.canister_id, "handle_message")
+                .with_arg(Encode!(&message).map_err(canister_error)?)
+                .call_and_wait()
+                .await
+                .map_err(canister_error)?;
+            Decode!(response.as_slice(), Vec<String>).map_err(canister_error)
+        }
+
+        async fn handle_query_message(&self, message: String) -> Result<Vec<String>, GatewayError> {
+            let response = self
+                .agent
+                .query(&self.canister_id, "handle_query_message")
+                .with_arg(Encode!(&message).map_err(canister_error)?)
+                .call()
+                .await
+                .map_err(canister_error)?;
+            Decode!(response.as_slice(), Vec<String>).map_err(canister_error)
+        }
+    }
+
+    fn is_mainnet(url: &str) -> bool {
+        ["https://ic0.app", "https://icp0.io", "https://icp-api.io"]
+            .iter()
+            .any(|mainnet| url.trim_end_matches('/') == *mainnet)
+    }
+
+    fn canister_error<E: ToString>(e: E) -> GatewayError {
+        GatewayError::Canister(e.to_string())
+    }
+}
+
+/// In-process canister for the tests
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::sync::Mutex;
+
+    use serde_json::{json, Value};
+
+    use super::Canister;
+    use crate::GatewayError;
+
+    /// Stores every event, answers `REQ` and `COUNT` with the `since`, `until` and `limit` of the first filter
+    #[derive(Default)]
+    pub struct MockCanister {
+        pub events: Mutex<Vec<Value>>,
+        /// Messages received, prefixed with `update` or `query`
+        pub calls: Mutex<Vec<String>>,
+        pub offline: Mutex<bool>,
+    }
+
+    impl MockCanister {
+        pub fn add_event(&self, id: &str, created_at: u64) -> Value {
+            let event = json!({ "id": id, "created_at": created_at, "kind": 1, "content": id });
+            self.events.lock().unwrap().push(event.clone());
+            event
+        }
+
+        fn answer(&self, call: &str, message: String) -> Result<Vec<String>, GatewayError> {
+            self.calls.lock().unwrap().push(format!("{call} {message}"));
+            if *self.offline.lock().unwrap() {
+                return Err(GatewayError::Canister(String::from("offline")));
+            }
+            let value: Value = serde_json::from_str(message.as_str()).unwrap_or_default();
+            let replies = match (value[0].as_str(), call) {
+                (Some("EVENT"), "update") => {
+                    self.events.lock().unwrap().push(value[1].clone());
+                    vec![json!(["OK", value[1]["id"], true, ""])]
+                }
+                (Some("EVENT"), _) => vec![json!(["OK", value[1]["id"], false, "restricted: query"])],
+                (Some("REQ"), _) => {
+                    let mut events = self.matching(&value[2]);
+                    if let Some(limit) = value[2]["limit"].as_u64() {
+                        events.truncate(limit as usize);
+                    }
+                    let mut replies: Vec<Value> = events.into_iter().map(|event| json!(["EVENT", value[1], event])).collect();
+                    replies.push(json!(["EOSE", value[1]]));
+                    replies
+                }
+                (Some("COUNT"), _) => vec![json!(["COUNT", value[1], { "count": self.matching(&value[2]).len() }])],
+                _ => vec![json!(["NOTICE", "invalid: unknown message"])],
+            };
+            Ok(replies.iter().map(Value::to_string).collect())
+        }
+
+        /// Newest first
+        fn matching(&self, filter: &Value) -> Vec<Value> {
+            let since: u64 = filter["since"].as_u64().unwrap_or_default();
+            let until: u64 = filter["until"].as_u64().unwrap_or(u64::MAX);
+            let mut events: Vec<Value> = self
+                .events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| (since..=until).contains(&event["created_at"].as_u64().unwrap_or_default()))
+                .cloned()
+                .collect();
+            events.sort_by_key(|event| std::cmp::Reverse(event["created_at"].as_u64()));
+            events
+        }
+    }
+
+    impl Canister for MockCanister {
+        async fn handle_message(&self, message: String) -> Result<Vec<String>, GatewayError> {
+            self.answer("update", message)
+        }
+
+        async fn handle_query_message(&self, message: String) -> Result<Vec<String>, GatewayError> {
+            self.answer("query", message)
+        }
+    }
+}
@@ -0,0 +1,22 @@
+/// Gateway error
+#[derive(thiserror::Error, Debug)]
+pub enum GatewayError {
+
+    #[error("Canister call failed: {0}")]
+    Canister(String),
+
+    #[error("WebSocket: {0}")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+
+    #[error("IO: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for GatewayError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(e))
+    }
+}
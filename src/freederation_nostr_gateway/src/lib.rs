@@ -0,0 +1,19 @@
+//! Websocket gateway of the relay canister
+//!
+//! Nostr clients connect with NIP01 websockets, their messages are forwarded unchanged to the
+//! `handle_message` endpoints of the canister and the relay messages streamed back.
+//! Subscriptions stay open by polling the canister, see [`session`].
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/01.md>
+
+pub mod canister;
+pub mod gateway_error;
+pub mod server;
+pub mod session;
+
+pub use self::canister::Canister;
+#[cfg(feature = "agent")]
+pub use self::canister::AgentCanister;
+pub use self::gateway_error::GatewayError;
+pub use self::server::serve;
+pub use self::session::{GatewayConfig, Session};
@@ -0,0 +1,88 @@
+//! `freederation_nostr_gateway <canister id> [--listen <address>] [--url <replica url>] [--poll-ms <milliseconds>]`
+//!
+//! Serves the relay canister to Nostr clients over websockets.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use freederation_nostr_gateway::{serve, AgentCanister, GatewayConfig, GatewayError};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use tokio::net::TcpListener;
+
+const USAGE: &str = "usage: freederation_nostr_gateway <canister id> [--listen <address>] [--url <replica url>] [--poll-ms <milliseconds>]";
+
+/// Command line arguments
+struct Args {
+    canister_id: String,
+    listen: String,
+    url: String,
+    config: GatewayConfig,
+}
+
+impl Args {
+    fn parse<I>(mut args: I) -> Result<Self, GatewayError>
+    where
+        I: Iterator<Item = String>,
+    {
+        let mut canister_id: Option<String> = None;
+        let mut parsed = Self {
+            canister_id: String::new(),
+            listen: String::from("127.0.0.1:7777"),
+            url: String::from("http://127.0.0.1:4943"),
+            config: GatewayConfig::default(),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| GatewayError::Config(format!("missing value of {arg}")));
+            match arg.as_str() {
+                "--listen" => parsed.listen = value()?,
+                "--url" => parsed.url = value()?,
+                "--poll-ms" => {
+                    let millis: u64 = value()?.parse().map_err(|_| GatewayError::Config(String::from("--poll-ms must be a number")))?;
+                    parsed.config.poll_interval = Duration::from_millis(millis.max(1));
+                }
+                _ if canister_id.is_none() && !arg.starts_with("--") => canister_id = Some(arg),
+                _ => return Err(GatewayError::Config(format!("unexpected argument {arg}"))),
+            }
+        }
+        parsed.canister_id = canister_id.ok_or_else(|| GatewayError::Config(String::from("missing canister id")))?;
+        Ok(parsed)
+    }
+}
+
+/// Writes the log records of the gateway to stderr, `info` and above
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+#[tokio::main]
+async fn main() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+    if let Err(e) = run().await {
+        eprintln!("{e}\n{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), GatewayError> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    let canister = AgentCanister::connect(args.url.as_str(), args.canister_id.as_str()).await?;
+    let listener = TcpListener::bind(args.listen.as_str()).await?;
+    log::info!("relaying ws://{} to canister {} at {}", listener.local_addr()?, args.canister_id, args.url);
+    serve(listener, Arc::new(canister), args.config).await
+}
@@ -0,0 +1,91 @@
+//! Websocket server, one [`Session`] per connection
+
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::MissedTickBehavior;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::canister::Canister;
+use crate::session::{GatewayConfig, Session};
+use crate::GatewayError;
+
+/// Accept connections until the listener fails
+pub async fn serve<C: Canister>(listener: TcpListener, canister: Arc<C>, config: GatewayConfig) -> Result<(), GatewayError> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let session = Session::new(canister.clone(), config.clone());
+        tokio::spawn(async move {
+            if let Err(e) = connection(stream, session).await {
+                log::warn!("{peer}: {e}");
+            }
+        });
+    }
+}
+
+/// Answer the client messages in order, polling the subscriptions in between
+async fn connection<C: Canister>(stream: TcpStream, mut session: Session<C>) -> Result<(), GatewayError> {
+    let mut websocket = tokio_tungstenite::accept_async(stream).await?;
+    let mut interval = tokio::time::interval(session.config().poll_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let replies: Vec<String> = tokio::select! {
+            message = websocket.next() => match message {
+                Some(Ok(Message::Text(text))) => session.on_message(text.as_str()).await,
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+            },
+            _ = interval.tick(), if session.subscriptions() > 0 => session.poll().await,
+        };
+        for reply in replies {
+            websocket.send(Message::Text(reply)).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::Value;
+    use tokio_tungstenite::connect_async;
+
+    use super::*;
+    use crate::canister::mock::MockCanister;
+
+    #[tokio::test]
+    async fn test_websocket_relay() {
+        let canister = Arc::new(MockCanister::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let config = GatewayConfig { poll_interval: Duration::from_millis(10), ..Default::default() };
+        tokio::spawn(serve(listener, canister.clone(), config));
+
+        let (mut client, _) = connect_async(format!("ws://{address}")).await.unwrap();
+        client.send(Message::Text(String::from(r#"["REQ","live",{}]"#))).await.unwrap();
+        let eose = next(&mut client).await;
+        assert_eq!(eose, serde_json::json!(["EOSE", "live"]));
+
+        let event = r#"["EVENT",{"id":"e1","created_at":1000}]"#;
+        client.send(Message::Text(String::from(event))).await.unwrap();
+        let mut replies = [next(&mut client).await, next(&mut client).await];
+        replies.sort_by_key(|reply| reply[0].as_str().map(String::from));
+        assert_eq!(replies[0][0], "EVENT");
+        assert_eq!(replies[0][1], "live");
+        assert_eq!(replies[0][2]["id"], "e1");
+        assert_eq!(replies[1], serde_json::json!(["OK", "e1", true, ""]));
+
+        client.close(None).await.unwrap();
+    }
+
+    /// Next relay message, failing after 5 seconds
+    async fn next<S>(client: &mut S) -> Value
+    where
+        S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+}
@@ -0,0 +1,380 @@
+//! State of a websocket connection
+//!
+//! The canister answers a `REQ` with the stored events and `EOSE`, it keeps no subscription.
+//! The session keeps the open subscriptions and polls them for their newest matching events,
+//! forwarding the ones it has not seen yet.
+//!
+//! Polls carry no `since`: `created_at` is chosen by the author, a time cursor would miss
+//! backdated events such as gift wraps and stall behind an event from the future.
+//! A poll returning a full page of unseen events asks for the next older page, up to
+//! [`MAX_POLL_PAGES`] pages, so bursts between two polls are forwarded too.
+//!
+//! An event is not forwarded live when more than [`GatewayConfig::poll_limit`] matching events
+//! are newer than it, or share its `created_at` second.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::canister::Canister;
+use crate::GatewayError;
+
+/// Pages of unseen events fetched by one poll of a subscription
+pub const MAX_POLL_PAGES: usize = 5;
+
+/// Settings shared by every connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayConfig {
+    /// Interval between two polls of the open subscriptions
+    pub poll_interval: Duration,
+    /// `limit` of the polls, at most the query limit of the canister
+    pub poll_limit: usize,
+    /// Open subscriptions per connection
+    pub max_subscriptions: usize,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            poll_limit: 100,
+            max_subscriptions: 20,
+        }
+    }
+}
+
+/// Open `REQ`
+#[derive(Debug, Clone)]
+struct Subscription {
+    filters: Vec<Value>,
+    /// Ids of the stored and forwarded events
+    seen: HashSet<String>,
+    /// Same ids, oldest first, the first ones are forgotten beyond `capacity`
+    order: VecDeque<String>,
+    /// A page and the unseen events of one poll
+    capacity: usize,
+    /// `false` until a poll learned the events stored before the subscription
+    primed: bool,
+}
+
+impl Subscription {
+    fn new(filters: Vec<Value>, poll_limit: usize) -> Self {
+        Self {
+            filters,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            capacity: poll_limit * (MAX_POLL_PAGES + 1),
+            primed: false,
+        }
+    }
+
+    /// `REQ` of the newest events until `until`, `limit` replaces the one of the client
+    fn poll_request(&self, subscription_id: &str, limit: usize, until: Option<u64>) -> String {
+        let mut request: Vec<Value> = vec![json!("REQ"), json!(subscription_id)];
+        request.extend(self.filters.iter().map(|filter| {
+            let mut filter = filter.clone();
+            if let Value::Object(fields) = &mut filter {
+                fields.insert(String::from("limit"), json!(limit));
+                if let Some(until) = until {
+                    let until: u64 = fields.get("until").and_then(Value::as_u64).map_or(until, |current| current.min(until));
+                    fields.insert(String::from("until"), json!(until));
+                }
+            }
+            filter
+        }));
+        Value::Array(request).to_string()
+    }
+
+    /// Check if `event` is new, remembering it
+    fn remember(&mut self, event: &Value) -> bool {
+        let Some(id) = event["id"].as_str() else {
+            return false;
+        };
+        if !self.seen.insert(String::from(id)) {
+            return false;
+        }
+        self.order.push_back(String::from(id));
+        if self.order.len() > self.capacity {
+            if let Some(forgotten) = self.order.pop_front() {
+                self.seen.remove(&forgotten);
+            }
+        }
+        true
+    }
+}
+
+/// Subscriptions of a connection
+pub struct Session<C: Canister> {
+    canister: Arc<C>,
+    config: GatewayConfig,
+    subscriptions: HashMap<String, Subscription>,
+}
+
+impl<C: Canister> Session<C> {
+    pub fn new(canister: Arc<C>, config: GatewayConfig) -> Self {
+        Self {
+            canister,
+            config,
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    /// Settings of the session
+    #[inline]
+    pub fn config(&self) -> &GatewayConfig {
+        &self.config
+    }
+
+    /// Number of open subscriptions
+    #[inline]
+    pub fn subscriptions(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Relay messages answering a client message
+    ///
+    /// `EVENT` is an update call, everything else a query call. `CLOSE` is not forwarded.
+    pub async fn on_message(&mut self, text: &str) -> Vec<String> {
+        let value: Value = serde_json::from_str(text).unwrap_or_default();
+        let subscription_id: Option<&str> = value.get(1).and_then(Value::as_str);
+        match (value.get(0).and_then(Value::as_str), subscription_id) {
+            (Some("EVENT"), _) => match self.canister.handle_message(String::from(text)).await {
+                Ok(replies) => replies,
+                Err(e) => vec![match value[1]["id"].as_str() {
+                    Some(id) => json!(["OK", id, false, format!("error: {e}")]).to_string(),
+                    None => notice(e),
+                }],
+            },
+            (Some("REQ"), Some(subscription_id)) => self.subscribe(subscription_id, &value, text).await,
+            (Some("CLOSE"), Some(subscription_id)) => {
+                self.subscriptions.remove(subscription_id);
+                Vec::new()
+            }
+            _ => self.canister.handle_query_message(String::from(text)).await.unwrap_or_else(|e| vec![notice(e)]),
+        }
+    }
+
+    /// Answer a `REQ` with the stored events, then keep it open once the canister sent `EOSE`
+    ///
+    /// A `REQ` reusing an open subscription id replaces it. A first poll learns the stored events
+    /// beyond the `limit` of the client, so they are not forwarded as new ones.
+    async fn subscribe(&mut self, subscription_id: &str, value: &Value, text: &str) -> Vec<String> {
+        if !self.subscriptions.contains_key(subscription_id) && self.subscriptions.len() >= self.config.max_subscriptions {
+            let message = format!("rate-limited: at most {} subscriptions", self.config.max_subscriptions);
+            return vec![json!(["CLOSED", subscription_id, message]).to_string()];
+        }
+        self.subscriptions.remove(subscription_id);
+
+        let replies: Vec<String> = match self.canister.handle_query_message(String::from(text)).await {
+            Ok(replies) => replies,
+            Err(e) => return vec![json!(["CLOSED", subscription_id, format!("error: {e}")]).to_string()],
+        };
+        let parsed: Vec<Value> = replies.iter().filter_map(|reply| serde_json::from_str(reply).ok()).collect();
+        if parsed.iter().any(|reply| reply[0] == "EOSE" && reply[1] == subscription_id) {
+            let filters: Vec<Value> = value.as_array().map(|array| array[2..].to_vec()).unwrap_or_default();
+            let mut subscription = Subscription::new(filters, self.config.poll_limit);
+            for event in stored_events(parsed) {
+                subscription.remember(&event);
+            }
+            self.subscriptions.insert(String::from(subscription_id), subscription);
+            if let Err(e) = self.fetch(subscription_id).await {
+                log::warn!("first poll of {subscription_id} failed: {e}");
+            }
+        }
+        replies
+    }
+
+    /// New events of the open subscriptions, oldest first by subscription
+    ///
+    /// A failed poll is retried on the next one.
+    pub async fn poll(&mut self) -> Vec<String> {
+        let mut messages: Vec<String> = Vec::new();
+        let ids: Vec<String> = self.subscriptions.keys().cloned().collect();
+        for subscription_id in ids {
+            match self.fetch(&subscription_id).await {
+                Ok(events) => messages.extend(events.into_iter().map(|event| json!(["EVENT", subscription_id, event]).to_string())),
+                Err(e) => log::warn!("poll of {subscription_id} failed: {e}"),
+            }
+        }
+        messages
+    }
+
+    /// Unseen events of a subscription, oldest first, none while it is not primed
+    ///
+    /// Pages are fetched newest first while a full page ends with an unseen event.
+    /// A failure after the first page keeps the events fetched so far.
+    async fn fetch(&mut self, subscription_id: &str) -> Result<Vec<Value>, GatewayError> {
+        let limit: usize = self.config.poll_limit;
+        let mut unseen: Vec<Value> = Vec::new();
+        let mut until: Option<u64> = None;
+        for page in 0..MAX_POLL_PAGES {
+            let Some(request) = self.subscriptions.get(subscription_id).map(|subscription| subscription.poll_request(subscription_id, limit, until)) else {
+                break;
+            };
+            let replies: Vec<Value> = match self.canister.handle_query_message(request).await {
+                Ok(replies) => replies.iter().filter_map(|reply| serde_json::from_str(reply).ok()).collect(),
+                Err(e) if page == 0 => return Err(e),
+                Err(e) => {
+                    log::warn!("page {page} of the poll of {subscription_id} failed: {e}");
+                    break;
+                }
+            };
+            let Some(subscription) = self.subscriptions.get_mut(subscription_id) else {
+                break;
+            };
+            let events: Vec<Value> = stored_events(replies);
+            let full: bool = events.len() >= limit;
+            let oldest: Option<u64> = events.first().and_then(|event| event["created_at"].as_u64());
+            let mut oldest_unseen: bool = false;
+            for (index, event) in events.into_iter().enumerate() {
+                if subscription.remember(&event) {
+                    oldest_unseen |= index == 0;
+                    unseen.push(event);
+                }
+            }
+            match oldest {
+                Some(oldest) if full && oldest_unseen && until.is_none_or(|until| oldest < until) => until = Some(oldest),
+                _ => break,
+            }
+        }
+        match self.subscriptions.get_mut(subscription_id) {
+            Some(subscription) if !subscription.primed => {
+                subscription.primed = true;
+                Ok(Vec::new())
+            }
+            _ => {
+                unseen.sort_by_key(|event| event["created_at"].as_u64());
+                Ok(unseen)
+            }
+        }
+    }
+}
+
+/// Events of the `EVENT` replies, oldest first
+fn stored_events(replies: Vec<Value>) -> Vec<Value> {
+    let mut events: Vec<Value> = replies
+        .into_iter()
+        .filter(|reply| reply[0] == "EVENT")
+        .filter_map(|mut reply| reply.get_mut(2).map(Value::take))
+        .collect();
+    events.sort_by_key(|event| event["created_at"].as_u64());
+    events
+}
+
+fn notice<E: ToString>(e: E) -> String {
+    json!(["NOTICE", format!("error: {}", e.to_string())]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canister::mock::MockCanister;
+
+    fn session(canister: &Arc<MockCanister>) -> Session<MockCanister> {
+        Session::new(canister.clone(), GatewayConfig { poll_limit: 3, max_subscriptions: 2, ..Default::default() })
+    }
+
+    fn ids(messages: &[String]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|message| serde_json::from_str::<Value>(message).unwrap())
+            .map(|message| format!("{} {}", message[0].as_str().unwrap(), message[2]["id"].as_str().unwrap_or_default()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_subscription_polling() {
+        let canister = Arc::new(MockCanister::default());
+        canister.add_event("older", 800);
+        canister.add_event("old", 900);
+        let mut session = session(&canister);
+
+        let replies = session.on_message(r#"["REQ","feed",{"kinds":[1],"limit":1}]"#).await;
+        assert_eq!(ids(&replies), ["EVENT old", "EOSE "]);
+        assert_eq!(session.subscriptions(), 1);
+        assert!(session.poll().await.is_empty());
+
+        canister.add_event("b", 1_002);
+        canister.add_event("a", 1_001);
+        canister.add_event("backdated", 950);
+        assert_eq!(ids(&session.poll().await), ["EVENT backdated", "EVENT a", "EVENT b"]);
+        assert!(session.poll().await.is_empty());
+
+        canister.add_event("future", 5_000);
+        assert_eq!(ids(&session.poll().await), ["EVENT future"]);
+        canister.add_event("c", 1_003);
+        assert_eq!(ids(&session.poll().await), ["EVENT c"]);
+        let polls: Vec<String> = canister.calls.lock().unwrap().iter().skip(1).cloned().collect();
+        assert_eq!(polls[0], r#"query ["REQ","feed",{"kinds":[1],"limit":3}]"#);
+        assert_eq!(polls[3], r#"query ["REQ","feed",{"kinds":[1],"limit":3,"until":950}]"#);
+
+        assert!(session.on_message(r#"["CLOSE","feed"]"#).await.is_empty());
+        assert_eq!(session.subscriptions(), 0);
+        canister.add_event("d", 1_004);
+        assert!(session.poll().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_burst_polling() {
+        let canister = Arc::new(MockCanister::default());
+        let mut session = session(&canister);
+        session.on_message(r#"["REQ","feed",{"until":2000}]"#).await;
+
+        for created_at in 1_001..=1_008 {
+            canister.add_event(&created_at.to_string(), created_at);
+        }
+        let expected: Vec<String> = (1_001..=1_008).map(|created_at| format!("EVENT {created_at}")).collect();
+        assert_eq!(ids(&session.poll().await), expected);
+        let polls: Vec<String> = canister.calls.lock().unwrap().iter().skip(2).cloned().collect();
+        assert_eq!(
+            polls,
+            [
+                r#"query ["REQ","feed",{"limit":3,"until":2000}]"#,
+                r#"query ["REQ","feed",{"limit":3,"until":1006}]"#,
+                r#"query ["REQ","feed",{"limit":3,"until":1004}]"#,
+                r#"query ["REQ","feed",{"limit":3,"until":1002}]"#,
+            ]
+        );
+        assert!(session.poll().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_forwarding() {
+        let canister = Arc::new(MockCanister::default());
+        let mut session = session(&canister);
+
+        let replies = session.on_message(r#"["EVENT",{"id":"e1","created_at":1000}]"#).await;
+        assert_eq!(replies, [r#"["OK","e1",true,""]"#]);
+        let replies = session.on_message(r#"["COUNT","c",{}]"#).await;
+        assert_eq!(replies, [r#"["COUNT","c",{"count":1}]"#]);
+        let replies = session.on_message("hello").await;
+        assert_eq!(replies, [r#"["NOTICE","invalid: unknown message"]"#]);
+        assert_eq!(
+            *canister.calls.lock().unwrap(),
+            [r#"update ["EVENT",{"id":"e1","created_at":1000}]"#, r#"query ["COUNT","c",{}]"#, "query hello"]
+        );
+
+        *canister.offline.lock().unwrap() = true;
+        let replies = session.on_message(r#"["EVENT",{"id":"e2","created_at":1000}]"#).await;
+        assert_eq!(replies, [r#"["OK","e2",false,"error: Canister call failed: offline"]"#]);
+        let replies = session.on_message(r#"["REQ","feed",{}]"#).await;
+        assert_eq!(replies, [r#"["CLOSED","feed","error: Canister call failed: offline"]"#]);
+        assert_eq!(session.subscriptions(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_limit() {
+        let canister = Arc::new(MockCanister::default());
+        let mut session = session(&canister);
+        session.on_message(r#"["REQ","a",{}]"#).await;
+        session.on_message(r#"["REQ","b",{}]"#).await;
+        let replies = session.on_message(r#"["REQ","c",{}]"#).await;
+        assert_eq!(replies, [r#"["CLOSED","c","rate-limited: at most 2 subscriptions"]"#]);
+
+        let replies = session.on_message(r#"["REQ","b",{"kinds":[1]}]"#).await;
+        assert_eq!(ids(&replies), ["EOSE "]);
+        assert_eq!(session.subscriptions(), 2);
+    }
+}